@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use clap::{Args, Subcommand};
+use clap::{Args, Subcommand, ValueEnum};
 
 // Needed a struct to have multiple sub-subcommands
 #[derive(Debug, Clone, Args)]
@@ -26,12 +26,29 @@ pub struct CleanArgs {
   /// At least keep this number of generations
   pub keep: u32,
 
+  #[arg(long, value_name = "PROFILE=N", value_parser = parse_keep_for)]
+  /// Keep this number of generations for one profile instead of `--keep`.
+  ///
+  /// PROFILE is matched against the basename of the profile path, e.g.
+  /// `system` for `/nix/var/nix/profiles/system` or `home-manager` for
+  /// `~/.local/state/nix/profiles/home-manager`. Can be given multiple times;
+  /// the last override for a profile wins.
+  pub keep_for: Vec<(String, u32)>,
+
   #[arg(long, short = 'K', default_value = "0h")]
   /// At least keep gcroots and generations in this time range since now.
   ///
   /// See the documentation of humantime for possible formats: <https://docs.rs/humantime/latest/humantime/fn.parse_duration.html>
   pub keep_since: humantime::Duration,
 
+  #[arg(long, conflicts_with = "keep_since")]
+  /// Remove gcroots and generations older than this, like
+  /// `nix-collect-garbage --delete-older-than`.
+  ///
+  /// This is the same as `--keep-since`, phrased the other way around.
+  /// `--keep` still retains at least that many generations.
+  pub older_than: Option<humantime::Duration>,
+
   /// Only print actions, without performing them
   #[arg(long, short = 'n')]
   pub dry: bool,
@@ -40,10 +57,29 @@ pub struct CleanArgs {
   #[arg(long, short)]
   pub ask: bool,
 
+  /// Print the cleanup plan as JSON, with the closure size of every path to be
+  /// removed and an estimate of the freed space
+  #[arg(long, requires = "dry", conflicts_with = "ask")]
+  pub json: bool,
+
+  /// Format of the summary printed after cleaning. With `json` or `kv`, the
+  /// cleanup plan is only printed if `--ask` is given
+  #[arg(long, value_enum, default_value_t, conflicts_with = "json")]
+  pub format: SummaryFormat,
+
+  /// Print how many generations and how much space each user would lose,
+  /// with the users holding the most reclaimable space first
+  #[arg(long, conflicts_with = "json")]
+  pub summary: bool,
+
   /// Don't run nix store --gc
   #[arg(long = "no-gc", alias = "nogc")]
   pub no_gc: bool,
 
+  /// Run nix store --gc even if no generations or gcroots were removed
+  #[arg(long, conflicts_with = "no_gc")]
+  pub force_gc: bool,
+
   /// Don't clean gcroots
   #[arg(long = "no-gcroots", alias = "nogcroots")]
   pub no_gcroots: bool,
@@ -64,11 +100,62 @@ pub struct CleanArgs {
   #[arg(long)]
   pub keep_one: bool,
 
+  /// Only remove generations older than the active one of each profile, and
+  /// never anything newer, such as a pending `nh os boot` generation. `--keep`
+  /// and `--keep-since` still apply
+  #[arg(long)]
+  pub older_than_current: bool,
+
   /// Cross filesystem boundaries when scanning gcroots
   #[arg(long, short = 'x')]
   pub cross_filesystems: bool,
 }
 
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum SummaryFormat {
+  /// A sentence for humans
+  #[default]
+  Human,
+  /// A single JSON object
+  Json,
+  /// One `key=value` pair per line
+  Kv,
+}
+
+impl CleanArgs {
+  /// The time range since now in which gcroots and generations are kept,
+  /// taking `--older-than` into account.
+  #[must_use]
+  pub fn keep_since(&self) -> humantime::Duration {
+    self.older_than.unwrap_or(self.keep_since)
+  }
+
+  /// The number of generations to keep for the profile named `name`: its
+  /// `--keep-for` override if there is one, `--keep` otherwise.
+  #[must_use]
+  pub fn keep_for_profile(&self, name: &str) -> u32 {
+    self
+      .keep_for
+      .iter()
+      .rev()
+      .find(|(profile, _)| profile == name)
+      .map_or(self.keep, |(_, keep)| *keep)
+  }
+}
+
+fn parse_keep_for(s: &str) -> Result<(String, u32), String> {
+  let (profile, keep) = s
+    .split_once('=')
+    .ok_or_else(|| format!("expected PROFILE=N, got `{s}`"))?;
+  if profile.is_empty() || profile.contains('/') {
+    return Err(format!("`{profile}` is not a profile name"));
+  }
+  let keep = keep
+    .parse()
+    .map_err(|err| format!("invalid keep count `{keep}`: {err}"))?;
+  Ok((profile.to_owned(), keep))
+}
+
 #[derive(Debug, Clone, Args)]
 pub struct CleanProfileArgs {
   #[command(flatten)]
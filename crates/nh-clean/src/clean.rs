@@ -1,19 +1,26 @@
 pub mod args;
+mod json;
+mod summary;
 
 use std::{
   collections::{BTreeMap, HashMap},
   fmt,
+  io::{self, Write},
+  ops::Range,
   path::{Path, PathBuf},
   sync::LazyLock,
-  time::SystemTime,
+  time::{Instant, SystemTime},
 };
 
 use color_eyre::{
   Result,
   eyre::{Context, ContextCompat, bail, eyre},
 };
-use inquire::Confirm;
-use nh_core::command::{Command, ElevationStrategy};
+use nh_core::{
+  command::{Command, ElevationStrategy},
+  confirm::confirm,
+  exit::Aborted,
+};
 use nix::{
   errno::Errno,
   fcntl::AtFlags,
@@ -24,6 +31,8 @@ use tracing::{Level, debug, info, instrument, span, warn};
 use walkdir::WalkDir;
 use yansi::{Color, Paint};
 
+use crate::args::SummaryFormat;
+
 // Nix impl:
 // https://github.com/NixOS/nix/blob/master/src/nix-collect-garbage/nix-collect-garbage.cc
 
@@ -45,6 +54,9 @@ static RESULT_LINK_REGEX: LazyLock<Regex> = LazyLock::new(|| {
 
 const AUTO_GCROOTS_DIR: &str = "/nix/var/nix/gcroots/auto";
 
+/// The system profile managed by NixOS and nix-darwin.
+const SYSTEM_PROFILE: &str = "/nix/var/nix/profiles/system";
+
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 struct Generation {
   number:        u32,
@@ -92,6 +104,7 @@ impl args::CleanMode {
   /// Panics if the current user's UID cannot be resolved to a user. For
   /// example, if  `User::from_uid(uid)` returns `None`.
   pub fn run(&self, elevate: ElevationStrategy) -> Result<()> {
+    let started = Instant::now();
     let mut profiles = Vec::new();
     let mut gcroots_tagged = Vec::new();
     let now = SystemTime::now();
@@ -106,7 +119,9 @@ impl args::CleanMode {
         &args.common
       },
       Self::All(args) => {
-        if !uid.is_root() {
+        // With `--elevation-strategy none`, scan as the current user and let
+        // removals fail with the real permission errors instead.
+        if !uid.is_root() && !matches!(elevate, ElevationStrategy::None) {
           nh_core::util::self_elevate(elevate);
         }
 
@@ -135,11 +150,23 @@ impl args::CleanMode {
           },
         ));
 
-        // Most unix systems start regular users at uid 1000+, but macos is
-        // special at 501+ https://en.wikipedia.org/wiki/User_identifier
-        let uid_min = if cfg!(target_os = "macos") { 501 } else { 1000 };
-        let uid_max = uid_min + 100;
-        debug!("Scanning XDG profiles for users 0, {uid_min}-{uid_max}");
+        // The nix-darwin system profile lives next to the other global
+        // profiles, but make sure it is never missed on macOS, where it is the
+        // profile people most expect `nh clean all` to trim.
+        if cfg!(target_os = "macos") {
+          let system_profile = PathBuf::from(SYSTEM_PROFILE);
+          if system_profile.is_symlink() && !profiles.contains(&system_profile)
+          {
+            debug!(?system_profile, "Adding nix-darwin system profile");
+            profiles.push(system_profile);
+          }
+        }
+
+        let uids = regular_user_uids();
+        debug!(
+          "Scanning XDG profiles for users 0, {}-{}",
+          uids.start, uids.end
+        );
 
         // Check root user (uid 0)
         if let Some(user) =
@@ -153,7 +180,7 @@ impl args::CleanMode {
         }
 
         // Check regular users in the expected range
-        for uid in uid_min..uid_max {
+        for uid in uids {
           if let Some(user) =
             nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))?
           {
@@ -199,7 +226,7 @@ impl args::CleanMode {
     for p in profiles {
       profiles_tagged.insert(
         p.clone(),
-        cleanable_generations(&p, args.keep, args.keep_since)?,
+        cleanable_generations(&p, args, args.keep_since())?,
       );
     }
 
@@ -283,9 +310,18 @@ impl args::CleanMode {
               debug!(?dur);
               match dur {
                 Err(err) => {
-                  warn!(?err, ?now, "Failed to compare time!");
+                  warn!(
+                    ?err,
+                    ?now,
+                    "gcroot is dated in the future, keeping it"
+                  );
+                  gcroots_tagged.push(GcRootTagged {
+                    src,
+                    dst,
+                    tbr: false,
+                  });
                 },
-                Ok(val) if val <= args.keep_since.into() => {
+                Ok(val) if val <= args.keep_since().into() => {
                   gcroots_tagged.push(GcRootTagged {
                     src,
                     dst,
@@ -322,99 +358,39 @@ impl args::CleanMode {
       }
     }
 
-    // Present the user the information about the paths to clean
-    println!();
-    println!("{}", Paint::new("Welcome to nh clean").bold());
-    println!(
-      "Keeping {} generation(s)",
-      Paint::new(args.keep).fg(Color::Green)
-    );
-    println!(
-      "Keeping paths newer than {}",
-      Paint::new(args.keep_since).fg(Color::Green)
-    );
-    if args.keep_one {
-      println!("Keeping all active direnv gcroots");
-    }
-    if args.no_direnv {
-      println!("Skipping all direnv gcroots");
+    if args.json {
+      let plan =
+        json::cleanup_plan(&profiles_tagged, &gcroots_tagged, &orphan_gcroots);
+      println!("{plan}");
+      return Ok(());
     }
-    println!();
-    println!("legend:");
-    println!(
-      "{}: path regular expression to be matched",
-      Paint::new("RE").fg(Color::Magenta)
-    );
-    println!("{}: path to be kept", Paint::new("OK").fg(Color::Green));
-    println!("{}: path to be removed", Paint::new("DEL").fg(Color::Red));
-    println!();
-    if !orphan_gcroots.is_empty() {
-      println!("{}", Paint::new("orphaned gcroots").fg(Color::Blue).bold());
-      for path in &orphan_gcroots {
-        println!(
-          "- {} {}",
-          Paint::new("DEL").fg(Color::Red),
-          path.to_string_lossy()
-        );
-      }
-      println!();
-    }
-    if !gcroots_tagged.is_empty() {
-      println!("{}", Paint::new("gcroots").fg(Color::Blue).bold());
-      for re in regexes {
-        println!("- {}  {}", Paint::new("RE").fg(Color::Magenta), re.as_str());
-      }
-      println!(
-        "- {}  /nix/store direct children",
-        Paint::new("RE").fg(Color::Magenta)
-      );
-      for gcroot in &gcroots_tagged {
-        if gcroot.tbr {
-          println!(
-            "- {} {}",
-            Paint::new("DEL").fg(Color::Red),
-            gcroot.dst.to_string_lossy()
-          );
-        } else {
-          println!(
-            "- {} {}",
-            Paint::new("OK ").fg(Color::Green),
-            gcroot.dst.to_string_lossy()
-          );
-        }
-      }
-      println!();
+
+    // With a machine-readable --format, stdout only carries the summary
+    let mut out: Box<dyn Write> = if args.format == SummaryFormat::Human {
+      Box::new(io::stdout())
+    } else {
+      Box::new(io::stderr())
+    };
+    if args.format == SummaryFormat::Human || args.ask {
+      print_plan(
+        &mut out,
+        args,
+        regexes,
+        &profiles_tagged,
+        &gcroots_tagged,
+        &orphan_gcroots,
+      )?;
     }
-    for (profile, generations_tagged) in &profiles_tagged {
-      println!(
-        "{}",
-        Paint::new(profile.to_string_lossy()).fg(Color::Blue).bold()
-      );
-      for (generation, tbr) in generations_tagged.iter().rev() {
-        if *tbr {
-          println!(
-            "- {} {}",
-            Paint::new("DEL").fg(Color::Red),
-            generation.path.to_string_lossy()
-          );
-        } else {
-          println!(
-            "- {} {}",
-            Paint::new("OK ").fg(Color::Green),
-            generation.path.to_string_lossy()
-          );
-        }
-      }
-      println!();
+    if args.summary {
+      summary::print_per_user(
+        &mut out,
+        &summary::removals_per_user(&profiles_tagged),
+      )?;
     }
 
     // Clean the paths
-    if args.ask
-      && !Confirm::new("Confirm the cleanup plan?")
-        .with_default(false)
-        .prompt()?
-    {
-      bail!("User rejected the cleanup plan");
+    if args.ask && !confirm("Confirm the cleanup plan?").prompt()? {
+      bail!(Aborted("User rejected the cleanup plan"));
     }
 
     if !args.dry {
@@ -437,19 +413,43 @@ impl args::CleanMode {
       }
     }
 
-    if !args.no_gc {
+    let generations_removed = profiles_tagged
+      .values()
+      .flat_map(GenerationsTagged::values)
+      .filter(|tbr| **tbr)
+      .count();
+    let gcroots_removed = orphan_gcroots.len()
+      + gcroots_tagged.iter().filter(|gcroot| gcroot.tbr).count();
+    let removed_nothing = generations_removed == 0 && gcroots_removed == 0;
+
+    let mut freed_bytes = 0;
+    if removed_nothing && !args.no_gc && !args.force_gc {
+      info!(
+        "Nothing was removed, skipping garbage collection (use --force-gc to \
+         run it anyway)"
+      );
+    } else if !args.no_gc {
       let mut gc_args = vec!["store", "gc"];
       if let Some(ref max) = args.max {
         gc_args.push("--max");
         gc_args.push(max.as_str());
       }
-      Command::new("nix")
+      let gc = Command::new("nix")
         .args(gc_args)
         .dry(args.dry)
         .message("Performing garbage collection on the nix store")
         .show_output(true)
         .with_required_env()
-        .run()?;
+        .run_with_output()?;
+      if let Some((exit_status, output)) = gc {
+        if !exit_status.success() {
+          bail!("Garbage collection exited with status {exit_status:?}");
+        }
+        freed_bytes = summary::freed_bytes(&output).unwrap_or_else(|| {
+          debug!("Failed to read the freed space from the nix store gc output");
+          0
+        });
+      }
     }
 
     if args.optimise {
@@ -462,10 +462,163 @@ impl args::CleanMode {
         .run()?;
     }
 
+    let summary = summary::CleanSummary {
+      generations_removed,
+      gcroots_removed,
+      freed_bytes,
+      duration: started.elapsed(),
+      dry: args.dry,
+    };
+    println!("{}", summary.render(args.format));
+
     Ok(())
   }
 }
 
+/// Print which paths the cleanup keeps and which it removes.
+fn print_plan(
+  out: &mut impl Write,
+  args: &args::CleanArgs,
+  regexes: &[&Regex],
+  profiles_tagged: &ProfilesTagged,
+  gcroots_tagged: &[GcRootTagged],
+  orphan_gcroots: &[PathBuf],
+) -> io::Result<()> {
+  writeln!(out)?;
+  writeln!(out, "{}", Paint::new("Welcome to nh clean").bold())?;
+  writeln!(
+    out,
+    "Keeping {} generation(s)",
+    Paint::new(args.keep).fg(Color::Green)
+  )?;
+  for (profile, keep) in &args.keep_for {
+    writeln!(
+      out,
+      "Keeping {} generation(s) of {profile}",
+      Paint::new(keep).fg(Color::Green)
+    )?;
+  }
+  writeln!(
+    out,
+    "Keeping paths newer than {}",
+    Paint::new(args.keep_since()).fg(Color::Green)
+  )?;
+  if args.older_than_current {
+    writeln!(out, "Keeping generations from the active one onwards")?;
+  }
+  if args.keep_one {
+    writeln!(out, "Keeping all active direnv gcroots")?;
+  }
+  if args.no_direnv {
+    writeln!(out, "Skipping all direnv gcroots")?;
+  }
+  writeln!(out)?;
+  writeln!(out, "legend:")?;
+  writeln!(
+    out,
+    "{}: path regular expression to be matched",
+    Paint::new("RE").fg(Color::Magenta)
+  )?;
+  writeln!(
+    out,
+    "{}: path to be kept",
+    Paint::new("OK").fg(Color::Green)
+  )?;
+  writeln!(
+    out,
+    "{}: path to be removed",
+    Paint::new("DEL").fg(Color::Red)
+  )?;
+  writeln!(out)?;
+  if !orphan_gcroots.is_empty() {
+    writeln!(
+      out,
+      "{}",
+      Paint::new("orphaned gcroots").fg(Color::Blue).bold()
+    )?;
+    for path in orphan_gcroots {
+      writeln!(
+        out,
+        "- {} {}",
+        Paint::new("DEL").fg(Color::Red),
+        path.to_string_lossy()
+      )?;
+    }
+    writeln!(out)?;
+  }
+  if !gcroots_tagged.is_empty() {
+    writeln!(out, "{}", Paint::new("gcroots").fg(Color::Blue).bold())?;
+    for re in regexes {
+      writeln!(
+        out,
+        "- {}  {}",
+        Paint::new("RE").fg(Color::Magenta),
+        re.as_str()
+      )?;
+    }
+    writeln!(
+      out,
+      "- {}  /nix/store direct children",
+      Paint::new("RE").fg(Color::Magenta)
+    )?;
+    for gcroot in gcroots_tagged {
+      if gcroot.tbr {
+        writeln!(
+          out,
+          "- {} {}",
+          Paint::new("DEL").fg(Color::Red),
+          gcroot.dst.to_string_lossy()
+        )?;
+      } else {
+        writeln!(
+          out,
+          "- {} {}",
+          Paint::new("OK ").fg(Color::Green),
+          gcroot.dst.to_string_lossy()
+        )?;
+      }
+    }
+    writeln!(out)?;
+  }
+  for (profile, generations_tagged) in profiles_tagged {
+    writeln!(
+      out,
+      "{}",
+      Paint::new(profile.to_string_lossy()).fg(Color::Blue).bold()
+    )?;
+    for (generation, tbr) in generations_tagged.iter().rev() {
+      if *tbr {
+        writeln!(
+          out,
+          "- {} {}",
+          Paint::new("DEL").fg(Color::Red),
+          generation.path.to_string_lossy()
+        )?;
+      } else {
+        writeln!(
+          out,
+          "- {} {}",
+          Paint::new("OK ").fg(Color::Green),
+          generation.path.to_string_lossy()
+        )?;
+      }
+    }
+    writeln!(out)?;
+  }
+  Ok(())
+}
+
+/// The uids of regular (non-system) users whose XDG profiles are scanned by
+/// `nh clean all`.
+///
+/// Most unix systems start regular users at uid 1000+, but macOS is special at
+/// 501+ <https://en.wikipedia.org/wiki/User_identifier>. The nix-darwin build
+/// users (`_nixbld*`) live at 300+ and are therefore never included.
+const fn regular_user_uids() -> Range<u32> {
+  let uid_min = if cfg!(target_os = "macos") { 501 } else { 1000 };
+  uid_min..uid_min + 100
+}
+
 #[instrument(ret, level = "debug")]
 fn profiles_in_dir<P: AsRef<Path> + fmt::Debug>(dir: P) -> Vec<PathBuf> {
   let mut res = Vec::new();
@@ -508,7 +661,7 @@ fn profiles_in_dir<P: AsRef<Path> + fmt::Debug>(dir: P) -> Vec<PathBuf> {
 #[instrument(err, level = "debug")]
 fn cleanable_generations(
   profile: &Path,
-  keep: u32,
+  args: &args::CleanArgs,
   keep_since: humantime::Duration,
 ) -> Result<GenerationsTagged> {
   let name = profile
@@ -516,6 +669,7 @@ fn cleanable_generations(
     .context("Checking profile's name")?
     .to_str()
     .context("Profile name is not valid UTF-8")?;
+  let keep = args.keep_for_profile(name);
 
   let mut result = GenerationsTagged::new();
 
@@ -562,11 +716,100 @@ fn cleanable_generations(
     }
   }
 
-  let now = SystemTime::now();
-  for (generation, tbr) in &mut result {
+  keep_recent_generations(&mut result, SystemTime::now(), keep_since);
+
+  for (_, tbr) in result.iter_mut().rev().take(keep as _) {
+    *tbr = false;
+  }
+
+  let active = active_store_paths(profile);
+  keep_active_generations(&mut result, &active);
+  if args.older_than_current {
+    keep_generations_from_current(&mut result, &active);
+  }
+
+  debug!("{:#?}", result);
+  Ok(result)
+}
+
+/// Store paths that are in use and must never be removed, whatever `--keep`
+/// says: the running and the booted system, and the generation `profile`
+/// points to, which is the active one for Home Manager and nix-darwin.
+fn active_store_paths(profile: &Path) -> Vec<PathBuf> {
+  [
+    Path::new("/run/current-system"),
+    Path::new("/run/booted-system"),
+    profile,
+  ]
+  .into_iter()
+  .filter_map(|path| path.canonicalize().ok())
+  .collect()
+}
+
+/// Untag generations whose store path is one of `active`.
+fn keep_active_generations(
+  generations: &mut GenerationsTagged,
+  active: &[PathBuf],
+) {
+  for (generation, tbr) in generations {
+    if *tbr
+      && generation
+        .path
+        .canonicalize()
+        .is_ok_and(|path| active.contains(&path))
+    {
+      debug!(?generation, "Generation is in use, keeping it");
+      *tbr = false;
+    }
+  }
+}
+
+/// Untag every generation that is not strictly older than the oldest one whose
+/// store path is one of `active`. If none of them is active, all are kept.
+fn keep_generations_from_current(
+  generations: &mut GenerationsTagged,
+  active: &[PathBuf],
+) {
+  let current = generations
+    .keys()
+    .filter(|generation| {
+      generation
+        .path
+        .canonicalize()
+        .is_ok_and(|path| active.contains(&path))
+    })
+    .map(|generation| generation.number)
+    .min();
+
+  if current.is_none() {
+    warn!("Could not find the active generation, keeping all generations");
+  }
+  for (generation, tbr) in generations {
+    if current.is_none_or(|current| generation.number >= current) {
+      *tbr = false;
+    }
+  }
+}
+
+/// Untag generations modified within `keep_since` of `now`.
+///
+/// Generations dated in the future (clock skew, restored backups) are treated
+/// as newer than `now` and kept.
+fn keep_recent_generations(
+  generations: &mut GenerationsTagged,
+  now: SystemTime,
+  keep_since: humantime::Duration,
+) {
+  for (generation, tbr) in generations {
     match now.duration_since(generation.last_modified) {
       Err(err) => {
-        warn!(?err, ?now, ?generation, "Failed to compare time!");
+        warn!(
+          ?err,
+          ?now,
+          ?generation,
+          "Generation is dated in the future, keeping it"
+        );
+        *tbr = false;
       },
       Ok(val) if val <= keep_since.into() => {
         *tbr = false;
@@ -574,13 +817,6 @@ fn cleanable_generations(
       Ok(_) => {},
     }
   }
-
-  for (_, tbr) in result.iter_mut().rev().take(keep as _) {
-    *tbr = false;
-  }
-
-  debug!("{:#?}", result);
-  Ok(result)
 }
 
 fn is_nix_store_direct_child(path: &Path) -> bool {
@@ -637,6 +873,182 @@ fn remove_path_nofail(path: &Path) {
 mod tests {
   use super::*;
 
+  #[derive(Debug, clap::Parser)]
+  struct CleanCli {
+    #[command(flatten)]
+    args: args::CleanArgs,
+  }
+
+  fn parse_clean_args(argv: &[&str]) -> Result<args::CleanArgs, clap::Error> {
+    use clap::Parser;
+
+    CleanCli::try_parse_from(
+      std::iter::once("clean").chain(argv.iter().copied()),
+    )
+    .map(|cli| cli.args)
+  }
+
+  fn generation(number: u32, last_modified: SystemTime) -> Generation {
+    Generation {
+      number,
+      last_modified,
+      path: PathBuf::from(format!(
+        "/nix/var/nix/profiles/system-{number}-link"
+      )),
+    }
+  }
+
+  /// A `system` profile in a tempdir with generations 1 to `count`, pointing
+  /// to generation `current`.
+  fn profile_with_generations(
+    count: u32,
+    current: u32,
+  ) -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().expect("tempdir");
+    for number in 1..=count {
+      let store_path = dir.path().join(format!("system-{number}"));
+      std::fs::create_dir(&store_path).expect("store path");
+      std::os::unix::fs::symlink(
+        store_path,
+        dir.path().join(format!("system-{number}-link")),
+      )
+      .expect("generation link");
+    }
+    let profile = dir.path().join("system");
+    std::os::unix::fs::symlink(format!("system-{current}-link"), &profile)
+      .expect("profile");
+    (dir, profile)
+  }
+
+  #[test]
+  fn future_dated_generation_is_kept() {
+    let now = SystemTime::now();
+    let hour = std::time::Duration::from_hours(1);
+
+    let mut generations = GenerationsTagged::new();
+    generations.insert(generation(1, now - 48 * hour), true);
+    generations.insert(generation(2, now + 48 * hour), true);
+
+    keep_recent_generations(
+      &mut generations,
+      now,
+      humantime::Duration::from(std::time::Duration::ZERO),
+    );
+
+    let tagged: Vec<_> = generations
+      .iter()
+      .map(|(g, tbr)| (g.number, *tbr))
+      .collect();
+    assert_eq!(tagged, [(1, true), (2, false)]);
+  }
+
+  #[test]
+  fn keep_zero_preserves_active_generation() {
+    let (_dir, profile) = profile_with_generations(2, 2);
+
+    let args = parse_clean_args(&["--keep", "0"]).expect("valid arguments");
+    let generations = cleanable_generations(&profile, &args, args.keep_since())
+      .expect("generations");
+
+    let tagged: Vec<_> = generations
+      .iter()
+      .map(|(g, tbr)| (g.number, *tbr))
+      .collect();
+    assert_eq!(tagged, [(1, true), (2, false)]);
+  }
+
+  #[test]
+  fn older_than_current_keeps_newer_generations() {
+    let (_dir, profile) = profile_with_generations(3, 2);
+
+    let args = parse_clean_args(&["--keep", "0", "--older-than-current"])
+      .expect("valid arguments");
+    let generations = cleanable_generations(&profile, &args, args.keep_since())
+      .expect("generations");
+
+    let tagged: Vec<_> = generations
+      .iter()
+      .map(|(g, tbr)| (g.number, *tbr))
+      .collect();
+    assert_eq!(tagged, [(1, true), (2, false), (3, false)]);
+  }
+
+  #[test]
+  fn older_than_sets_keep_since() {
+    let args = parse_clean_args(&["--older-than", "30d", "--keep", "3"])
+      .expect("valid arguments");
+    assert_eq!(
+      std::time::Duration::from(args.keep_since()),
+      std::time::Duration::from_hours(30 * 24)
+    );
+    assert_eq!(args.keep, 3);
+  }
+
+  #[test]
+  fn keep_since_is_used_without_older_than() {
+    let args =
+      parse_clean_args(&["--keep-since", "4d"]).expect("valid arguments");
+    assert_eq!(
+      std::time::Duration::from(args.keep_since()),
+      std::time::Duration::from_hours(4 * 24)
+    );
+  }
+
+  #[test]
+  fn keep_for_overrides_keep_by_profile_basename() {
+    let args = parse_clean_args(&[
+      "--keep",
+      "3",
+      "--keep-for",
+      "system=10",
+      "--keep-for",
+      "home-manager=2",
+      "--keep-for",
+      "system=5",
+    ])
+    .expect("valid arguments");
+
+    assert_eq!(args.keep_for_profile("system"), 5);
+    assert_eq!(args.keep_for_profile("home-manager"), 2);
+    assert_eq!(args.keep_for_profile("profile"), 3);
+  }
+
+  #[test]
+  fn keep_for_rejects_malformed_overrides() {
+    for value in [
+      "system",
+      "=3",
+      "system=many",
+      "/nix/var/nix/profiles/system=3",
+    ] {
+      assert!(
+        parse_clean_args(&["--keep-for", value]).is_err(),
+        "{value} should be rejected"
+      );
+    }
+  }
+
+  #[test]
+  fn older_than_conflicts_with_keep_since() {
+    assert!(
+      parse_clean_args(&["--older-than", "30d", "--keep-since", "4d"]).is_err()
+    );
+  }
+
+  #[test]
+  fn regular_user_uids_match_platform_defaults() {
+    let uids = regular_user_uids();
+    if cfg!(target_os = "macos") {
+      // The first user created on a Mac gets uid 501
+      assert!(uids.contains(&501));
+      assert!(!uids.contains(&300), "nixbld users must be excluded");
+    } else {
+      assert!(uids.contains(&1000));
+      assert!(!uids.contains(&999));
+    }
+    assert!(!uids.contains(&0));
+  }
+
   #[test]
   fn store_direct_child_accepts_top_level_entry() {
     assert!(is_nix_store_direct_child(Path::new(
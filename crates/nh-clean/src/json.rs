@@ -0,0 +1,187 @@
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{
+    Mutex,
+    atomic::{AtomicUsize, Ordering},
+  },
+  thread,
+};
+
+use nh_core::util::{get_closure_size, get_closure_size_excluding};
+use serde_json::{Value, json};
+use tracing::debug;
+
+use crate::{GcRootTagged, ProfilesTagged};
+
+/// How many `nix path-info` processes are run at the same time.
+const MAX_PARALLEL_QUERIES: usize = 4;
+
+/// Build the `--dry --json` cleanup plan, including the closure size of every
+/// path tagged for removal and an estimate of the space the cleanup frees.
+///
+/// The estimate leaves out store paths that the generations and gcroots that
+/// are kept still refer to. Other roots, e.g. the running system, can keep
+/// more paths alive, so it can still be too high.
+pub fn cleanup_plan(
+  profiles: &ProfilesTagged,
+  gcroots: &[GcRootTagged],
+  orphans: &[PathBuf],
+) -> Value {
+  let roots = |remove: bool| -> Vec<&Path> {
+    profiles
+      .values()
+      .flat_map(|generations| {
+        generations
+          .iter()
+          .filter(move |(_, tbr)| **tbr == remove)
+          .map(|(generation, _)| generation.path.as_path())
+      })
+      .chain(
+        gcroots
+          .iter()
+          .filter(|gcroot| gcroot.tbr == remove)
+          .map(|gcroot| gcroot.dst.as_path()),
+      )
+      .filter(|path| path.exists())
+      .collect()
+  };
+  let to_remove = roots(true);
+  let kept = roots(false);
+
+  let sizes = closure_sizes(&to_remove);
+  let estimated_freed = get_closure_size_excluding(&to_remove, &kept)
+    .map_err(|err| debug!("Failed to estimate freed space: {err}"))
+    .ok();
+
+  render_plan(profiles, gcroots, orphans, &sizes, estimated_freed)
+}
+
+/// Query the closure size of each path, a few at a time. Paths whose size
+/// cannot be determined are left out.
+fn closure_sizes(paths: &[&Path]) -> HashMap<PathBuf, u64> {
+  let next = AtomicUsize::new(0);
+  let sizes = Mutex::new(HashMap::new());
+
+  thread::scope(|scope| {
+    for _ in 0..MAX_PARALLEL_QUERIES.min(paths.len()) {
+      scope.spawn(|| {
+        while let Some(path) = paths.get(next.fetch_add(1, Ordering::Relaxed)) {
+          match get_closure_size(path) {
+            Ok(size) => {
+              if let Ok(mut sizes) = sizes.lock() {
+                sizes.insert(path.to_path_buf(), size);
+              }
+            },
+            Err(err) => debug!(?path, "Failed to get closure size: {err}"),
+          }
+        }
+      });
+    }
+  });
+
+  sizes.into_inner().unwrap_or_default()
+}
+
+fn render_plan(
+  profiles: &ProfilesTagged,
+  gcroots: &[GcRootTagged],
+  orphans: &[PathBuf],
+  sizes: &HashMap<PathBuf, u64>,
+  estimated_freed: Option<u64>,
+) -> Value {
+  let closure_size =
+    |path: &Path, tbr: bool| tbr.then(|| sizes.get(path)).flatten().copied();
+
+  let mut profile_paths: Vec<&PathBuf> = profiles.keys().collect();
+  profile_paths.sort();
+
+  let profiles: Vec<Value> = profile_paths
+    .into_iter()
+    .map(|profile| {
+      let generations: Vec<Value> = profiles[profile]
+        .iter()
+        .map(|(generation, tbr)| {
+          json!({
+            "number": generation.number,
+            "path": generation.path,
+            "remove": tbr,
+            "closure_size": closure_size(&generation.path, *tbr),
+          })
+        })
+        .collect();
+      json!({ "profile": profile, "generations": generations })
+    })
+    .collect();
+
+  let gcroots: Vec<Value> = gcroots
+    .iter()
+    .map(|gcroot| {
+      json!({
+        "path": gcroot.src,
+        "target": gcroot.dst,
+        "remove": gcroot.tbr,
+        "closure_size": closure_size(&gcroot.dst, gcroot.tbr),
+      })
+    })
+    .collect();
+
+  json!({
+    "profiles": profiles,
+    "gcroots": gcroots,
+    "orphaned_gcroots": orphans,
+    "estimated_freed_bytes": estimated_freed,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::SystemTime;
+
+  use super::*;
+  use crate::{Generation, GenerationsTagged};
+
+  fn generation(number: u32) -> Generation {
+    Generation {
+      number,
+      last_modified: SystemTime::UNIX_EPOCH,
+      path: PathBuf::from(format!(
+        "/nix/var/nix/profiles/system-{number}-link"
+      )),
+    }
+  }
+
+  #[test]
+  fn plan_reports_sizes_of_removed_paths_only() {
+    let mut generations = GenerationsTagged::new();
+    generations.insert(generation(1), true);
+    generations.insert(generation(2), false);
+    let mut profiles = ProfilesTagged::new();
+    profiles.insert(PathBuf::from("/nix/var/nix/profiles/system"), generations);
+
+    let gcroots = [GcRootTagged {
+      src: PathBuf::from("/nix/var/nix/gcroots/auto/abc"),
+      dst: PathBuf::from("/home/user/project/result"),
+      tbr: true,
+    }];
+    let orphans = [PathBuf::from("/nix/var/nix/gcroots/auto/def")];
+
+    let sizes = HashMap::from([
+      (PathBuf::from("/nix/var/nix/profiles/system-1-link"), 100),
+      (PathBuf::from("/nix/var/nix/profiles/system-2-link"), 200),
+      (PathBuf::from("/home/user/project/result"), 50),
+    ]);
+
+    let plan = render_plan(&profiles, &gcroots, &orphans, &sizes, Some(120));
+
+    let generations = &plan["profiles"][0]["generations"];
+    assert_eq!(generations[0]["number"], 1);
+    assert_eq!(generations[0]["remove"], true);
+    assert_eq!(generations[0]["closure_size"], 100);
+    assert_eq!(generations[1]["remove"], false);
+    assert!(generations[1]["closure_size"].is_null());
+    assert_eq!(plan["gcroots"][0]["closure_size"], 50);
+    assert_eq!(plan["orphaned_gcroots"][0], "/nix/var/nix/gcroots/auto/def");
+    assert_eq!(plan["estimated_freed_bytes"], 120);
+  }
+}
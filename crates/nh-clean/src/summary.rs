@@ -0,0 +1,286 @@
+use std::{
+  collections::BTreeMap,
+  io::{self, Write},
+  os::unix::fs::MetadataExt,
+  path::Path,
+  sync::LazyLock,
+  time::Duration,
+};
+
+use nh_core::util::{format_size, get_closure_size_excluding};
+use nix::unistd::{Uid, User};
+use regex::Regex;
+use serde_json::json;
+use tracing::debug;
+use yansi::{Color, Paint};
+
+use crate::{ProfilesTagged, args::SummaryFormat};
+
+static FREED_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+  #[allow(clippy::expect_used)]
+  Regex::new(r"([\d.]+) (bytes|B|KiB|MiB|GiB|TiB) freed")
+    .expect("Failed to compile freed space regex")
+});
+
+/// What a cleanup removed, printed once it is done.
+#[derive(Debug)]
+pub struct CleanSummary {
+  /// Generations removed, or that would be removed with `--dry`
+  pub generations_removed: usize,
+  /// Gcroots removed, including orphaned ones
+  pub gcroots_removed:     usize,
+  /// Bytes freed by garbage collection, 0 if it did not run
+  pub freed_bytes:         u64,
+  pub duration:            Duration,
+  /// Nothing was removed, as `--dry` was given
+  pub dry:                 bool,
+}
+
+impl CleanSummary {
+  /// Render the summary in `format`. `kv` prints one `key=value` pair per
+  /// line, using the same keys as `json`.
+  #[must_use]
+  pub fn render(&self, format: SummaryFormat) -> String {
+    let duration_ms = self.duration.as_millis();
+    match format {
+      SummaryFormat::Human if self.dry => {
+        format!(
+          "Would remove {} generation(s) and {} gcroot(s)",
+          self.generations_removed, self.gcroots_removed
+        )
+      },
+      SummaryFormat::Human => {
+        format!(
+          "Removed {} generation(s) and {} gcroot(s), freed {} in {:.1}s",
+          self.generations_removed,
+          self.gcroots_removed,
+          format_size(self.freed_bytes),
+          self.duration.as_secs_f64()
+        )
+      },
+      SummaryFormat::Json => {
+        json!({
+          "dry": self.dry,
+          "generations_removed": self.generations_removed,
+          "gcroots_removed": self.gcroots_removed,
+          "freed_bytes": self.freed_bytes,
+          "duration_ms": duration_ms,
+        })
+        .to_string()
+      },
+      SummaryFormat::Kv => {
+        [
+          format!("dry={}", self.dry),
+          format!("generations_removed={}", self.generations_removed),
+          format!("gcroots_removed={}", self.gcroots_removed),
+          format!("freed_bytes={}", self.freed_bytes),
+          format!("duration_ms={duration_ms}"),
+        ]
+        .join("\n")
+      },
+    }
+  }
+}
+
+/// The bytes freed according to the output of `nix store gc`, which ends
+/// with a line like `42 store paths deleted, 1.50 GiB freed`.
+#[must_use]
+#[expect(
+  clippy::cast_possible_truncation,
+  clippy::cast_sign_loss,
+  reason = "Sizes are non-negative and far below u64::MAX"
+)]
+pub fn freed_bytes(gc_output: &str) -> Option<u64> {
+  let captures = FREED_REGEX.captures_iter(gc_output).last()?;
+  let size: f64 = captures[1].parse().ok()?;
+  let unit: f64 = match &captures[2] {
+    "KiB" => 1024.0,
+    "MiB" => 1024.0 * 1024.0,
+    "GiB" => 1024.0 * 1024.0 * 1024.0,
+    "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+    _ => 1.0,
+  };
+  Some((size * unit).round() as u64)
+}
+
+/// The generations of one user's profiles tagged for removal.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UserRemovals {
+  pub user:        String,
+  pub generations: usize,
+  /// The space removing the generations frees, leaving out what the kept
+  /// generations still use, if it could be queried
+  pub size:        Option<u64>,
+}
+
+/// Group the generations tagged for removal by the user owning their
+/// profile, with the users holding the most reclaimable space first.
+pub fn removals_per_user(profiles: &ProfilesTagged) -> Vec<UserRemovals> {
+  let kept: Vec<&Path> = profiles
+    .values()
+    .flat_map(|generations| generations.iter())
+    .filter(|(_, tbr)| !**tbr)
+    .map(|(generation, _)| generation.path.as_path())
+    .collect();
+
+  let mut per_user: BTreeMap<String, Vec<&Path>> = BTreeMap::new();
+  for (profile, generations) in profiles {
+    per_user.entry(profile_owner(profile)).or_default().extend(
+      generations
+        .iter()
+        .filter(|(_, tbr)| **tbr)
+        .map(|(generation, _)| generation.path.as_path()),
+    );
+  }
+
+  let mut removals: Vec<UserRemovals> = per_user
+    .into_iter()
+    .map(|(user, paths)| {
+      let size = get_closure_size_excluding(&paths, &kept)
+        .map_err(|err| debug!(%user, "Failed to estimate freed space: {err}"))
+        .ok();
+      UserRemovals {
+        user,
+        generations: paths.len(),
+        size,
+      }
+    })
+    .collect();
+  sort_by_size(&mut removals);
+  removals
+}
+
+/// The name of the user owning `profile`, or its uid if it has no name.
+fn profile_owner(profile: &Path) -> String {
+  let Ok(metadata) = profile.symlink_metadata() else {
+    return "unknown".to_owned();
+  };
+  User::from_uid(Uid::from_raw(metadata.uid()))
+    .ok()
+    .flatten()
+    .map_or_else(|| metadata.uid().to_string(), |user| user.name)
+}
+
+fn sort_by_size(removals: &mut [UserRemovals]) {
+  removals.sort_by(|a, b| {
+    b.size
+      .cmp(&a.size)
+      .then_with(|| b.generations.cmp(&a.generations))
+  });
+}
+
+/// Print the per-user breakdown of `--summary` to `out`.
+pub fn print_per_user(
+  out: &mut impl Write,
+  removals: &[UserRemovals],
+) -> io::Result<()> {
+  writeln!(
+    out,
+    "{}",
+    Paint::new("per-user summary").fg(Color::Blue).bold()
+  )?;
+  for removal in removals {
+    writeln!(
+      out,
+      "- {}: {} generation(s), {}",
+      Paint::new(&removal.user).bold(),
+      removal.generations,
+      removal
+        .size
+        .map_or_else(|| "unknown size".to_owned(), format_size)
+    )?;
+  }
+  writeln!(out)
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Fine in tests")]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn users_with_most_reclaimable_space_come_first() {
+    let user = |user: &str, generations, size| {
+      UserRemovals {
+        user: user.to_owned(),
+        generations,
+        size,
+      }
+    };
+    let mut removals = vec![
+      user("alice", 3, Some(100)),
+      user("bob", 5, None),
+      user("root", 1, Some(4000)),
+    ];
+
+    sort_by_size(&mut removals);
+
+    let users: Vec<_> = removals.iter().map(|r| r.user.as_str()).collect();
+    assert_eq!(users, ["root", "alice", "bob"]);
+  }
+
+  #[test]
+  fn freed_bytes_are_read_from_gc_output() {
+    let output = "finding garbage collector roots...\ndeleting unused \
+                  links...\n42 store paths deleted, 1.50 MiB freed\n";
+    assert_eq!(freed_bytes(output), Some(1_572_864));
+    assert_eq!(freed_bytes("0 store paths deleted, 0 bytes freed"), Some(0));
+    assert_eq!(freed_bytes("error: cannot connect to daemon"), None);
+  }
+
+  #[test]
+  fn kv_summary_has_one_pair_per_line() {
+    let summary = CleanSummary {
+      generations_removed: 3,
+      gcroots_removed:     1,
+      freed_bytes:         2048,
+      duration:            Duration::from_millis(1500),
+      dry:                 false,
+    };
+
+    assert_eq!(
+      summary.render(SummaryFormat::Kv),
+      [
+        "dry=false",
+        "generations_removed=3",
+        "gcroots_removed=1",
+        "freed_bytes=2048",
+        "duration_ms=1500",
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn dry_human_summary_says_what_would_be_removed() {
+    let summary = CleanSummary {
+      generations_removed: 3,
+      gcroots_removed:     1,
+      freed_bytes:         0,
+      duration:            Duration::from_millis(1500),
+      dry:                 true,
+    };
+
+    assert_eq!(
+      summary.render(SummaryFormat::Human),
+      "Would remove 3 generation(s) and 1 gcroot(s)"
+    );
+  }
+
+  #[test]
+  fn dry_machine_summaries_say_nothing_was_removed() {
+    let summary = CleanSummary {
+      generations_removed: 3,
+      gcroots_removed:     1,
+      freed_bytes:         0,
+      duration:            Duration::from_millis(1500),
+      dry:                 true,
+    };
+
+    assert!(summary.render(SummaryFormat::Kv).starts_with("dry=true\n"));
+    let json: serde_json::Value =
+      serde_json::from_str(&summary.render(SummaryFormat::Json)).unwrap();
+    assert_eq!(json["dry"], true);
+    assert_eq!(json["generations_removed"], 3);
+  }
+}
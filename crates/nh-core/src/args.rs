@@ -1,9 +1,12 @@
-use std::path::PathBuf;
+use std::{ffi::OsString, path::PathBuf};
 
 use clap::{Args, ValueEnum};
-use nh_installable::InstallableArgs;
+use color_eyre::{Result, eyre::bail};
+use nh_installable::{Installable, InstallableArgs};
 use tracing::warn;
 
+use crate::{flake_config, timings::TimingsFormat, util};
+
 #[derive(Debug, Args)]
 pub struct CommonRebuildArgs {
   /// Only print actions, without performing them
@@ -18,25 +21,86 @@ pub struct CommonRebuildArgs {
   pub installable: InstallableArgs,
 
   /// Don't use nix-output-monitor for the build process
-  #[arg(long)]
+  #[arg(
+    long,
+    env = "NH_NO_NOM",
+    value_parser = clap::builder::BoolishValueParser::new()
+  )]
   pub no_nom: bool,
 
+  /// Extra arguments for nix-output-monitor, split like a shell would, e.g.
+  /// `--nom-args="--foo bar"`. Ignored when nom is disabled
+  #[arg(long, allow_hyphen_values = true)]
+  pub nom_args: Option<String>,
+
   /// Path to save the result link, defaults to using a temporary directory
   #[arg(long, short)]
   pub out_link: Option<PathBuf>,
 
+  /// Keep the result link in the current directory instead of a temporary
+  /// directory, so it survives a failed activation
+  #[arg(long)]
+  pub keep_result: bool,
+
   /// Whether to display a package diff
-  #[arg(long, short, value_enum, default_value_t = DiffType::Auto)]
+  #[arg(
+    long,
+    short,
+    value_enum,
+    default_value_t = DiffType::Auto,
+    default_value_if("no_diff", "true", Some("never"))
+  )]
   pub diff: DiffType,
 
+  /// Skip the package diff and go straight from build to activation. Same as
+  /// `--diff never`
+  #[arg(long, conflicts_with = "diff")]
+  pub no_diff: bool,
+
+  /// Fail if the build does not produce this store path, e.g. to catch
+  /// unexpected input drift or non-reproducible builds in CI
+  #[arg(long, value_name = "STORE_PATH")]
+  pub expect_path: Option<PathBuf>,
+
+  /// Print how long each phase (update, build, diff, activation) took.
+  /// Use `--timings=json` for a machine-readable report on stdout
+  #[arg(
+    long,
+    value_enum,
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "text"
+  )]
+  pub timings: Option<TimingsFormat>,
+
+  /// Show the resolved URL, locked revision and last-modified date of the
+  /// flake before building
+  #[arg(long)]
+  pub show_metadata: bool,
+
   #[command(flatten)]
   pub passthrough: NixBuildPassthroughArgs,
 }
 
+/// Split the value of `--nom-args` into arguments the way a shell would.
+///
+/// # Errors
+///
+/// Returns an error if the value has unbalanced quotes.
+pub fn split_nom_args(nom_args: Option<&str>) -> Result<Vec<String>> {
+  let Some(nom_args) = nom_args else {
+    return Ok(Vec::new());
+  };
+  let Some(args) = shlex::split(nom_args) else {
+    bail!("Failed to parse --nom-args '{nom_args}', check its quoting");
+  };
+  Ok(args)
+}
+
 #[derive(ValueEnum, Clone, Default, Debug)]
 pub enum DiffType {
-  /// Display package diff only if the of the
-  /// current and the deployed configuration matches
+  /// Display package diff only if the new configuration differs from the
+  /// current one
   #[default]
   Auto,
   /// Always display package diff
@@ -51,6 +115,11 @@ pub struct NixBuildPassthroughArgs {
   #[arg(long, short = 'j')]
   pub max_jobs: Option<usize>,
 
+  /// Never build locally, only substitute or use remote builders. Same as
+  /// `--max-jobs 0`
+  #[arg(long, conflicts_with = "max_jobs")]
+  pub no_local_build: bool,
+
   /// Number of cores Nix should utilize
   #[arg(long)]
   pub cores: Option<usize>,
@@ -91,16 +160,25 @@ pub struct NixBuildPassthroughArgs {
   #[arg(long, short = 't')]
   pub show_trace: bool,
 
-  /// Accept configuration from flakes
+  /// Accept configuration from flakes. Set `NH_ACCEPT_FLAKE_CONFIG=1` to
+  /// accept it for every rebuild without `--ask-flake-config`
   #[arg(long)]
   pub accept_flake_config: bool,
 
+  /// Show the configuration a flake wants to set and ask before accepting it
+  #[arg(long, conflicts_with = "accept_flake_config")]
+  pub ask_flake_config: bool,
+
   /// Refresh flakes to the latest revision
   #[arg(long)]
   pub refresh: bool,
 
-  /// Allow impure builds
-  #[arg(long)]
+  /// Allow impure evaluation, for both flake and non-flake configurations
+  #[arg(
+    long,
+    env = "NH_IMPURE",
+    value_parser = clap::builder::BoolishValueParser::new()
+  )]
   pub impure: bool,
 
   /// Build without internet access
@@ -157,15 +235,154 @@ pub struct NixBuildPassthroughArgs {
 }
 
 impl NixBuildPassthroughArgs {
+  /// Resolve `--ask-flake-config` for `installable` by prompting the user, and
+  /// forward `--accept-flake-config` if they agree. Without
+  /// `--ask-flake-config`, `NH_ACCEPT_FLAKE_CONFIG` turns on
+  /// `--accept-flake-config`.
+  ///
+  /// The variable is read here rather than by clap, so that an explicit
+  /// `--ask-flake-config` still takes precedence over it.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the flake configuration cannot be read or the prompt
+  /// fails.
+  pub fn confirm_flake_config(
+    &mut self,
+    installable: &Installable,
+  ) -> Result<()> {
+    if self.ask_flake_config && !self.accept_flake_config {
+      self.accept_flake_config =
+        flake_config::confirm_flake_config(installable)?;
+    } else if !self.ask_flake_config
+      && util::env_flag_enabled("NH_ACCEPT_FLAKE_CONFIG")
+    {
+      self.accept_flake_config = true;
+    }
+    Ok(())
+  }
+
+  /// Reject flag combinations that Nix cannot honor together and malformed
+  /// `--override-input` pairs, before any evaluation starts.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error naming the first pair of contradictory flags or the
+  /// first malformed input override.
+  pub fn validate(&self) -> Result<()> {
+    for pair in self.override_input.chunks(2) {
+      let [input, flake_url] = pair else {
+        bail!("--override-input expects an input name and a flake reference");
+      };
+      validate_input_override(input, flake_url)?;
+    }
+
+    let conflicts = [
+      (self.offline, self.refresh, "--offline", "--refresh"),
+      (
+        self.no_net,
+        self.use_substitutes,
+        "--no-net",
+        "--use-substitutes",
+      ),
+      (
+        self.no_write_lock_file,
+        self.commit_lock_file,
+        "--no-write-lock-file",
+        "--commit-lock-file",
+      ),
+    ];
+
+    for (first, second, first_flag, second_flag) in conflicts {
+      if first && second {
+        bail!(
+          "{first_flag} and {second_flag} cannot be used together, please \
+           drop one of them"
+        );
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Warn that `--no-local-build` fails as soon as something has to be built,
+  /// unless it can be built on `--builders` or a `--build-host`.
+  pub fn warn_no_local_build(&self, has_build_host: bool) {
+    if self.no_local_build && self.builders.is_none() && !has_build_host {
+      warn!(
+        "--no-local-build is set without --builders or --build-host. The \
+         build will fail if anything cannot be substituted, unless remote \
+         builders are configured in nix.conf"
+      );
+    }
+  }
+
+  /// Warn that the inputs overridden with `--override-input` are not recorded
+  /// in `flake.lock`, so the next rebuild without the flag reverts them. Meant
+  /// to be called after a successful activation.
+  pub fn warn_unpersisted_overrides(&self) {
+    if let Some(message) = self.unpersisted_overrides_message() {
+      warn!("{message}");
+    }
+  }
+
+  fn unpersisted_overrides_message(&self) -> Option<String> {
+    if self.override_input.is_empty() {
+      return None;
+    }
+
+    let overrides: Vec<String> = self
+      .override_input
+      .chunks(2)
+      .map(|pair| pair.join(" = "))
+      .collect();
+    Some(format!(
+      "This configuration was built with overridden inputs ({}), which are \
+       not recorded in flake.lock. The next rebuild without --override-input \
+       will revert them; update the inputs in flake.nix to keep them.",
+      overrides.join(", ")
+    ))
+  }
+
   #[must_use]
   pub fn generate_passthrough_args(&self) -> Vec<String> {
+    self.passthrough_args(PassthroughFor::LocalBuild)
+  }
+
+  /// The passthrough arguments that apply to `nix eval`, e.g. `--impure`,
+  /// `--override-input` and `--accept-flake-config`. Build-only flags such as
+  /// `--max-jobs`, `--keep-going` or `--json` are left out.
+  #[must_use]
+  pub fn eval_args(&self) -> Vec<String> {
+    self.passthrough_args(PassthroughFor::Eval)
+  }
+
+  /// Arguments for the `nix build` that runs on a `--build-host`:
+  /// `extra_args` followed by the passthrough arguments. `--no-local-build`
+  /// only applies to this machine, so it is left out for the build host.
+  #[must_use]
+  pub fn remote_build_args(&self, extra_args: &[String]) -> Vec<OsString> {
+    extra_args
+      .iter()
+      .cloned()
+      .chain(self.passthrough_args(PassthroughFor::RemoteBuild))
+      .map(Into::into)
+      .collect()
+  }
+
+  fn passthrough_args(&self, target: PassthroughFor) -> Vec<String> {
+    let build = target != PassthroughFor::Eval;
     let mut args = Vec::new();
 
-    if let Some(jobs) = self.max_jobs {
+    if build && let Some(jobs) = self.max_jobs {
       args.push("--max-jobs".into());
       args.push(jobs.to_string());
     }
-    if let Some(cores) = self.cores {
+    if self.no_local_build && target == PassthroughFor::LocalBuild {
+      args.push("--max-jobs".into());
+      args.push("0".into());
+    }
+    if build && let Some(cores) = self.cores {
       args.push("--cores".into());
       args.push(cores.to_string());
     }
@@ -173,19 +390,19 @@ impl NixBuildPassthroughArgs {
       args.push("--log-format".into());
       args.push(format.clone());
     }
-    if self.keep_going {
+    if self.keep_going && build {
       args.push("--keep-going".into());
     }
-    if self.keep_failed {
+    if self.keep_failed && build {
       args.push("--keep-failed".into());
     }
-    if self.fallback {
+    if self.fallback && build {
       args.push("--fallback".into());
     }
-    if self.repair {
+    if self.repair && build {
       args.push("--repair".into());
     }
-    if let Some(ref builders) = self.builders {
+    if build && let Some(ref builders) = self.builders {
       args.push("--builders".into());
       args.push(builders.clone());
     }
@@ -193,7 +410,7 @@ impl NixBuildPassthroughArgs {
       args.push("--include".into());
       args.push(inc.clone());
     }
-    if self.print_build_logs {
+    if self.print_build_logs && build {
       args.push("--print-build-logs".into());
     }
     if self.show_trace {
@@ -223,6 +440,9 @@ impl NixBuildPassthroughArgs {
     if self.no_write_lock_file {
       args.push("--no-write-lock-file".into());
     }
+    if self.commit_lock_file {
+      args.push("--commit-lock-file".into());
+    }
     if self.no_use_registries {
       args.push("--no-use-registries".into());
     }
@@ -230,10 +450,10 @@ impl NixBuildPassthroughArgs {
       warn!("--no-registries is deprecated, use --no-use-registries instead");
       args.push("--no-use-registries".into());
     }
-    if self.no_build_output {
+    if self.no_build_output && build {
       args.push("--quiet".into());
     }
-    if self.json {
+    if self.json && build {
       args.push("--json".into());
     }
     for pair in self.option.chunks(2) {
@@ -251,7 +471,48 @@ impl NixBuildPassthroughArgs {
   }
 }
 
+/// Which command the passthrough arguments are generated for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PassthroughFor {
+  /// `nix build` on this machine
+  LocalBuild,
+  /// `nix build` on a `--build-host`, which ignores `--no-local-build`
+  RemoteBuild,
+  /// `nix eval`, which only takes the evaluation flags
+  Eval,
+}
+
+/// Check that `input` is a (possibly nested, `/`-separated) flake input name
+/// and that `flake_url` is a non-empty reference.
+fn validate_input_override(input: &str, flake_url: &str) -> Result<()> {
+  let valid_segment = |segment: &str| {
+    segment
+      .chars()
+      .next()
+      .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+      && segment
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '\''))
+  };
+
+  if !input.split('/').all(valid_segment) {
+    bail!(
+      "Invalid input name '{input}' for --override-input, expected a flake \
+       input such as 'nixpkgs' or 'home-manager/nixpkgs'"
+    );
+  }
+  if flake_url.trim().is_empty() || flake_url.contains(char::is_whitespace) {
+    bail!(
+      "Invalid flake reference '{flake_url}' for --override-input {input}, \
+       expected something like 'github:NixOS/nixpkgs/pull/12345/head'"
+    );
+  }
+
+  Ok(())
+}
+
 #[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Fine in tests")]
 mod tests {
   use super::NixBuildPassthroughArgs;
 
@@ -265,6 +526,101 @@ mod tests {
     assert_eq!(args.generate_passthrough_args(), ["--quiet"]);
   }
 
+  #[test]
+  fn no_local_build_sets_max_jobs_to_zero() {
+    let args = NixBuildPassthroughArgs {
+      no_local_build: true,
+      ..Default::default()
+    };
+
+    assert_eq!(args.generate_passthrough_args(), ["--max-jobs", "0"]);
+  }
+
+  #[test]
+  fn eval_args_keep_only_evaluation_flags() {
+    let args = NixBuildPassthroughArgs {
+      max_jobs: Some(4),
+      keep_going: true,
+      json: true,
+      impure: true,
+      accept_flake_config: true,
+      include: vec!["nixpkgs=/src/nixpkgs".to_owned()],
+      override_input: vec![
+        "nixpkgs".to_owned(),
+        "path:/src/nixpkgs".to_owned(),
+      ],
+      ..Default::default()
+    };
+
+    assert_eq!(args.eval_args(), [
+      "--include",
+      "nixpkgs=/src/nixpkgs",
+      "--accept-flake-config",
+      "--impure",
+      "--override-input",
+      "nixpkgs",
+      "path:/src/nixpkgs",
+    ]);
+  }
+
+  #[test]
+  fn no_local_build_still_builds_on_build_host() {
+    let args = NixBuildPassthroughArgs {
+      no_local_build: true,
+      keep_going: true,
+      ..Default::default()
+    };
+
+    assert_eq!(args.remote_build_args(&["--impure".to_owned()]), [
+      "--impure",
+      "--keep-going"
+    ]);
+  }
+
+  #[test]
+  fn unpersisted_overrides_are_listed() {
+    assert_eq!(
+      NixBuildPassthroughArgs::default().unpersisted_overrides_message(),
+      None
+    );
+
+    let args = NixBuildPassthroughArgs {
+      override_input: vec!["nixpkgs".into(), "path:/src/nixpkgs".into()],
+      ..Default::default()
+    };
+    let message = args.unpersisted_overrides_message().unwrap();
+    assert!(
+      message.contains("(nixpkgs = path:/src/nixpkgs)"),
+      "{message}"
+    );
+  }
+
+  #[test]
+  fn no_diff_disables_the_diff() {
+    use clap::Parser;
+
+    use super::{CommonRebuildArgs, DiffType};
+
+    #[derive(Parser)]
+    struct Cli {
+      #[command(flatten)]
+      common: CommonRebuildArgs,
+    }
+
+    let cli = Cli::try_parse_from(["nh", "--no-diff"]).unwrap();
+    assert!(matches!(cli.common.diff, DiffType::Never));
+    assert!(Cli::try_parse_from(["nh", "--no-diff", "--diff=always"]).is_err());
+  }
+
+  #[test]
+  fn nom_args_are_split_like_a_shell() {
+    assert!(super::split_nom_args(None).unwrap().is_empty());
+    assert_eq!(super::split_nom_args(Some("--foo 'bar baz'")).unwrap(), [
+      "--foo", "bar baz"
+    ]);
+    assert!(super::split_nom_args(Some("--foo 'bar")).is_err());
+  }
+
   #[test]
   fn option_pairs_are_emitted() {
     let args = NixBuildPassthroughArgs {
@@ -282,6 +638,109 @@ mod tests {
     ]);
   }
 
+  #[test]
+  fn commit_lock_file_is_emitted() {
+    let args = NixBuildPassthroughArgs {
+      commit_lock_file: true,
+      ..Default::default()
+    };
+
+    assert_eq!(args.generate_passthrough_args(), ["--commit-lock-file"]);
+  }
+
+  #[test]
+  fn validate_accepts_compatible_flags() {
+    let args = NixBuildPassthroughArgs {
+      offline: true,
+      no_write_lock_file: true,
+      use_substitutes: true,
+      ..Default::default()
+    };
+
+    assert!(args.validate().is_ok());
+  }
+
+  #[test]
+  fn validate_rejects_offline_with_refresh() {
+    let args = NixBuildPassthroughArgs {
+      offline: true,
+      refresh: true,
+      ..Default::default()
+    };
+
+    let err = args.validate().unwrap_err().to_string();
+    assert!(err.contains("--offline and --refresh"), "{err}");
+  }
+
+  #[test]
+  fn validate_rejects_no_net_with_use_substitutes() {
+    let args = NixBuildPassthroughArgs {
+      no_net: true,
+      use_substitutes: true,
+      ..Default::default()
+    };
+
+    let err = args.validate().unwrap_err().to_string();
+    assert!(err.contains("--no-net and --use-substitutes"), "{err}");
+  }
+
+  #[test]
+  fn validate_rejects_no_write_lock_file_with_commit_lock_file() {
+    let args = NixBuildPassthroughArgs {
+      no_write_lock_file: true,
+      commit_lock_file: true,
+      ..Default::default()
+    };
+
+    let err = args.validate().unwrap_err().to_string();
+    assert!(
+      err.contains("--no-write-lock-file and --commit-lock-file"),
+      "{err}"
+    );
+  }
+
+  #[test]
+  fn validate_accepts_nested_input_overrides() {
+    let args = NixBuildPassthroughArgs {
+      override_input: vec![
+        "home-manager/nixpkgs".into(),
+        "github:NixOS/nixpkgs/pull/12345/head".into(),
+      ],
+      ..Default::default()
+    };
+
+    assert!(args.validate().is_ok());
+  }
+
+  #[test]
+  fn validate_rejects_malformed_input_names() {
+    for input in ["", "nixpkgs/", "1nixpkgs", "nix pkgs", "nixpkgs#foo"] {
+      let args = NixBuildPassthroughArgs {
+        override_input: vec![input.into(), "github:NixOS/nixpkgs".into()],
+        ..Default::default()
+      };
+
+      let err = args.validate().unwrap_err().to_string();
+      assert!(err.contains("Invalid input name"), "{input}: {err}");
+    }
+  }
+
+  #[test]
+  fn validate_rejects_malformed_flake_references() {
+    for flake_url in ["", " ", "github:NixOS/nixpkgs extra"] {
+      let args = NixBuildPassthroughArgs {
+        override_input: vec!["nixpkgs".into(), flake_url.into()],
+        ..Default::default()
+      };
+
+      let err = args.validate().unwrap_err().to_string();
+      assert!(
+        err.contains("Invalid flake reference"),
+        "{flake_url}: {err}"
+      );
+    }
+  }
+
   #[test]
   fn override_input_pairs_are_emitted() {
     let args = NixBuildPassthroughArgs {
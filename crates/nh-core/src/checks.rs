@@ -1,10 +1,41 @@
-use std::{cmp::Ordering, env};
-
-use color_eyre::Result;
+use std::{
+  cmp::Ordering,
+  env,
+  fs::{self, OpenOptions},
+  io::{self, IsTerminal, Write},
+  path::{Path, PathBuf},
+};
+
+use color_eyre::{
+  Result,
+  eyre::{Context, bail},
+};
 use semver::Version;
-use tracing::{debug, warn};
-
-use crate::util::{self, NixVariant, normalize_version_string};
+use tracing::{debug, info, warn};
+
+use crate::{
+  util::{self, NixVariant, normalize_version_string},
+  variant_quirks,
+};
+
+// XXX: Both Nix and Lix follow semantic versioning (semver). Update the
+// versions below once latest stable for either of those packages change.
+// We *also* cannot (or rather, will not) make this check for non-nixpkgs
+// Nix variants, since there is no good baseline for what to support
+// without the understanding of stable/unstable branches. What do we check
+// for, whether upstream made an announcement? No thanks.
+// TODO: Set up a CI to automatically update those in the future.
+const MIN_LIX_VERSION: &str = "2.93.3";
+const MIN_NIX_VERSION: &str = "2.31.2";
+
+/// Minimum recommended version for a Nix variant.
+#[must_use]
+pub const fn min_nix_version(variant: &NixVariant) -> &'static str {
+  match variant {
+    NixVariant::Lix => MIN_LIX_VERSION,
+    _ => MIN_NIX_VERSION,
+  }
+}
 
 /// Verifies if the installed Nix version meets requirements
 ///
@@ -16,16 +47,6 @@ use crate::util::{self, NixVariant, normalize_version_string};
 ///
 /// Returns an error if the Nix version cannot be determined or parsed.
 pub fn check_nix_version() -> Result<()> {
-  // XXX: Both Nix and Lix follow semantic versioning (semver). Update the
-  // versions below once latest stable for either of those packages change.
-  // We *also* cannot (or rather, will not) make this check for non-nixpkgs
-  // Nix variants, since there is no good baseline for what to support
-  // without the understanding of stable/unstable branches. What do we check
-  // for, whether upstream made an announcement? No thanks.
-  // TODO: Set up a CI to automatically update those in the future.
-  const MIN_LIX_VERSION: &str = "2.93.3";
-  const MIN_NIX_VERSION: &str = "2.31.2";
-
   if env::var("NH_NO_CHECKS").is_ok() {
     return Ok(());
   }
@@ -52,10 +73,7 @@ pub fn check_nix_version() -> Result<()> {
   // available in Nixpkgs, and even if I were they do not strike me as
   // responsible enough to provide timely security updates. In other
   // words I simply don't care about DetNix.
-  let min_version = match nix_variant {
-    util::NixVariant::Lix => MIN_LIX_VERSION,
-    _ => MIN_NIX_VERSION,
-  };
+  let min_version = min_nix_version(nix_variant);
 
   let current = match Version::parse(&version_normal) {
     Ok(ver) => ver,
@@ -72,11 +90,7 @@ pub fn check_nix_version() -> Result<()> {
 
   match current.cmp(&required) {
     Ordering::Less => {
-      let binary_name = match nix_variant {
-        NixVariant::Lix => "Lix",
-        NixVariant::Determinate => "Determinate Nix",
-        NixVariant::Nix => "Nix",
-      };
+      let binary_name = nix_variant.display_name();
       warn!(
         "Warning: {binary_name} version {version} is older than the \
          recommended minimum version {min_version}. You may encounter issues."
@@ -153,6 +167,13 @@ pub trait FeatureRequirements {
   /// Returns the list of required experimental features
   fn required_features(&self) -> Vec<&'static str>;
 
+  /// Whether the installed Nix has to meet the minimum supported version
+  /// before the command runs. Commands that only query Nix, or do not use it
+  /// at all, can opt out so they keep working on older installations.
+  fn requires_nix_version(&self) -> bool {
+    true
+  }
+
   /// Checks if all required features are enabled
   ///
   /// # Errors
@@ -172,10 +193,7 @@ pub trait FeatureRequirements {
 
     let missing = util::get_missing_experimental_features(&required)?;
     if !missing.is_empty() {
-      return Err(color_eyre::eyre::eyre!(
-        "Missing required experimental features for this command: {}",
-        missing.join(", ")
-      ));
+      return offer_to_enable_features(&missing);
     }
 
     debug!("All required Nix features are enabled");
@@ -183,6 +201,100 @@ pub trait FeatureRequirements {
   }
 }
 
+/// Offer to add the missing experimental features to the user's `nix.conf`.
+///
+/// Without a terminal to prompt on, when the user declines, or when the line
+/// cannot be written, this fails with the exact line to add instead.
+fn offer_to_enable_features(missing: &[String]) -> Result<()> {
+  let line = nix_conf_line(missing);
+  let nix_conf = user_nix_conf();
+
+  let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+  if interactive && let Some(nix_conf) = &nix_conf {
+    warn!(
+      "This command needs the experimental Nix features: {}",
+      missing.join(", ")
+    );
+    if let Some(note) = missing_features_note(missing) {
+      warn!("{note}");
+    }
+    let accepted = inquire::Confirm::new(&format!(
+      "Add `{line}` to {}?",
+      nix_conf.display()
+    ))
+    .with_default(false)
+    .prompt()
+    .wrap_err("Failed to read confirmation")?;
+
+    if accepted {
+      match append_line(nix_conf, &line) {
+        Ok(()) => {
+          info!("Enabled {} in {}", missing.join(", "), nix_conf.display());
+          return Ok(());
+        },
+        Err(err) => warn!("Failed to write {}: {err}", nix_conf.display()),
+      }
+    }
+  }
+
+  let location = nix_conf.map_or_else(
+    || "/etc/nix/nix.conf".to_owned(),
+    |path| format!("{} or /etc/nix/nix.conf", path.display()),
+  );
+  let mut message = format!(
+    "Missing required experimental features for this command: {}",
+    missing.join(", ")
+  );
+  if let Some(note) = missing_features_note(missing) {
+    message.push('\n');
+    message.push_str(note);
+  }
+  bail!(
+    "{message}\nAdd the following line to {location} to enable them:\n  {line}"
+  )
+}
+
+/// Explain why a missing feature matters when the feature name alone does
+/// not make it obvious.
+fn missing_features_note(missing: &[String]) -> Option<&'static str> {
+  missing
+    .iter()
+    .any(|feature| feature == "nix-command")
+    .then_some(
+      "nh runs the `nix` CLI (`nix build`, `nix eval`, ...) for nearly every \
+       operation, so `nix-command` must be enabled even for non-flake \
+       configurations.",
+    )
+}
+
+fn nix_conf_line(missing: &[String]) -> String {
+  format!("extra-experimental-features = {}", missing.join(" "))
+}
+
+/// `$XDG_CONFIG_HOME/nix/nix.conf`, falling back to `~/.config/nix/nix.conf`.
+fn user_nix_conf() -> Option<PathBuf> {
+  util::xdg_dir_from(
+    env::var_os("XDG_CONFIG_HOME"),
+    env::var_os("HOME"),
+    ".config",
+  )
+  .map(|dir| dir.join("nix").join("nix.conf"))
+}
+
+fn append_line(path: &Path, line: &str) -> io::Result<()> {
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+
+  let needs_newline = fs::read_to_string(path)
+    .is_ok_and(|contents| !contents.is_empty() && !contents.ends_with('\n'));
+  let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+  if needs_newline {
+    writeln!(file)?;
+  }
+  writeln!(file, "{line}")
+}
+
 /// Feature requirements for commands that use flakes
 #[derive(Debug)]
 pub struct FlakeFeatures;
@@ -195,8 +307,7 @@ impl FeatureRequirements for FlakeFeatures {
     // as they simply decided to mark those as no-longer-experimental-lol.
     // Remove redundant experimental features if the Nix variant is
     // determinate.
-    let variant = util::get_nix_variant();
-    if !matches!(variant, NixVariant::Determinate) {
+    if variant_quirks::quirks().experimental_flakes {
       features.push("nix-command");
       features.push("flakes");
     }
@@ -206,16 +317,21 @@ impl FeatureRequirements for FlakeFeatures {
 }
 
 /// Feature requirements for legacy (non-flake) commands
-/// XXX: There are actually no experimental feature requirements for legacy
-/// (nix2) CLI but since move-fast-break-everything is a common mantra among Nix
-/// & Nix-adjacent software, I've implemented this. Do not remove, this is
-/// simply for futureproofing.
+///
+/// Non-flake installables are still built and evaluated through the new
+/// `nix` CLI (`nix build`, `nix eval`), so `nix-command` is required even
+/// though `flakes` is not.
 #[derive(Debug)]
 pub struct LegacyFeatures;
 
 impl FeatureRequirements for LegacyFeatures {
   fn required_features(&self) -> Vec<&'static str> {
-    vec![]
+    // See FlakeFeatures: Determinate Nix has nix-command enabled regardless.
+    if variant_quirks::quirks().experimental_flakes {
+      vec!["nix-command"]
+    } else {
+      vec![]
+    }
   }
 }
 
@@ -235,29 +351,17 @@ impl FeatureRequirements for OsReplFeatures {
     }
 
     // For flake repls, check if we need experimental features
-    match util::get_nix_variant() {
-      NixVariant::Determinate => {
-        // Determinate Nix doesn't need experimental features
-      },
-      NixVariant::Lix => {
-        features.push("nix-command");
-        features.push("flakes");
-
-        // Lix-specific repl-flake feature for older versions
-        if let Ok(version) = util::get_nix_version() {
-          let normalized_version = normalize_version_string(&version);
-          if let Ok(current) = Version::parse(&normalized_version)
-            && let Ok(threshold) = Version::parse("2.93.0")
-            && current < threshold
-          {
-            features.push("repl-flake");
-          }
-        }
-      },
-      NixVariant::Nix => {
-        features.push("nix-command");
-        features.push("flakes");
-      },
+    let quirks = variant_quirks::quirks();
+    if quirks.experimental_flakes {
+      features.push("nix-command");
+      features.push("flakes");
+    }
+
+    // Older Lix versions need repl-flake on top of that
+    if util::get_nix_version()
+      .is_ok_and(|version| quirks.needs_repl_flake(&version))
+    {
+      features.push("repl-flake");
     }
 
     features
@@ -280,8 +384,7 @@ impl FeatureRequirements for HomeReplFeatures {
     }
 
     // For flake repls, only need nix-command and flakes
-    let variant = util::get_nix_variant();
-    if !matches!(variant, NixVariant::Determinate) {
+    if variant_quirks::quirks().experimental_flakes {
       features.push("nix-command");
       features.push("flakes");
     }
@@ -306,8 +409,7 @@ impl FeatureRequirements for DarwinReplFeatures {
     }
 
     // For flake repls, only need nix-command and flakes
-    let variant = util::get_nix_variant();
-    if !matches!(variant, NixVariant::Determinate) {
+    if variant_quirks::quirks().experimental_flakes {
       features.push("nix-command");
       features.push("flakes");
     }
@@ -316,7 +418,8 @@ impl FeatureRequirements for DarwinReplFeatures {
   }
 }
 
-/// Feature requirements for commands that don't need experimental features
+/// Feature requirements for commands that don't need experimental features.
+/// These commands also skip the Nix version check.
 #[derive(Debug)]
 pub struct NoFeatures;
 
@@ -324,6 +427,10 @@ impl FeatureRequirements for NoFeatures {
   fn required_features(&self) -> Vec<&'static str> {
     vec![]
   }
+
+  fn requires_nix_version(&self) -> bool {
+    false
+  }
 }
 
 #[cfg(test)]
@@ -366,6 +473,37 @@ mod tests {
     }
   }
 
+  #[test]
+  fn nix_conf_line_lists_missing_features() {
+    let missing = ["nix-command".to_owned(), "flakes".to_owned()];
+    assert_eq!(
+      nix_conf_line(&missing),
+      "extra-experimental-features = nix-command flakes"
+    );
+  }
+
+  #[test]
+  fn missing_features_note_explains_nix_command() {
+    assert!(missing_features_note(&["nix-command".to_owned()]).is_some());
+    assert!(missing_features_note(&["flakes".to_owned()]).is_none());
+  }
+
+  #[test]
+  fn append_line_keeps_existing_settings() {
+    let dir = tempfile::tempdir().expect("Failed to create tempdir");
+    let nix_conf = dir.path().join("nix").join("nix.conf");
+
+    fs::create_dir_all(dir.path().join("nix")).expect("Failed to create dir");
+    fs::write(&nix_conf, "max-jobs = 4").expect("Failed to write file");
+    append_line(&nix_conf, "extra-experimental-features = flakes")
+      .expect("Failed to append");
+
+    assert_eq!(
+      fs::read_to_string(&nix_conf).expect("Failed to read file"),
+      "max-jobs = 4\nextra-experimental-features = flakes\n"
+    );
+  }
+
   proptest! {
       #[test]
       fn test_normalize_version_string_handles_various_formats(
@@ -431,14 +569,15 @@ mod tests {
       }
 
       #[test]
-      fn test_legacy_features_always_empty(
+      fn test_legacy_features_never_require_flakes(
           _dummy in 0..100u32
       ) {
           let features = LegacyFeatures;
           let result = features.required_features();
 
-          // Property: Legacy features should always be empty
-          prop_assert!(result.is_empty());
+          // Property: Legacy features need at most nix-command
+          prop_assert!(!result.contains(&"flakes"));
+          prop_assert!(result.iter().all(|feature| *feature == "nix-command"));
       }
 
       #[test]
@@ -659,6 +798,13 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_no_features_skips_nix_version_check() {
+    assert!(!NoFeatures.requires_nix_version());
+    assert!(FlakeFeatures.requires_nix_version());
+    assert!(LegacyFeatures.requires_nix_version());
+  }
+
   #[test]
   #[serial]
   fn test_verify_nix_environment_bypassed_with_nh_no_checks() {
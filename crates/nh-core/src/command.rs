@@ -4,7 +4,7 @@ use std::{
   env,
   ffi::{OsStr, OsString},
   io::{Read, Write},
-  path::PathBuf,
+  path::{Path, PathBuf},
   str::FromStr,
   sync::{Mutex, OnceLock},
 };
@@ -43,6 +43,37 @@ pub fn get_sudo_opts() -> Vec<String> {
   })
 }
 
+/// Echo every external command to stderr before running it, independent of
+/// the log level. Set from `--print-commands`.
+pub fn set_print_commands(enabled: bool) {
+  nix_command::set_print_commands(enabled);
+}
+
+/// Log a command that is about to run: always at debug level, and on stderr
+/// in a copy-pasteable form with `--print-commands`.
+pub fn log_command(cmd: &Exec) {
+  debug!(?cmd);
+  nix_command::print_command_line(&cmd.to_cmdline_lossy());
+}
+
+/// Like [`log_command`], for commands whose output is piped into the next.
+pub fn log_pipeline(cmds: &[&Exec]) {
+  debug!(?cmds);
+  nix_command::print_command_line(
+    &cmds
+      .iter()
+      .map(|cmd| cmd.to_cmdline_lossy())
+      .collect::<Vec<_>>()
+      .join(" | "),
+  );
+}
+
+/// Like [`log_command`], for commands run through [`std::process::Command`].
+pub fn log_std_command(cmd: &std::process::Command) {
+  debug!(?cmd);
+  nix_command::print_command(cmd);
+}
+
 /// Execute a command, streaming output to stdout/stderr while optionally
 /// capturing it for error reporting.
 ///
@@ -68,6 +99,7 @@ pub fn exec_with_streaming(
   cmd: Exec,
   capture_output: bool,
 ) -> Result<(subprocess::ExitStatus, String, String)> {
+  log_command(&cmd);
   let mut job = cmd
     .stdout(Redirection::Pipe)
     .start()
@@ -153,6 +185,97 @@ pub fn exec_with_streaming(
   Ok((exit_status, stdout_output, stderr_output))
 }
 
+/// Extra environment variables to forward to elevated commands, read from
+/// `NH_PRESERVE_ENV` as a comma-separated list of names.
+///
+/// The plain on/off values `0` and `1` are not variable names and yield an
+/// empty list.
+fn extra_preserved_env() -> Vec<String> {
+  env::var("NH_PRESERVE_ENV")
+    .ok()
+    .filter(|value| !matches!(value.trim(), "0" | "1"))
+    .map(|value| parse_preserve_env_list(&value))
+    .unwrap_or_default()
+}
+
+fn parse_preserve_env_list(value: &str) -> Vec<String> {
+  value
+    .split(',')
+    .map(str::trim)
+    .filter(|name| !name.is_empty())
+    .map(String::from)
+    .collect()
+}
+
+/// Elevation programs that need their own argument conventions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ElevationProgram {
+  Sudo,
+  Doas,
+  Run0,
+  Pkexec,
+  /// Any other program, assumed to accept a command like doas does.
+  Other,
+}
+
+impl ElevationProgram {
+  fn from_path(path: &Path) -> Self {
+    match path.file_name().and_then(OsStr::to_str) {
+      Some("sudo") => Self::Sudo,
+      Some("doas") => Self::Doas,
+      Some("run0") => Self::Run0,
+      Some("pkexec") => Self::Pkexec,
+      _ => Self::Other,
+    }
+  }
+}
+
+/// Build the arguments placed between an elevation program and the elevated
+/// command, forwarding `env` in the form the program understands.
+fn elevation_args(
+  program: ElevationProgram,
+  askpass: bool,
+  sudo_opts: Vec<String>,
+  env: &[(String, String)],
+) -> Vec<String> {
+  let mut args = Vec::new();
+
+  match program {
+    ElevationProgram::Sudo => {
+      if askpass {
+        args.push("-A".to_string());
+      }
+      args.extend(sudo_opts);
+    },
+    ElevationProgram::Run0 => {
+      // Request allocation of a pseudo TTY for the run0 session. Without this,
+      // running `run0` changes the user of `/dev/pts/<current-terminal>
+      // to `root`, which we want to avoid since it can cause issues with
+      // subsequent commands.
+      args.push("--pty-late".to_string());
+
+      // run0 sets up the environment of the transient unit itself, so pass
+      // variables natively instead of going through `env`.
+      args.extend(
+        env
+          .iter()
+          .map(|(key, value)| format!("--setenv={key}={value}")),
+      );
+      return args;
+    },
+    ElevationProgram::Doas
+    | ElevationProgram::Pkexec
+    | ElevationProgram::Other => {},
+  }
+
+  // Insert 'env' command to explicitly pass environment variables to the
+  // elevated command. pkexec and doas clear the environment of the elevated
+  // process, so this is the only portable way to forward it.
+  args.push("env".to_string());
+  args.extend(env.iter().map(|(key, value)| format!("{key}={value}")));
+  args
+}
+
 static PASSWORD_CACHE: OnceLock<Mutex<HashMap<String, SecretString>>> =
   OnceLock::new();
 
@@ -400,34 +523,45 @@ impl ElevationStrategy {
 #[derive(Debug)]
 #[allow(clippy::struct_field_names)]
 pub struct Command {
-  dry:         bool,
-  message:     Option<String>,
-  command:     OsString,
-  args:        Vec<OsString>,
-  elevate:     Option<ElevationStrategy>,
-  ssh:         Option<String>,
-  show_output: bool,
-  env_vars:    HashMap<String, EnvAction>,
+  dry:           bool,
+  message:       Option<String>,
+  command:       OsString,
+  args:          Vec<OsString>,
+  elevate:       Option<ElevationStrategy>,
+  ssh:           Option<String>,
+  show_output:   bool,
+  env_vars:      HashMap<String, EnvAction>,
+  capture_limit: usize,
 }
 
+/// How much stdout [`Command::run_capture`] keeps by default before
+/// truncating, in bytes.
+pub const DEFAULT_CAPTURE_LIMIT: usize = 64 * 1024 * 1024;
+
 impl Command {
   pub fn new<S: AsRef<OsStr>>(command: S) -> Self {
     Self {
-      dry:         false,
-      message:     None,
-      command:     command.as_ref().to_os_string(),
-      args:        vec![],
-      elevate:     None,
-      ssh:         None,
-      show_output: false,
-      env_vars:    HashMap::new(),
+      dry:           false,
+      message:       None,
+      command:       command.as_ref().to_os_string(),
+      args:          vec![],
+      elevate:       None,
+      ssh:           None,
+      show_output:   false,
+      env_vars:      HashMap::new(),
+      capture_limit: DEFAULT_CAPTURE_LIMIT,
     }
   }
 
   /// Set whether to run the command with elevated privileges.
+  ///
+  /// [`ElevationStrategy::None`] is treated like no elevation at all, so the
+  /// command runs directly and fails with the real permission error if the
+  /// current user lacks the required privileges.
   #[must_use]
   pub fn elevate(mut self, elevate: Option<ElevationStrategy>) -> Self {
-    self.elevate = elevate;
+    self.elevate =
+      elevate.filter(|strategy| !matches!(strategy, ElevationStrategy::None));
     self
   }
 
@@ -445,6 +579,14 @@ impl Command {
     self
   }
 
+  /// Set how many bytes of stdout [`Self::run_capture`] keeps. Output beyond
+  /// that is discarded with a warning.
+  #[must_use]
+  pub const fn capture_limit(mut self, bytes: usize) -> Self {
+    self.capture_limit = bytes;
+    self
+  }
+
   /// Add a single argument to the command.
   #[must_use]
   pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
@@ -551,8 +693,14 @@ impl Command {
         .insert("HOME".to_string(), EnvAction::Set(String::new()));
     }
 
-    // Preserve all variables in PRESERVE_ENV if present
-    for &key in PRESERVE_ENV {
+    // Preserve all variables in PRESERVE_ENV if present, along with any extra
+    // ones the user listed in NH_PRESERVE_ENV
+    let extra_preserved = extra_preserved_env();
+    for key in PRESERVE_ENV
+      .iter()
+      .copied()
+      .chain(extra_preserved.iter().map(String::as_str))
+    {
       if env::var(key).is_ok() {
         self.env_vars.insert(key.to_string(), EnvAction::Preserve);
       }
@@ -610,119 +758,83 @@ impl Command {
   ///
   /// Panics: If called when `self.elevate` is `None`
   fn build_sudo_cmd(&self) -> Result<Exec> {
-    let elevation_strategy = self
-      .elevate
-      .as_ref()
-      .ok_or_else(|| eyre::eyre!("Command not found for elevation"))?;
-
-    let elevation_program = elevation_strategy
-      .resolve()
-      .context("Failed to resolve elevation program")?;
-
-    let mut cmd = Exec::cmd(&elevation_program);
-
-    // Use NH_SUDO_ASKPASS program for sudo if present, but NOT for
-    // Passwordless variant (Passwordless expects NOPASSWD config without
-    // password input)
-    let program_name = elevation_program
-      .file_name()
-      .and_then(|name| name.to_str())
-      .ok_or_else(|| {
-        eyre::eyre!("Failed to determine elevation program name")
-      })?;
-    if program_name == "sudo"
-      && !matches!(elevation_strategy, ElevationStrategy::Passwordless)
-      && let Ok(askpass) = env::var("NH_SUDO_ASKPASS")
-    {
-      cmd = cmd.env("SUDO_ASKPASS", askpass).arg("-A");
-    }
-    // Request allocation of a pseudo TTY for the run0 session. Without this,
-    // running `run0` changes the user of `/dev/pts/<current-terminal>
-    // to `root`, which we want to avoid since it can cause issues with
-    // subsequent commands.
-    if program_name == "run0" {
-      cmd = cmd.arg("--pty-late");
+    let (parts, askpass) = self.build_sudo_parts()?;
+    let (program, args) = parts
+      .split_first()
+      .ok_or_else(|| eyre::eyre!("Empty elevation command"))?;
+
+    let mut cmd = Exec::cmd(program).args(args);
+    if let Some(askpass) = askpass {
+      cmd = cmd.env("SUDO_ASKPASS", askpass);
     }
 
-    if program_name == "sudo" {
-      cmd = cmd.args(get_sudo_opts());
-    }
+    Ok(cmd)
+  }
 
+  /// The environment forwarded to the elevated command.
+  fn elevated_env(&self) -> Vec<(String, String)> {
     // NH_PRESERVE_ENV: set to "0" to disable preserving environment variables,
-    // "1" to force, unset defaults to force
+    // "1" or a list of extra variables to force, unset defaults to force
     let preserve_env = env::var("NH_PRESERVE_ENV")
       .as_deref()
       .map_or(true, |x| !matches!(x, "0"));
 
-    // Insert 'env' command to explicitly pass environment variables to the
-    // elevated command
-    cmd = cmd.arg("env");
-    for arg in self.env_vars.iter().filter_map(|(key, action)| {
-      match action {
-        EnvAction::Set(value) => Some(format!("{key}={value}")),
-        EnvAction::Preserve if preserve_env => {
-          env::var(key).ok().map(|value| format!("{key}={value}"))
-        },
-        _ => None,
-      }
-    }) {
-      cmd = cmd.arg(arg);
-    }
-
-    Ok(cmd)
+    let mut vars: Vec<_> = self
+      .env_vars
+      .iter()
+      .filter_map(|(key, action)| {
+        match action {
+          EnvAction::Set(value) => Some((key.clone(), value.clone())),
+          EnvAction::Preserve if preserve_env => {
+            env::var(key).ok().map(|value| (key.clone(), value))
+          },
+          _ => None,
+        }
+      })
+      .collect();
+    vars.sort_unstable();
+    vars
   }
 
-  fn build_sudo_parts(&self) -> Result<Vec<String>> {
-    let elevation_program = self
+  /// The elevation program followed by its arguments, up to (but excluding)
+  /// the elevated command itself, and the `SUDO_ASKPASS` helper to set for it.
+  fn build_sudo_parts(&self) -> Result<(Vec<String>, Option<String>)> {
+    let elevation_strategy = self
       .elevate
       .as_ref()
-      .ok_or_else(|| eyre::eyre!("Command not found for elevation"))?
+      .ok_or_else(|| eyre::eyre!("Command not found for elevation"))?;
+    let elevation_program = elevation_strategy
       .resolve()
       .context("Failed to resolve elevation program")?;
 
-    let mut parts = vec![elevation_program.to_string_lossy().to_string()];
+    let program = ElevationProgram::from_path(&elevation_program);
 
-    let program_name = elevation_program
-      .file_name()
-      .and_then(|name| name.to_str())
-      .ok_or_else(|| {
-        eyre::eyre!("Failed to determine elevation program name")
-      })?;
-    if program_name == "sudo"
-      && let Ok(_askpass) = env::var("NH_SUDO_ASKPASS")
+    // Use NH_SUDO_ASKPASS program for sudo if present, but NOT for
+    // Passwordless variant (Passwordless expects NOPASSWD config without
+    // password input)
+    let askpass = if program == ElevationProgram::Sudo
+      && !matches!(elevation_strategy, ElevationStrategy::Passwordless)
     {
-      parts.push("-A".to_string());
-    }
-    // Request allocation of a pseudo TTY for the run0 session. Without this,
-    // running `run0` changes the user of `/dev/pts/<current-terminal>
-    // to `root`, which we want to avoid since it can cause issues with
-    // subsequent commands.
-    if program_name == "run0" {
-      parts.push("--pty-late".to_string());
-    }
-
-    if program_name == "sudo" {
-      parts.extend(get_sudo_opts());
-    }
+      env::var("NH_SUDO_ASKPASS").ok()
+    } else {
+      None
+    };
 
-    let preserve_env = env::var("NH_PRESERVE_ENV")
-      .as_deref()
-      .map_or(true, |x| !matches!(x, "0"));
+    let sudo_opts = if program == ElevationProgram::Sudo {
+      get_sudo_opts()
+    } else {
+      Vec::new()
+    };
 
-    parts.push("env".to_string());
-    for env_arg in self.env_vars.iter().filter_map(|(key, action)| {
-      match action {
-        EnvAction::Set(value) => Some(format!("{key}={value}")),
-        EnvAction::Preserve if preserve_env => {
-          env::var(key).map_or(None, |value| Some(format!("{key}={value}")))
-        },
-        _ => None,
-      }
-    }) {
-      parts.push(env_arg);
-    }
+    let mut parts = vec![elevation_program.to_string_lossy().to_string()];
+    parts.extend(elevation_args(
+      program,
+      askpass.is_some(),
+      sudo_opts,
+      &self.elevated_env(),
+    ));
 
-    Ok(parts)
+    Ok((parts, askpass))
   }
 
   /// Create a sudo command for self-elevation with proper environment handling
@@ -743,7 +855,7 @@ impl Command {
       .elevate(Some(strategy))
       .with_required_env();
 
-    let mut sudo_parts = cmd_builder.build_sudo_parts()?;
+    let (mut sudo_parts, askpass) = cmd_builder.build_sudo_parts()?;
 
     // Add the target executable and arguments
     sudo_parts.push(current_exe.to_string_lossy().to_string());
@@ -755,10 +867,7 @@ impl Command {
       std_cmd.args(&sudo_parts[1..]);
     }
 
-    // check if using SUDO_ASKPASS
-    if sudo_parts[1] == "-A"
-      && let Ok(askpass) = env::var("NH_SUDO_ASKPASS")
-    {
+    if let Some(askpass) = askpass {
       std_cmd.env("SUDO_ASKPASS", askpass);
     }
     Ok(std_cmd)
@@ -871,7 +980,7 @@ impl Command {
       info!("{m}");
     }
 
-    debug!(?cmd);
+    log_command(&cmd);
 
     if self.dry {
       return Ok(());
@@ -918,6 +1027,9 @@ impl Command {
 
   /// Run the configured command and capture its output.
   ///
+  /// At most [`Self::capture_limit`] bytes are kept. The rest of the output
+  /// is read and discarded, so a runaway command cannot exhaust memory.
+  ///
   /// # Errors
   ///
   /// Returns an error if the command fails to execute.
@@ -933,21 +1045,148 @@ impl Command {
       info!("{m}");
     }
 
-    debug!(?cmd);
+    log_command(&cmd);
 
     if self.dry {
       return Ok(None);
     }
-    Ok(Some(cmd.capture()?.stdout_str()))
+
+    let mut job = cmd.start().wrap_err("Failed to start command")?;
+    let stdout = job
+      .stdout
+      .take()
+      .ok_or_else(|| eyre::eyre!("Failed to capture stdout"))?;
+    let (stdout, truncated) = read_limited(stdout, self.capture_limit)
+      .wrap_err("Failed to read command output")?;
+    job
+      .wait()
+      .wrap_err("Failed to wait for command completion")?;
+
+    if truncated {
+      self.warn_truncated();
+    }
+    Ok(Some(String::from_utf8_lossy(&stdout).into_owned()))
+  }
+
+  /// Run the configured command without elevation and return its exit status
+  /// together with its combined stdout and stderr, or `None` in dry mode.
+  /// The output is also echoed to stderr if [`Self::show_output`] is set.
+  ///
+  /// At most [`Self::capture_limit`] bytes are kept, like with
+  /// [`Self::run_capture`]. Everything is still echoed.
+  ///
+  /// Unlike [`Self::run`], a non-zero exit status is not an error, so the
+  /// caller can inspect the output first.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the command fails to start or its output cannot be
+  /// read.
+  pub fn run_with_output(&self) -> Result<Option<(ExitStatus, String)>> {
+    let cmd = self.apply_env_to_exec(
+      Exec::cmd(&self.command)
+        .args(&self.args)
+        .stderr(Redirection::Merge)
+        .stdout(Redirection::Pipe),
+    );
+
+    if let Some(m) = &self.message {
+      info!("{m}");
+    }
+
+    log_command(&cmd);
+
+    if self.dry {
+      return Ok(None);
+    }
+
+    let mut job = cmd.start().wrap_err("Failed to start command")?;
+    let stdout = job
+      .stdout
+      .take()
+      .ok_or_else(|| eyre::eyre!("Failed to capture output"))?;
+
+    let (output, truncated) = if self.show_output {
+      read_limited(EchoToStderr(stdout), self.capture_limit)
+    } else {
+      read_limited(stdout, self.capture_limit)
+    }
+    .wrap_err("Failed to read command output")?;
+
+    let exit_status = job
+      .wait()
+      .wrap_err("Failed to wait for command completion")?;
+
+    if truncated {
+      self.warn_truncated();
+    }
+    Ok(Some((
+      exit_status,
+      String::from_utf8_lossy(&output).into_owned(),
+    )))
+  }
+
+  fn warn_truncated(&self) {
+    warn!(
+      "Output of {} exceeded {} bytes and was truncated",
+      self.command.to_string_lossy(),
+      self.capture_limit
+    );
+  }
+}
+
+/// A reader that copies everything read through it to stderr.
+struct EchoToStderr<R>(R);
+
+impl<R: Read> Read for EchoToStderr<R> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let read = self.0.read(buf)?;
+    std::io::stderr().write_all(&buf[..read])?;
+    Ok(read)
   }
 }
 
+/// Read at most `limit` bytes from `reader`, then drain the rest so the
+/// writing process does not block. Returns the kept bytes and whether any
+/// were discarded.
+fn read_limited(
+  reader: impl Read,
+  limit: usize,
+) -> std::io::Result<(Vec<u8>, bool)> {
+  let limit = u64::try_from(limit).unwrap_or(u64::MAX);
+  let mut kept = Vec::new();
+  let mut limited = reader.take(limit);
+  limited.read_to_end(&mut kept)?;
+  let discarded =
+    std::io::copy(&mut limited.into_inner(), &mut std::io::sink())?;
+  Ok((kept, discarded > 0))
+}
+
+/// Whether nix-output-monitor is installed. When it is not, a warning is
+/// logged the first time this is called, so builds can fall back to plain Nix
+/// output instead of failing.
+#[must_use]
+pub fn nom_available() -> bool {
+  static AVAILABLE: OnceLock<bool> = OnceLock::new();
+  *AVAILABLE.get_or_init(|| {
+    let available = which("nom").is_ok();
+    if !available {
+      warn!(
+        "nix-output-monitor (nom) was not found in PATH, showing plain Nix \
+         output instead. Pass --no-nom to silence this warning"
+      );
+    }
+    available
+  })
+}
+
 #[derive(Debug)]
 pub struct Build {
   message:     Option<String>,
   installable: Installable,
   extra_args:  Vec<OsString>,
   nom:         bool,
+  nom_args:    Vec<String>,
 }
 
 impl Build {
@@ -958,6 +1197,7 @@ impl Build {
       installable,
       extra_args: vec![],
       nom: false,
+      nom_args: vec![],
     }
   }
 
@@ -979,6 +1219,13 @@ impl Build {
     self
   }
 
+  /// Extra arguments for nix-output-monitor, ignored without [`Self::nom`].
+  #[must_use]
+  pub fn nom_args(mut self, args: Vec<String>) -> Self {
+    self.nom_args = args;
+    self
+  }
+
   #[must_use]
   pub fn extra_args<I>(mut self, args: I) -> Self
   where
@@ -1014,16 +1261,14 @@ impl Build {
       .args(&self.extra_args)
       .to_exec();
 
-    if self.nom {
-      let pipeline = {
-        base_command
-          .args(["--log-format", "internal-json", "--verbose"])
-          .stderr(Redirection::Merge)
-          .stdout(Redirection::Pipe)
-          | Exec::cmd("nom").args(["--json"])
-      }
-      .stdout(Redirection::None);
-      debug!(?pipeline);
+    if self.nom && nom_available() {
+      let nix = base_command
+        .args(["--log-format", "internal-json", "--verbose"])
+        .stderr(Redirection::Merge)
+        .stdout(Redirection::Pipe);
+      let nom = Exec::cmd("nom").args(["--json"]).args(&self.nom_args);
+      log_pipeline(&[&nix, &nom]);
+      let pipeline = (nix | nom).stdout(Redirection::None);
 
       // Use `popen()` to get access to individual processes so we can check
       // Nix's exit status, not nom's. The pipeline's `join()` only returns
@@ -1041,7 +1286,7 @@ impl Build {
       if let Some(nix_proc) = job.processes.first() {
         let exit_status = nix_proc.wait()?;
         if !exit_status.success() {
-          bail!(ExitError(exit_status));
+          return Err(self.failed(exit_status));
         }
       }
     } else {
@@ -1049,22 +1294,155 @@ impl Build {
         .stderr(Redirection::Merge)
         .stdout(Redirection::None);
 
-      debug!(?cmd);
+      log_command(&cmd);
       let exit = cmd.join();
 
       let exit_status = exit?;
       if !exit_status.success() {
-        bail!(ExitError(exit_status));
+        return Err(self.failed(exit_status));
       }
     }
 
     Ok(())
   }
+
+  /// Run the build command and return the store path from the `--json` build
+  /// results. The result link is created at `out_link`, or not at all if it
+  /// is `None`. The JSON is only printed if `--json` was passed through.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the build command fails to execute, or if Nix does
+  /// not report an output path.
+  pub fn run_out_path(&self, out_link: Option<&Path>) -> Result<PathBuf> {
+    if let Some(m) = &self.message {
+      info!("{m}");
+    }
+
+    let installable_args = self.installable.to_args();
+
+    let link_args: Vec<&OsStr> = out_link.map_or_else(
+      || vec!["--no-link".as_ref()],
+      |out_link| vec!["--out-link".as_ref(), out_link.as_os_str()],
+    );
+    let base_command = NixCommand::new(CommandKind::Build)
+      .print_build_logs(false)
+      .args(&installable_args)
+      .args(&self.extra_args)
+      .args(link_args)
+      .arg("--json")
+      .to_exec()
+      .stdout(Redirection::Pipe);
+
+    let (stdout, exit_status) = if self.nom && nom_available() {
+      let cmd = base_command
+        .args(["--log-format", "internal-json", "--verbose"])
+        .stderr(Redirection::Pipe);
+      log_command(&cmd);
+
+      // Only the logs on stderr go through nom, stdout carries the out paths
+      // we are after.
+      let mut nix = cmd.start()?;
+      let logs = nix
+        .stderr
+        .take()
+        .ok_or_else(|| eyre::eyre!("Failed to capture nix build logs"))?;
+      let nom = Exec::cmd("nom")
+        .args(["--json"])
+        .args(&self.nom_args)
+        .stdin(logs)
+        .stdout(Redirection::None)
+        .start()?;
+
+      let mut stdout = String::new();
+      if let Some(mut out) = nix.stdout.take() {
+        out.read_to_string(&mut stdout)?;
+      }
+
+      nom.wait()?;
+      (stdout, nix.wait()?)
+    } else {
+      let cmd = base_command.stderr(Redirection::None);
+      log_command(&cmd);
+
+      let mut job = cmd.start()?;
+      let mut stdout = String::new();
+      if let Some(mut out) = job.stdout.take() {
+        out.read_to_string(&mut stdout)?;
+      }
+
+      (stdout, job.wait()?)
+    };
+
+    if !exit_status.success() {
+      return Err(self.failed(exit_status));
+    }
+    if self.extra_args.iter().any(|arg| arg == "--json") {
+      print!("{stdout}");
+    }
+
+    out_path_from_build_json(&stdout)
+  }
+
+  /// The error for a failed build. Suggests `--show-trace` when the failure
+  /// looks like an evaluation error and no trace was requested.
+  fn failed(&self, exit_status: ExitStatus) -> eyre::Report {
+    let show_trace = self.extra_args.iter().any(|arg| arg == "--show-trace");
+    if suggests_show_trace(exit_status.code(), show_trace) {
+      warn!("If evaluation failed, rerun with --show-trace for the full trace");
+    }
+    ExitError(exit_status).into()
+  }
+}
+
+/// The `out` output of the first result of `nix build --json`, falling back
+/// to its first output for derivations without an `out` output.
+fn out_path_from_build_json(json: &str) -> Result<PathBuf> {
+  let results: serde_json::Value = serde_json::from_str(json)
+    .wrap_err("Failed to parse nix build JSON output")?;
+  let outputs = results
+    .get(0)
+    .and_then(|result| result.get("outputs"))
+    .and_then(serde_json::Value::as_object);
+
+  outputs
+    .and_then(|outputs| {
+      outputs
+        .get("out")
+        .or_else(|| outputs.values().next())?
+        .as_str()
+    })
+    .map(PathBuf::from)
+    .ok_or_else(|| eyre::eyre!("nix build did not report an output path"))
+}
+
+/// Nix exits with 100 and up when a derivation fails to build, and with 1 for
+/// other errors, which for a build are almost always evaluation errors.
+pub(crate) const fn is_eval_exit_code(code: Option<u32>) -> bool {
+  matches!(code, Some(1))
 }
 
+const fn suggests_show_trace(code: Option<u32>, show_trace: bool) -> bool {
+  is_eval_exit_code(code) && !show_trace
+}
+
+/// Nix failed to evaluate an installable, e.g. because an attribute does not
+/// exist.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct EvalError(pub String);
+
 #[derive(Debug, Error)]
 #[error("Command exited with status {0:?}")]
-pub struct ExitError(ExitStatus);
+pub struct ExitError(pub(crate) ExitStatus);
+
+impl ExitError {
+  /// The exit code of the failed command, if it exited normally.
+  #[must_use]
+  pub fn code(&self) -> Option<u32> {
+    self.0.code()
+  }
+}
 
 #[cfg(test)]
 mod tests {
@@ -1138,6 +1516,59 @@ mod tests {
     assert!(cmd.ssh.is_none());
     assert!(!cmd.show_output);
     assert!(cmd.env_vars.is_empty());
+    assert_eq!(cmd.capture_limit, DEFAULT_CAPTURE_LIMIT);
+  }
+
+  #[test]
+  fn test_out_path_from_build_json() {
+    let json = r#"[{"drvPath":"/nix/store/abc-nixos-system.drv",
+      "outputs":{"out":"/nix/store/abc-nixos-system"}}]"#;
+    assert_eq!(
+      out_path_from_build_json(json).unwrap(),
+      PathBuf::from("/nix/store/abc-nixos-system")
+    );
+
+    let json = r#"[{"outputs":{"bin":"/nix/store/abc-tool-bin"}}]"#;
+    assert_eq!(
+      out_path_from_build_json(json).unwrap(),
+      PathBuf::from("/nix/store/abc-tool-bin")
+    );
+
+    assert!(out_path_from_build_json("[]").is_err());
+    assert!(out_path_from_build_json("/nix/store/abc").is_err());
+  }
+
+  #[test]
+  fn test_show_trace_suggested_for_evaluation_errors() {
+    assert!(suggests_show_trace(Some(1), false));
+    assert!(!suggests_show_trace(Some(1), true));
+    assert!(!suggests_show_trace(Some(100), false));
+    assert!(!suggests_show_trace(None, false));
+  }
+
+  #[test]
+  fn test_read_limited_truncates_and_drains() {
+    let mut input = std::io::Cursor::new(b"0123456789".to_vec());
+    let (kept, truncated) = read_limited(&mut input, 4).unwrap();
+    assert_eq!(kept, b"0123");
+    assert!(truncated);
+    assert_eq!(input.position(), 10);
+
+    let (kept, truncated) = read_limited(&b"0123"[..], 4).unwrap();
+    assert_eq!(kept, b"0123");
+    assert!(!truncated);
+  }
+
+  #[test]
+  fn test_run_with_output_keeps_at_most_capture_limit() {
+    let (status, output) = Command::new("printf")
+      .arg("0123456789")
+      .capture_limit(4)
+      .run_with_output()
+      .unwrap()
+      .unwrap();
+    assert!(status.success());
+    assert_eq!(output, "0123");
   }
 
   #[test]
@@ -1548,6 +1979,128 @@ mod tests {
     assert!(cmdline.contains("'NIX_CONFIG="));
   }
 
+  #[test]
+  fn test_parse_preserve_env_list() {
+    assert_eq!(parse_preserve_env_list("FOO, BAR,,BAZ "), [
+      "FOO", "BAR", "BAZ"
+    ]);
+    assert!(parse_preserve_env_list("").is_empty());
+  }
+
+  #[test]
+  #[serial]
+  fn test_with_required_env_preserves_extra_vars() {
+    let _extra_guard = EnvGuard::new("MY_ACTIVATION_VAR", "value");
+    let _preserve_env_guard =
+      EnvGuard::new("NH_PRESERVE_ENV", "MY_ACTIVATION_VAR, MY_UNSET_VAR");
+    unsafe {
+      env::remove_var("MY_UNSET_VAR");
+    }
+
+    let cmd = Command::new("test").with_required_env();
+
+    assert!(matches!(
+      cmd.env_vars.get("MY_ACTIVATION_VAR"),
+      Some(EnvAction::Preserve)
+    ));
+    assert!(!cmd.env_vars.contains_key("MY_UNSET_VAR"));
+  }
+
+  #[test]
+  #[serial]
+  fn test_with_required_env_keeps_required_vars_with_preserve_env_toggle() {
+    let _sshopts_guard = EnvGuard::new("NIX_SSHOPTS", "-p 2222");
+    for toggle in ["0", "1"] {
+      let _preserve_env_guard = EnvGuard::new("NH_PRESERVE_ENV", toggle);
+      assert!(extra_preserved_env().is_empty(), "{toggle}");
+
+      let cmd = Command::new("test").with_required_env();
+      assert!(
+        matches!(cmd.env_vars.get("NIX_SSHOPTS"), Some(EnvAction::Preserve)),
+        "{toggle}"
+      );
+    }
+  }
+
+  fn fixed_env() -> Vec<(String, String)> {
+    vec![
+      ("NH_FLAKE".to_string(), "/etc/nixos".to_string()),
+      ("NIX_CONFIG".to_string(), "warn-dirty = false".to_string()),
+    ]
+  }
+
+  #[test]
+  fn test_elevation_args_sudo() {
+    let args = elevation_args(
+      ElevationProgram::Sudo,
+      true,
+      vec!["--preserve-env=PATH".to_string()],
+      &fixed_env(),
+    );
+    assert_eq!(args, [
+      "-A",
+      "--preserve-env=PATH",
+      "env",
+      "NH_FLAKE=/etc/nixos",
+      "NIX_CONFIG=warn-dirty = false",
+    ]);
+  }
+
+  #[test]
+  fn test_elevation_args_doas() {
+    let args =
+      elevation_args(ElevationProgram::Doas, false, vec![], &fixed_env());
+    assert_eq!(args, [
+      "env",
+      "NH_FLAKE=/etc/nixos",
+      "NIX_CONFIG=warn-dirty = false",
+    ]);
+  }
+
+  #[test]
+  fn test_elevation_args_run0_uses_setenv() {
+    let args =
+      elevation_args(ElevationProgram::Run0, false, vec![], &fixed_env());
+    assert_eq!(args, [
+      "--pty-late",
+      "--setenv=NH_FLAKE=/etc/nixos",
+      "--setenv=NIX_CONFIG=warn-dirty = false",
+    ]);
+  }
+
+  #[test]
+  fn test_elevation_args_pkexec() {
+    let args =
+      elevation_args(ElevationProgram::Pkexec, false, vec![], &fixed_env());
+    assert_eq!(args, [
+      "env",
+      "NH_FLAKE=/etc/nixos",
+      "NIX_CONFIG=warn-dirty = false",
+    ]);
+  }
+
+  #[test]
+  fn test_elevation_program_from_path() {
+    for (path, program) in [
+      ("/run/wrappers/bin/sudo", ElevationProgram::Sudo),
+      ("/run/wrappers/bin/doas", ElevationProgram::Doas),
+      ("/run/current-system/sw/bin/run0", ElevationProgram::Run0),
+      ("/run/wrappers/bin/pkexec", ElevationProgram::Pkexec),
+      ("/usr/local/bin/please", ElevationProgram::Other),
+    ] {
+      assert_eq!(ElevationProgram::from_path(Path::new(path)), program);
+    }
+  }
+
+  #[test]
+  fn test_elevate_with_none_strategy_runs_unprivileged() {
+    let cmd = Command::new("test").elevate(Some(ElevationStrategy::None));
+    assert!(cmd.elevate.is_none());
+
+    let cmd = Command::new("test").elevate(Some(ElevationStrategy::Auto));
+    assert_eq!(cmd.elevate, Some(ElevationStrategy::Auto));
+  }
+
   #[test]
   fn test_build_new() {
     let installable = Installable::Flake {
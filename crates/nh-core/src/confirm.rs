@@ -0,0 +1,22 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use inquire::Confirm;
+
+static DEFAULT_YES: AtomicBool = AtomicBool::new(false);
+
+/// Make pressing Enter at a [`confirm`] prompt proceed instead of declining.
+/// Set from `--default-yes`.
+pub fn set_default_yes(enabled: bool) {
+  DEFAULT_YES.store(enabled, Ordering::Relaxed);
+}
+
+/// A prompt to go ahead with an action the user asked to confirm with
+/// `--ask`, e.g. activating a configuration. Enter declines unless
+/// `--default-yes` is set.
+///
+/// Prompts about trusting configuration, like accepting a flake's
+/// `nixConfig`, always default to no and should not use this.
+#[must_use]
+pub fn confirm(message: &str) -> Confirm<'_> {
+  Confirm::new(message).with_default(DEFAULT_YES.load(Ordering::Relaxed))
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+
+use clap::Args;
+use color_eyre::{Result, eyre::bail};
+use nh_installable::{CommandContext, InstallableArgs};
+use semver::Version;
+use yansi::{Color, Paint};
+
+use crate::{
+  checks::{FeatureRequirements, OsReplFeatures, min_nix_version},
+  command::ElevationStrategy,
+  util::{self, normalize_version_string},
+};
+
+#[derive(Debug, Args)]
+/// Check the environment for common misconfigurations
+pub struct DoctorArgs {}
+
+/// Outcome of a single doctor check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+  Pass,
+  Warn,
+  Fail,
+}
+
+impl fmt::Display for CheckStatus {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let label = match self {
+      Self::Pass => Paint::new("PASS").fg(Color::Green),
+      Self::Warn => Paint::new("WARN").fg(Color::Yellow),
+      Self::Fail => Paint::new("FAIL").fg(Color::Red),
+    };
+    write!(f, "{}", label.bold())
+  }
+}
+
+/// A single line of the `nh doctor` checklist.
+#[derive(Debug)]
+pub struct CheckReport {
+  pub name:   &'static str,
+  pub status: CheckStatus,
+  pub detail: String,
+  /// How to fix the problem, shown for anything that did not pass.
+  pub hint:   Option<String>,
+}
+
+impl CheckReport {
+  fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+    Self {
+      name,
+      status: CheckStatus::Pass,
+      detail: detail.into(),
+      hint: None,
+    }
+  }
+
+  fn warn(
+    name: &'static str,
+    detail: impl Into<String>,
+    hint: impl Into<String>,
+  ) -> Self {
+    Self {
+      name,
+      status: CheckStatus::Warn,
+      detail: detail.into(),
+      hint: Some(hint.into()),
+    }
+  }
+
+  fn fail(
+    name: &'static str,
+    detail: impl Into<String>,
+    hint: impl Into<String>,
+  ) -> Self {
+    Self {
+      name,
+      status: CheckStatus::Fail,
+      detail: detail.into(),
+      hint: Some(hint.into()),
+    }
+  }
+}
+
+impl DoctorArgs {
+  /// Run every check, print the checklist and fail if any check failed.
+  ///
+  /// Checks never abort each other; warnings do not make the command fail.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if at least one check reported [`CheckStatus::Fail`].
+  pub fn run(&self, elevation: &ElevationStrategy) -> Result<()> {
+    let reports = run_checks(elevation);
+
+    for report in &reports {
+      println!("{} {}: {}", report.status, report.name, report.detail);
+      if let Some(hint) = &report.hint {
+        println!("     {}", Paint::new(hint).dim());
+      }
+    }
+
+    let failed = reports
+      .iter()
+      .filter(|report| report.status == CheckStatus::Fail)
+      .count();
+    if failed > 0 {
+      bail!("{failed} check(s) failed");
+    }
+
+    Ok(())
+  }
+}
+
+/// Run all environment checks without failing early.
+#[must_use]
+pub fn run_checks(elevation: &ElevationStrategy) -> Vec<CheckReport> {
+  let mut reports = vec![
+    check_nix_version(),
+    check_experimental_features(),
+    check_elevation(elevation),
+  ];
+
+  let contexts = if cfg!(target_os = "macos") {
+    [CommandContext::Darwin, CommandContext::Home]
+  } else {
+    [CommandContext::Os, CommandContext::Home]
+  };
+  reports.extend(contexts.into_iter().map(check_default_flake));
+
+  reports
+}
+
+fn check_nix_version() -> CheckReport {
+  const NAME: &str = "Nix version";
+
+  let variant = util::get_nix_variant();
+  let version = match util::get_nix_version() {
+    Ok(version) => version,
+    Err(err) => {
+      return CheckReport::fail(
+        NAME,
+        format!("could not run `nix --version`: {err}"),
+        "Make sure Nix is installed and `nix` is in PATH",
+      );
+    },
+  };
+
+  let minimum = min_nix_version(variant);
+  let name = variant.display_name();
+  match version_at_least(&version, minimum) {
+    Some(true) => CheckReport::pass(NAME, version),
+    Some(false) => {
+      CheckReport::warn(
+        NAME,
+        format!("{version} is older than the recommended {minimum}"),
+        format!("Upgrade {name} to {minimum} or newer"),
+      )
+    },
+    None => {
+      CheckReport::warn(
+        NAME,
+        format!("{version} could not be compared to {minimum}"),
+        "Please report your Nix version string to nh",
+      )
+    },
+  }
+}
+
+/// Whether a raw `nix --version` string is at least `minimum`, or `None` if
+/// either version cannot be parsed.
+fn version_at_least(version: &str, minimum: &str) -> Option<bool> {
+  let current = Version::parse(&normalize_version_string(version)).ok()?;
+  let required = Version::parse(minimum).ok()?;
+  Some(current >= required)
+}
+
+fn check_experimental_features() -> CheckReport {
+  const NAME: &str = "Experimental features";
+
+  // Flake repls have the largest set of requirements, including repl-flake on
+  // older Lix versions.
+  let required = OsReplFeatures { is_flake: true }.required_features();
+  if required.is_empty() {
+    return CheckReport::pass(NAME, "none required by this Nix variant");
+  }
+
+  match util::get_missing_experimental_features(&required) {
+    Ok(missing) if missing.is_empty() => {
+      CheckReport::pass(NAME, required.join(", "))
+    },
+    Ok(missing) => {
+      CheckReport::warn(
+        NAME,
+        format!("missing {}", missing.join(", ")),
+        format!(
+          "Add `experimental-features = {}` to nix.conf to use flakes",
+          required.join(" ")
+        ),
+      )
+    },
+    Err(err) => {
+      CheckReport::fail(
+        NAME,
+        format!("could not query enabled features: {err}"),
+        "Make sure `nix config show` works",
+      )
+    },
+  }
+}
+
+fn check_elevation(elevation: &ElevationStrategy) -> CheckReport {
+  const NAME: &str = "Elevation";
+
+  if matches!(elevation, ElevationStrategy::None) {
+    return CheckReport::pass(NAME, "disabled (--elevation-strategy none)");
+  }
+
+  match elevation.resolve() {
+    Ok(program) => CheckReport::pass(NAME, program.display().to_string()),
+    Err(err) => {
+      CheckReport::fail(
+        NAME,
+        err.to_string(),
+        "Install sudo, doas, run0 or pkexec, or pass --elevation-strategy",
+      )
+    },
+  }
+}
+
+fn check_default_flake(context: CommandContext) -> CheckReport {
+  let name = match context {
+    CommandContext::Os => "NixOS flake",
+    CommandContext::Home => "Home Manager flake",
+    CommandContext::Darwin => "nix-darwin flake",
+  };
+
+  let installable = InstallableArgs::Unspecified { attr: None };
+  match installable.resolve_or_default(context) {
+    Ok(installable) => CheckReport::pass(name, installable.to_args().join(" ")),
+    Err(err) => {
+      let message = err.to_string();
+      CheckReport::warn(
+        name,
+        message.lines().next().unwrap_or_default().to_string(),
+        "Set NH_FLAKE, or pass the installable on the command line",
+      )
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn version_at_least_compares_normalized_versions() {
+    assert_eq!(version_at_least("nix (Nix) 2.31.2", "2.31.2"), Some(true));
+    assert_eq!(
+      version_at_least("nix (Lix, like Nix) 2.93.3", "2.93.3"),
+      Some(true)
+    );
+    assert_eq!(
+      version_at_least("2.32pre20250521_76a4d4c2", "2.31.2"),
+      Some(true)
+    );
+    assert_eq!(version_at_least("2.24.14-1", "2.31.2"), Some(false));
+  }
+
+  #[test]
+  fn version_at_least_rejects_unparseable_versions() {
+    assert_eq!(version_at_least("2.31.2", "latest"), None);
+  }
+}
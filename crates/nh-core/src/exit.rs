@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use color_eyre::Report;
+use inquire::InquireError;
+use thiserror::Error;
+
+use crate::command::ExitError;
+
+/// The phase of a rebuild that is currently running. When a rebuild fails,
+/// `nh` exits with the code of the phase it failed in, so scripts can tell a
+/// failed build from a failed activation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Phase {
+  /// Evaluating, updating and building the configuration
+  Build      = 1,
+  /// Activating the built configuration
+  Activation = 2,
+  /// Checks that run before anything is built, e.g. the Nix version or
+  /// whether `nh` is running as root
+  Precheck   = 4,
+}
+
+/// Exit code used when the user declines a confirmation prompt.
+pub const EXIT_ABORTED: i32 = 3;
+
+static CURRENT_PHASE: AtomicU8 = AtomicU8::new(0);
+
+/// Record that the rebuild has entered `phase`.
+pub fn enter(phase: Phase) {
+  CURRENT_PHASE.store(phase as u8, Ordering::Relaxed);
+}
+
+/// Record that no rebuild phase is running anymore.
+pub fn leave() {
+  CURRENT_PHASE.store(0, Ordering::Relaxed);
+}
+
+fn current_phase() -> Option<Phase> {
+  match CURRENT_PHASE.load(Ordering::Relaxed) {
+    1 => Some(Phase::Build),
+    2 => Some(Phase::Activation),
+    4 => Some(Phase::Precheck),
+    _ => None,
+  }
+}
+
+/// The user declined a confirmation prompt.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct Aborted(pub &'static str);
+
+/// The exit code `nh` should use for `report`, or `None` for the default
+/// of 1.
+///
+/// Declined and cancelled prompts exit with [`EXIT_ABORTED`], failures
+/// during a rebuild with the code of the [`Phase`] they happened in, and
+/// other failed Nix commands with the exit code of that command.
+#[must_use]
+pub fn exit_code(report: &Report) -> Option<i32> {
+  code_for(report, current_phase())
+}
+
+fn code_for(report: &Report, phase: Option<Phase>) -> Option<i32> {
+  let aborted = report.chain().any(|err| {
+    err.is::<Aborted>()
+      || matches!(
+        err.downcast_ref::<InquireError>(),
+        Some(
+          InquireError::OperationCanceled | InquireError::OperationInterrupted
+        )
+      )
+  });
+  if aborted {
+    return Some(EXIT_ABORTED);
+  }
+
+  if let Some(phase) = phase {
+    return Some(i32::from(phase as u8));
+  }
+
+  report
+    .chain()
+    .find_map(|err| err.downcast_ref::<ExitError>())
+    .and_then(ExitError::code)
+    .and_then(|code| i32::try_from(code).ok())
+}
+
+#[cfg(test)]
+#[expect(clippy::expect_used, reason = "Fine in tests")]
+mod tests {
+  use color_eyre::eyre::eyre;
+
+  use super::*;
+
+  #[test]
+  fn aborted_takes_precedence_over_phase() {
+    let report = Report::new(Aborted("User rejected the new config"));
+    assert_eq!(
+      code_for(&report, Some(Phase::Activation)),
+      Some(EXIT_ABORTED)
+    );
+  }
+
+  #[test]
+  fn cancelled_prompt_counts_as_aborted() {
+    let report = Report::new(InquireError::OperationInterrupted)
+      .wrap_err("Failed to read confirmation");
+    assert_eq!(code_for(&report, None), Some(EXIT_ABORTED));
+  }
+
+  #[test]
+  fn failure_uses_code_of_current_phase() {
+    let report = eyre!("Failed to build configuration");
+    assert_eq!(code_for(&report, Some(Phase::Build)), Some(1));
+    assert_eq!(code_for(&report, Some(Phase::Activation)), Some(2));
+    assert_eq!(code_for(&report, Some(Phase::Precheck)), Some(4));
+  }
+
+  #[test]
+  fn failed_command_outside_rebuild_uses_its_exit_code() {
+    let status = subprocess::Exec::cmd("sh")
+      .args(["-c", "exit 3"])
+      .join()
+      .expect("failed to run sh");
+    let report =
+      Report::new(ExitError(status)).wrap_err("nix flake check failed");
+    assert_eq!(code_for(&report, None), Some(3));
+    assert_eq!(code_for(&report, Some(Phase::Build)), Some(1));
+  }
+
+  #[test]
+  fn failure_outside_rebuild_uses_default() {
+    let report = eyre!("No results found");
+    assert_eq!(code_for(&report, None), None);
+  }
+}
@@ -0,0 +1,124 @@
+use clap::Args;
+use color_eyre::{Result, eyre::bail};
+use nh_installable::{CommandContext, Installable, InstallableArgs};
+use nix_command::{CommandKind, NixCommand};
+use subprocess::Redirection;
+use tracing::{info, warn};
+
+use crate::{
+  args::NixBuildPassthroughArgs,
+  command::{ExitError, log_command},
+};
+
+#[derive(Debug, Args)]
+pub struct FlakeCheckArgs {
+  #[command(flatten)]
+  pub installable: InstallableArgs,
+
+  #[command(flatten)]
+  pub passthrough: NixBuildPassthroughArgs,
+
+  /// Extra arguments passed to nix flake check
+  #[arg(last = true)]
+  pub extra_args: Vec<String>,
+}
+
+impl FlakeCheckArgs {
+  #[must_use]
+  pub fn uses_flakes(&self, context: CommandContext) -> bool {
+    self.installable.uses_flakes(context)
+  }
+
+  /// Resolve the installable for `context` and run `nix flake check` on it.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the installable cannot be resolved, is not a flake,
+  /// or if `nix flake check` fails. A failing check is reported as an
+  /// [`ExitError`] carrying the exit status of Nix.
+  pub fn run(self, context: CommandContext) -> Result<()> {
+    self.passthrough.validate()?;
+    let installable = self.installable.resolve_or_default(context)?;
+
+    flake_check(
+      &installable,
+      &self.passthrough.generate_passthrough_args(),
+      &self.extra_args,
+    )
+  }
+}
+
+/// Run `nix flake check` against the flake of an installable, streaming its
+/// output.
+///
+/// Any attribute path on the installable is ignored, since `nix flake check`
+/// always evaluates the whole flake.
+///
+/// # Errors
+///
+/// Returns an error if the installable is not a flake, or if `nix flake check`
+/// cannot be started or exits unsuccessfully.
+pub fn flake_check(
+  installable: &Installable,
+  passthrough_args: &[String],
+  extra_args: &[String],
+) -> Result<()> {
+  let Installable::Flake {
+    reference,
+    attribute,
+  } = installable
+  else {
+    bail!(
+      "Only flake installables can be checked, {} is not supported",
+      installable.str_kind()
+    );
+  };
+
+  if !attribute.is_empty() {
+    warn!(
+      "Ignoring attribute '{}': nix flake check evaluates the entire flake",
+      attribute.join(".")
+    );
+  }
+
+  info!("Checking flake {reference}");
+
+  let cmd = NixCommand::new(CommandKind::Flake)
+    .arg("check")
+    .arg(reference)
+    .args(passthrough_args)
+    .args(extra_args)
+    .with_required_env()
+    .to_exec()
+    .stderr(Redirection::Merge)
+    .stdout(Redirection::None);
+
+  log_command(&cmd);
+
+  let exit_status = cmd.join()?;
+  if !exit_status.success() {
+    bail!(ExitError(exit_status));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+#[expect(clippy::expect_used, reason = "Fine in tests")]
+mod tests {
+  use std::path::PathBuf;
+
+  use super::*;
+
+  #[test]
+  fn non_flake_installables_are_rejected() {
+    let installable = Installable::File {
+      path:      PathBuf::from("default.nix"),
+      attribute: vec![],
+    };
+
+    let err = flake_check(&installable, &[], &[])
+      .expect_err("file installables cannot be checked");
+    assert!(err.to_string().contains("Only flake installables"));
+  }
+}
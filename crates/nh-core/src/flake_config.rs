@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+
+use color_eyre::{
+  Result,
+  eyre::{Context, eyre},
+};
+use nh_installable::Installable;
+use nix_command::{CommandKind, NixCommand};
+use serde_json::{Map, Value};
+use tracing::{debug, warn};
+
+use crate::{flake_metadata::get_flake_metadata, util::capture_nix_stdout};
+
+/// Ask whether the `nixConfig` of a flake installable should be trusted.
+///
+/// The requested settings are printed before prompting, so the user can see
+/// which substituters and keys they are about to accept. Returns `false`
+/// without prompting for non-flake installables and for flakes that do not
+/// set `nixConfig`.
+///
+/// # Errors
+///
+/// Returns an error if the flake cannot be fetched or evaluated, or if the
+/// prompt fails.
+pub fn confirm_flake_config(installable: &Installable) -> Result<bool> {
+  let Installable::Flake { reference, .. } = installable else {
+    debug!(
+      "Not asking about flake config for a {} installable",
+      installable.str_kind()
+    );
+    return Ok(false);
+  };
+
+  let settings = get_flake_nix_config(reference)?;
+  if settings.is_empty() {
+    debug!("Flake {reference} does not set nixConfig");
+    return Ok(false);
+  }
+
+  warn!("Flake {reference} wants to set the following Nix settings:");
+  for line in format_settings(&settings) {
+    eprintln!("  {line}");
+  }
+
+  inquire::Confirm::new("Accept this flake configuration?")
+    .with_default(false)
+    .prompt()
+    .context("Failed to read flake config confirmation")
+}
+
+/// Fetch a flake and read the `nixConfig` attribute of its `flake.nix`.
+///
+/// `flake.nix` is imported directly rather than through `getFlake`, because
+/// `nixConfig` is not part of the flake outputs.
+fn get_flake_nix_config(reference: &str) -> Result<Map<String, Value>> {
+  let metadata = get_flake_metadata(reference)?;
+  let flake_nix = flake_nix_path(&metadata).ok_or_else(|| {
+    eyre!("Flake metadata for {reference} has no source path")
+  })?;
+
+  let config = capture_nix_stdout(
+    &NixCommand::new(CommandKind::Eval)
+      .arg("--json")
+      .arg("--file")
+      .arg(flake_nix)
+      .arg("--apply")
+      .arg("flake: flake.nixConfig or { }"),
+  )?;
+
+  serde_json::from_str(&config).wrap_err("Failed to parse flake nixConfig")
+}
+
+/// Location of `flake.nix` in the fetched source described by `nix flake
+/// metadata --json`, honoring the `dir` of the locked reference.
+fn flake_nix_path(metadata: &Value) -> Option<PathBuf> {
+  let mut path = PathBuf::from(metadata.get("path")?.as_str()?);
+  if let Some(dir) = metadata
+    .get("resolved")
+    .and_then(|resolved| resolved.get("dir"))
+    .and_then(Value::as_str)
+  {
+    path.push(dir);
+  }
+  path.push("flake.nix");
+  Some(path)
+}
+
+/// Render each setting as `name = value`, the way it would appear in
+/// `nix.conf`.
+fn format_settings(settings: &Map<String, Value>) -> Vec<String> {
+  settings
+    .iter()
+    .map(|(name, value)| format!("{name} = {}", format_value(value)))
+    .collect()
+}
+
+fn format_value(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    Value::Array(items) => {
+      items.iter().map(format_value).collect::<Vec<_>>().join(" ")
+    },
+    other => other.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn flake_nix_path_uses_source_path() {
+    let metadata = json!({ "path": "/nix/store/abc-source" });
+    assert_eq!(
+      flake_nix_path(&metadata),
+      Some(PathBuf::from("/nix/store/abc-source/flake.nix"))
+    );
+  }
+
+  #[test]
+  fn flake_nix_path_honors_subdirectory() {
+    let metadata = json!({
+      "path": "/nix/store/abc-source",
+      "resolved": { "type": "github", "dir": "nix" },
+    });
+    assert_eq!(
+      flake_nix_path(&metadata),
+      Some(PathBuf::from("/nix/store/abc-source/nix/flake.nix"))
+    );
+  }
+
+  #[test]
+  fn flake_nix_path_requires_source_path() {
+    assert_eq!(flake_nix_path(&json!({})), None);
+  }
+
+  #[test]
+  fn format_settings_renders_nix_conf_lines() {
+    let mut settings = Map::new();
+    settings.insert(
+      "extra-substituters".into(),
+      json!(["https://a.cachix.org", "https://b.cachix.org"]),
+    );
+    settings.insert(
+      "extra-trusted-public-keys".into(),
+      json!("a.cachix.org-1:abc="),
+    );
+    settings.insert("http2".into(), json!(false));
+
+    assert_eq!(format_settings(&settings), [
+      "extra-substituters = https://a.cachix.org https://b.cachix.org",
+      "extra-trusted-public-keys = a.cachix.org-1:abc=",
+      "http2 = false",
+    ]);
+  }
+}
@@ -0,0 +1,142 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde_json::Value;
+use tracing::debug;
+use yansi::Paint;
+
+/// The locked revision of every input in a `flake.lock`, keyed by the name of
+/// its lock node. Inputs without a revision (e.g. `path:` inputs) are
+/// identified by their NAR hash instead.
+pub type LockedRevisions = BTreeMap<String, String>;
+
+/// Read the locked revisions from a `flake.lock`. Returns `None` if the file
+/// does not exist or cannot be parsed.
+#[must_use]
+pub fn read_locked_revisions(lock_file: &Path) -> Option<LockedRevisions> {
+  let contents = fs::read_to_string(lock_file)
+    .map_err(|err| debug!("Failed to read {}: {err}", lock_file.display()))
+    .ok()?;
+  let lock: Value = serde_json::from_str(&contents)
+    .map_err(|err| debug!("Failed to parse {}: {err}", lock_file.display()))
+    .ok()?;
+  Some(locked_revisions(&lock))
+}
+
+fn locked_revisions(lock: &Value) -> LockedRevisions {
+  let root = lock.get("root").and_then(Value::as_str).unwrap_or("root");
+  let Some(nodes) = lock.get("nodes").and_then(Value::as_object) else {
+    return LockedRevisions::new();
+  };
+
+  nodes
+    .iter()
+    .filter(|(name, _)| *name != root)
+    .filter_map(|(name, node)| {
+      let locked = node.get("locked")?;
+      let revision = locked
+        .get("rev")
+        .or_else(|| locked.get("narHash"))?
+        .as_str()?;
+      Some((name.clone(), revision.to_owned()))
+    })
+    .collect()
+}
+
+/// Print the inputs whose locked revision changed between `old` and `new`.
+pub fn print_lock_changes(old: &LockedRevisions, new: &LockedRevisions) {
+  let changes = lock_changes(old, new);
+  if changes.is_empty() {
+    eprintln!("No flake inputs changed");
+    return;
+  }
+
+  eprintln!("{}", Paint::new("Updated flake inputs").bold());
+  for line in changes {
+    eprintln!("  {line}");
+  }
+}
+
+fn lock_changes(old: &LockedRevisions, new: &LockedRevisions) -> Vec<String> {
+  let removed = old
+    .iter()
+    .filter(|(name, _)| !new.contains_key(*name))
+    .map(|(name, rev)| format!("{name}: {} → removed", short(rev)));
+
+  let changed = new.iter().filter_map(|(name, rev)| {
+    match old.get(name) {
+      None => Some(format!("{name}: added → {}", short(rev))),
+      Some(old_rev) if old_rev != rev => {
+        Some(format!("{name}: {} → {}", short(old_rev), short(rev)))
+      },
+      Some(_) => None,
+    }
+  });
+
+  let mut changes: Vec<String> = changed.chain(removed).collect();
+  changes.sort();
+  changes
+}
+
+/// Shorten a revision the way git does, leaving NAR hashes readable.
+fn short(revision: &str) -> &str {
+  let len = if revision.starts_with("sha256-") {
+    15
+  } else {
+    7
+  };
+  revision.get(..len).unwrap_or(revision)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn revisions(entries: &[(&str, &str)]) -> LockedRevisions {
+    entries
+      .iter()
+      .map(|(name, rev)| ((*name).to_owned(), (*rev).to_owned()))
+      .collect()
+  }
+
+  #[test]
+  fn locked_revisions_skips_root_and_falls_back_to_nar_hash() {
+    let lock = json!({
+      "nodes": {
+        "nixpkgs": {
+          "locked": { "rev": "0123456789abcdef", "narHash": "sha256-aaa" },
+        },
+        "local": { "locked": { "type": "path", "narHash": "sha256-bbb" } },
+        "root": { "inputs": { "nixpkgs": "nixpkgs", "local": "local" } },
+      },
+      "root": "root",
+      "version": 7,
+    });
+
+    assert_eq!(
+      locked_revisions(&lock),
+      revisions(&[("local", "sha256-bbb"), ("nixpkgs", "0123456789abcdef")])
+    );
+  }
+
+  #[test]
+  fn lock_changes_lists_changed_added_and_removed_inputs() {
+    let old = revisions(&[
+      ("nixpkgs", "0123456789abcdef"),
+      ("home-manager", "aaaaaaaaaaaa"),
+      ("flake-utils", "cccccccccccc"),
+    ]);
+    let new = revisions(&[
+      ("nixpkgs", "fedcba9876543210"),
+      ("home-manager", "aaaaaaaaaaaa"),
+      ("systems", "dddddddddddd"),
+    ]);
+
+    assert_eq!(lock_changes(&old, &new), [
+      "flake-utils: ccccccc → removed",
+      "nixpkgs: 0123456 → fedcba9",
+      "systems: added → ddddddd",
+    ]);
+  }
+}
@@ -0,0 +1,121 @@
+use chrono::{Local, TimeZone};
+use color_eyre::{Result, eyre::Context};
+use nh_installable::Installable;
+use nix_command::{CommandKind, NixCommand};
+use serde_json::Value;
+use tracing::debug;
+use yansi::Paint;
+
+use crate::util::capture_nix_stdout;
+
+/// Run `nix flake metadata --json` for a flake reference.
+///
+/// # Errors
+///
+/// Returns an error if the flake cannot be fetched or the output is not valid
+/// JSON.
+pub(crate) fn get_flake_metadata(reference: &str) -> Result<Value> {
+  let metadata = capture_nix_stdout(
+    &NixCommand::new(CommandKind::Flake)
+      .args(["metadata", "--json"])
+      .arg(reference),
+  )?;
+
+  serde_json::from_str(&metadata)
+    .wrap_err("Failed to parse flake metadata JSON")
+}
+
+/// Print where a flake installable resolves to before building it: the
+/// resolved and locked URL, the revision and the last-modified date.
+///
+/// Does nothing for non-flake installables.
+///
+/// # Errors
+///
+/// Returns an error if the flake metadata cannot be fetched.
+pub fn show_flake_metadata(installable: &Installable) -> Result<()> {
+  let Installable::Flake { reference, .. } = installable else {
+    debug!(
+      "Not showing flake metadata for a {} installable",
+      installable.str_kind()
+    );
+    return Ok(());
+  };
+
+  let metadata = get_flake_metadata(reference)?;
+
+  eprintln!("{}", Paint::new(format!("Flake {reference}")).bold());
+  for (key, value) in metadata_entries(&metadata) {
+    eprintln!("  {:<15}{value}", format!("{key}:"));
+  }
+
+  Ok(())
+}
+
+/// The fields of `nix flake metadata --json` worth showing, in display order.
+/// Fields the flake does not have (e.g. a revision for a path flake) are
+/// skipped.
+fn metadata_entries(metadata: &Value) -> Vec<(&'static str, String)> {
+  let text = |key: &str| metadata.get(key)?.as_str().map(ToOwned::to_owned);
+
+  let revision = text("revision").or_else(|| text("dirtyRevision"));
+  let last_modified = metadata
+    .get("lastModified")
+    .and_then(Value::as_i64)
+    .and_then(|secs| Local.timestamp_opt(secs, 0).single())
+    .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string());
+
+  [
+    ("Description", text("description")),
+    ("Resolved URL", text("resolvedUrl")),
+    ("Locked URL", text("url")),
+    ("Revision", revision),
+    ("Last modified", last_modified),
+  ]
+  .into_iter()
+  .filter_map(|(key, value)| Some((key, value?)))
+  .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn metadata_entries_lists_known_fields_in_order() {
+    let metadata = json!({
+      "description": "My systems",
+      "resolvedUrl": "github:me/dotfiles",
+      "url": "github:me/dotfiles/0123abcd",
+      "revision": "0123abcd",
+      "lastModified": 0,
+    });
+
+    let keys: Vec<_> = metadata_entries(&metadata)
+      .into_iter()
+      .map(|(k, _)| k)
+      .collect();
+    assert_eq!(keys, [
+      "Description",
+      "Resolved URL",
+      "Locked URL",
+      "Revision",
+      "Last modified"
+    ]);
+  }
+
+  #[test]
+  fn metadata_entries_falls_back_to_dirty_revision() {
+    let metadata = json!({
+      "resolvedUrl": "git+file:///etc/nixos",
+      "dirtyRevision": "0123abcd-dirty",
+    });
+
+    assert_eq!(metadata_entries(&metadata), [
+      ("Resolved URL", "git+file:///etc/nixos".to_string()),
+      ("Revision", "0123abcd-dirty".to_string()),
+    ]);
+  }
+}
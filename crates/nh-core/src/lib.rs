@@ -1,9 +1,20 @@
 pub mod args;
 pub mod checks;
 pub mod command;
+pub mod confirm;
+pub mod doctor;
+pub mod exit;
+pub mod flake_check;
+pub mod flake_config;
+pub mod flake_lock;
+pub mod flake_metadata;
+pub mod outcome;
 pub mod progress;
+pub mod summary;
+pub mod timings;
 pub mod update;
 pub mod util;
+pub mod variant_quirks;
 
 pub const NH_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const NH_REV: Option<&str> = option_env!("NH_REV");
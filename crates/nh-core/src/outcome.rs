@@ -0,0 +1,135 @@
+use std::{
+  cell::RefCell,
+  path::{Path, PathBuf},
+};
+
+/// What a command did, for tools that run nh as a library instead of parsing
+/// its output. The command runners record into it as they go; see [`capture`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outcome {
+  /// The store path of the configuration that was built
+  pub built:     Option<PathBuf>,
+  /// The package diff against the current configuration, if one was shown
+  pub diff:      Option<DiffSummary>,
+  /// Whether the configuration was activated
+  pub activated: bool,
+}
+
+/// Counts from a package diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffSummary {
+  /// Packages that were added, removed or changed version or size
+  pub changed_packages: usize,
+  /// Store paths only in the new closure
+  pub added_paths:      usize,
+  /// Store paths only in the old closure
+  pub removed_paths:    usize,
+}
+
+thread_local! {
+  /// The outcome of the innermost [`capture`] running on this thread, if any.
+  static OUTCOME: RefCell<Option<Outcome>> = const { RefCell::new(None) };
+}
+
+fn record(update: impl FnOnce(&mut Outcome)) {
+  OUTCOME.with_borrow_mut(|outcome| {
+    if let Some(outcome) = outcome {
+      update(outcome);
+    }
+  });
+}
+
+/// Puts the enclosing capture's outcome back when a capture ends, even by
+/// unwinding.
+struct RestoreOuter(Option<Outcome>);
+
+impl Drop for RestoreOuter {
+  fn drop(&mut self) {
+    OUTCOME.set(self.0.take());
+  }
+}
+
+/// Run `f` and return what it recorded along with its result.
+///
+/// Recording is per thread, so commands captured on different threads do not
+/// see each other's outcome, and a nested capture records only into itself.
+/// Nothing is recorded outside of a capture.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Outcome) {
+  let _restore = RestoreOuter(OUTCOME.replace(Some(Outcome::default())));
+  let result = f();
+  (result, OUTCOME.take().unwrap_or_default())
+}
+
+/// Record that `path` was built. Links like `result` are resolved to the
+/// store path they point to.
+pub fn record_built(path: &Path) {
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  record(|outcome| outcome.built = Some(path));
+}
+
+/// Record the package diff that was shown.
+pub fn record_diff(summary: DiffSummary) {
+  record(|outcome| outcome.diff = Some(summary));
+}
+
+/// Record that the configuration was activated.
+pub fn record_activated() {
+  record(|outcome| outcome.activated = true);
+}
+
+#[cfg(test)]
+#[expect(clippy::expect_used, reason = "Fine in tests")]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn capture_returns_recorded_outcome() {
+    let summary = DiffSummary {
+      changed_packages: 2,
+      added_paths:      3,
+      removed_paths:    1,
+    };
+    let ((), outcome) = capture(|| {
+      record_built(Path::new("/nix/store/does-not-exist-system"));
+      record_diff(summary);
+      record_activated();
+    });
+
+    assert_eq!(outcome, Outcome {
+      built:     Some(PathBuf::from("/nix/store/does-not-exist-system")),
+      diff:      Some(summary),
+      activated: true,
+    });
+    assert_eq!(capture(|| ()).1, Outcome::default());
+  }
+
+  #[test]
+  fn nested_capture_does_not_leak_into_outer() {
+    let ((), outer) = capture(|| {
+      record_activated();
+      let ((), inner) = capture(|| {
+        record_diff(DiffSummary {
+          changed_packages: 1,
+          added_paths:      0,
+          removed_paths:    0,
+        });
+      });
+      assert!(!inner.activated);
+      assert!(inner.diff.is_some());
+    });
+
+    assert!(outer.activated);
+    assert_eq!(outer.diff, None);
+  }
+
+  #[test]
+  fn capture_on_another_thread_is_separate() {
+    let ((), outcome) = capture(|| {
+      std::thread::spawn(record_activated)
+        .join()
+        .expect("recording thread panicked");
+    });
+
+    assert!(!outcome.activated);
+  }
+}
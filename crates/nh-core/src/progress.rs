@@ -1,4 +1,8 @@
-use std::time::Duration;
+use std::{
+  io::{self, IsTerminal},
+  sync::atomic::{AtomicBool, Ordering},
+  time::Duration,
+};
 
 use indicatif::{ProgressBar, ProgressStyle};
 
@@ -7,13 +11,28 @@ pub type Spinner = ProgressBar;
 const DEFAULT_SPINNER_TICK: Duration = Duration::from_millis(80);
 const SPINNER_FRAMES: &[&str] = &["⢹", "⢺", "⢼", "⣸", "⣇", "⡧", "⡗", "⡏"];
 
+static SPINNERS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable spinners for the rest of the process, e.g. for `--dry`
+/// runs where nothing long-running is expected to happen.
+pub fn set_spinners_enabled(enabled: bool) {
+  SPINNERS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
 /// Returns a spinner with a blue animation and message.
 ///
+/// The spinner is hidden when stderr is not a terminal or when spinners were
+/// disabled with [`set_spinners_enabled`].
+///
 /// # Panics
 ///
 /// Panics if the hardcoded spinner template is invalid.
 #[must_use]
 pub fn spinner(message: impl Into<String>) -> Spinner {
+  if !SPINNERS_ENABLED.load(Ordering::Relaxed) || !io::stderr().is_terminal() {
+    return ProgressBar::hidden();
+  }
+
   #[expect(clippy::expect_used)]
   let style = ProgressStyle::with_template("{spinner:.blue} {msg}")
     .expect("Static spinner template is valid")
@@ -0,0 +1,130 @@
+use nh_installable::Installable;
+use yansi::Paint;
+
+use crate::{args::DiffType, update::UpdateArgs};
+
+/// One-line description of what a rebuild command is about to do, printed
+/// before anything is built so the result of env var and hostname resolution
+/// is visible without `--verbose`.
+#[derive(Debug)]
+pub struct RebuildSummary<'a> {
+  /// The subcommand being run, e.g. `os switch`
+  pub command:     &'a str,
+  pub installable: &'a Installable,
+  /// The hostname or configuration name the attribute was selected for
+  pub hostname:    Option<&'a str>,
+  pub update:      &'a UpdateArgs,
+  pub build_host:  Option<String>,
+  pub target_host: Option<String>,
+  pub diff:        &'a DiffType,
+}
+
+impl RebuildSummary<'_> {
+  /// Print the summary to stderr. Styling is dropped when `NO_COLOR` is set.
+  pub fn print(&self) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    eprintln!("{}", self.render(!no_color));
+  }
+
+  fn render(&self, styled: bool) -> String {
+    let label = |text: &str| {
+      if styled {
+        text.bold().to_string()
+      } else {
+        text.to_owned()
+      }
+    };
+
+    let update = if self.update.update_all {
+      "all inputs".to_owned()
+    } else if let Some(inputs) = &self.update.update_input {
+      inputs.join(", ")
+    } else {
+      "no".to_owned()
+    };
+    let diff = match self.diff {
+      DiffType::Auto => "auto",
+      DiffType::Always => "always",
+      DiffType::Never => "never",
+    };
+
+    let mut parts = vec![format!(
+      "{} {}",
+      label(self.command),
+      self.installable.to_args().join(" ")
+    )];
+    if let Some(hostname) = self.hostname {
+      parts.push(format!("{} {hostname}", label("host:")));
+    }
+    parts.push(format!("{} {update}", label("update:")));
+    if let Some(build_host) = &self.build_host {
+      parts.push(format!("{} {build_host}", label("build host:")));
+    }
+    if let Some(target_host) = &self.target_host {
+      parts.push(format!("{} {target_host}", label("target host:")));
+    }
+    parts.push(format!("{} {diff}", label("diff:")));
+
+    parts.join(", ")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn installable() -> Installable {
+    Installable::Flake {
+      reference: "/etc/nixos".to_owned(),
+      attribute: vec!["nixosConfigurations".to_owned(), "laptop".to_owned()],
+    }
+  }
+
+  #[test]
+  fn render_lists_resolved_plan() {
+    let installable = installable();
+    let update = UpdateArgs {
+      update_all:   false,
+      update_input: Some(vec!["nixpkgs".to_owned(), "home-manager".to_owned()]),
+    };
+    let summary = RebuildSummary {
+      command:     "os switch",
+      installable: &installable,
+      hostname:    Some("laptop"),
+      update:      &update,
+      build_host:  Some("builder".to_owned()),
+      target_host: None,
+      diff:        &DiffType::Always,
+    };
+
+    assert_eq!(
+      summary.render(false),
+      "os switch /etc/nixos#nixosConfigurations.laptop, host: laptop, update: \
+       nixpkgs, home-manager, build host: builder, diff: always"
+    );
+  }
+
+  #[test]
+  fn render_omits_unset_hosts() {
+    let installable = installable();
+    let update = UpdateArgs {
+      update_all:   true,
+      update_input: None,
+    };
+    let summary = RebuildSummary {
+      command:     "os build",
+      installable: &installable,
+      hostname:    None,
+      update:      &update,
+      build_host:  None,
+      target_host: None,
+      diff:        &DiffType::Auto,
+    };
+
+    assert_eq!(
+      summary.render(false),
+      "os build /etc/nixos#nixosConfigurations.laptop, update: all inputs, \
+       diff: auto"
+    );
+  }
+}
@@ -0,0 +1,133 @@
+use std::{
+  fmt::Write,
+  time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use serde_json::json;
+
+/// Output format for `--timings`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimingsFormat {
+  /// Human-readable summary on stderr
+  Text,
+  /// A JSON object on stdout
+  Json,
+}
+
+/// Records how long each phase of a rebuild takes.
+///
+/// The report is printed when the recorder is dropped, so the phases that ran
+/// are reported even if a later one fails.
+#[derive(Debug)]
+pub struct Timings {
+  format:  Option<TimingsFormat>,
+  started: Instant,
+  phases:  Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+  /// Create a recorder. Nothing is reported when `format` is `None`.
+  #[must_use]
+  pub fn new(format: Option<TimingsFormat>) -> Self {
+    Self {
+      format,
+      started: Instant::now(),
+      phases: Vec::new(),
+    }
+  }
+
+  /// Run `f` and record its duration under `phase`.
+  pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    self.phases.push((phase, start.elapsed()));
+    result
+  }
+
+  fn render_text(&self, total: Duration) -> String {
+    let width = self
+      .phases
+      .iter()
+      .map(|(phase, _)| phase.len())
+      .max()
+      .unwrap_or_default()
+      .max("total".len());
+
+    let mut out = String::from("Timings:\n");
+    for (phase, duration) in &self.phases {
+      let seconds = duration.as_secs_f64();
+      let _ = writeln!(out, "  {phase:<width$}  {seconds:.2}s");
+    }
+    let _ = writeln!(out, "  {:<width$}  {:.2}s", "total", total.as_secs_f64());
+    out
+  }
+
+  fn render_json(&self, total: Duration) -> serde_json::Value {
+    json!({
+      "phases": self
+        .phases
+        .iter()
+        .map(|(phase, duration)| {
+          json!({ "phase": phase, "seconds": duration.as_secs_f64() })
+        })
+        .collect::<Vec<_>>(),
+      "total_seconds": total.as_secs_f64(),
+    })
+  }
+}
+
+impl Drop for Timings {
+  fn drop(&mut self) {
+    let total = self.started.elapsed();
+    match self.format {
+      None => {},
+      Some(TimingsFormat::Text) => eprint!("{}", self.render_text(total)),
+      Some(TimingsFormat::Json) => println!("{}", self.render_json(total)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sample() -> Timings {
+    let mut timings = Timings::new(None);
+    timings.phases = vec![
+      ("build", Duration::from_millis(12_500)),
+      ("activation", Duration::from_millis(1_250)),
+    ];
+    timings
+  }
+
+  #[test]
+  fn time_records_phases_in_order() {
+    let mut timings = Timings::new(None);
+    let value = timings.time("update", || 42);
+    timings.time("build", || ());
+
+    assert_eq!(value, 42);
+    let phases: Vec<_> = timings.phases.iter().map(|(p, _)| *p).collect();
+    assert_eq!(phases, ["update", "build"]);
+  }
+
+  #[test]
+  fn text_report_aligns_phases() {
+    let text = sample().render_text(Duration::from_secs(14));
+    assert_eq!(
+      text,
+      "Timings:\n  build       12.50s\n  activation  1.25s\n  total       \
+       14.00s\n"
+    );
+  }
+
+  #[test]
+  fn json_report_lists_phases() {
+    let value = sample().render_json(Duration::from_secs(14));
+    assert_eq!(value["phases"][0]["phase"], "build");
+    assert_eq!(value["phases"][0]["seconds"], 12.5);
+    assert_eq!(value["phases"][1]["phase"], "activation");
+    assert_eq!(value["total_seconds"], 14.0);
+  }
+}
@@ -1,9 +1,16 @@
-use clap::Args;
+use clap::{Args, ValueEnum};
 use color_eyre::{Result, eyre::bail};
-use nh_installable::Installable;
+use nh_installable::{
+  CommandContext,
+  Installable,
+  InstallableArgs,
+  local_flake_reference_path,
+};
 use nix_command::{CommandKind, NixCommand};
 use tracing::{info, warn};
 
+use crate::flake_lock::{print_lock_changes, read_locked_revisions};
+
 #[derive(Debug, Args)]
 pub struct UpdateArgs {
   #[arg(short = 'u', long = "update", conflicts_with = "update_input")]
@@ -17,6 +24,9 @@ pub struct UpdateArgs {
 
 /// Update flake inputs for an installable.
 ///
+/// For a flake in a local directory, the inputs whose locked revision changed
+/// are printed afterwards.
+///
 /// # Errors
 ///
 /// Returns an error if `nix flake update` fails.
@@ -51,11 +61,155 @@ pub fn update(
 
   info!("{message}");
 
+  let lock_file =
+    local_flake_reference_path(reference).map(|dir| dir.join("flake.lock"));
+  let old_revisions = lock_file
+    .as_deref()
+    .map(|lock_file| read_locked_revisions(lock_file).unwrap_or_default());
+
   let status = cmd.arg("--flake").arg(reference).run_with_logs()?;
 
   if !status.success() {
     bail!("{message} (exit status {status:?})");
   }
 
+  if let (Some(lock_file), Some(old_revisions)) = (lock_file, old_revisions)
+    && let Some(new_revisions) = read_locked_revisions(&lock_file)
+  {
+    print_lock_changes(&old_revisions, &new_revisions);
+  }
+
   Ok(())
 }
+
+/// Which rebuild command's flake `nh update` resolves to.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UpdateTarget {
+  /// The flake used by `nh os`
+  #[default]
+  Os,
+  /// The flake used by `nh home`
+  Home,
+  /// The flake used by `nh darwin`
+  Darwin,
+}
+
+impl From<UpdateTarget> for CommandContext {
+  fn from(target: UpdateTarget) -> Self {
+    match target {
+      UpdateTarget::Os => Self::Os,
+      UpdateTarget::Home => Self::Home,
+      UpdateTarget::Darwin => Self::Darwin,
+    }
+  }
+}
+
+#[derive(Debug, Args)]
+/// Update flake inputs without building or activating anything
+pub struct UpdateCommandArgs {
+  /// Flake input(s) to update. All inputs are updated when none are given
+  pub inputs: Vec<String>,
+
+  /// Flake to update, instead of the one the rebuild commands would use
+  #[arg(long, short = 'F', value_hint = clap::ValueHint::AnyPath)]
+  pub flake: Option<String>,
+
+  /// Resolve the flake the way this command does, e.g. from `NH_HOME_FLAKE`
+  /// for `home`
+  #[arg(long = "for", value_enum, default_value_t)]
+  pub target: UpdateTarget,
+
+  /// Commit the lock file after updates
+  #[arg(long)]
+  pub commit_lock_file: bool,
+}
+
+impl UpdateCommandArgs {
+  /// Update the inputs of the resolved flake.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if no flake can be resolved or `nix flake update`
+  /// fails.
+  pub fn run(self) -> Result<()> {
+    let installable = self
+      .flake
+      .as_deref()
+      .map_or(InstallableArgs::Unspecified { attr: None }, |flake| {
+        let reference = flake.split_once('#').map_or(flake, |(r, _)| r);
+        InstallableArgs::Specified(Installable::Flake {
+          reference: reference.to_owned(),
+          attribute: Vec::new(),
+        })
+      })
+      .resolve_or_default(self.target.into())?;
+
+    if !matches!(installable, Installable::Flake { .. }) {
+      bail!(
+        "Only flake installables can be updated, {} is not supported",
+        installable.str_kind()
+      );
+    }
+
+    let inputs = (!self.inputs.is_empty()).then_some(self.inputs);
+    update(&installable, inputs, self.commit_lock_file)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use clap::Parser;
+
+  use super::*;
+
+  #[derive(Parser)]
+  struct UpdateCli {
+    #[command(flatten)]
+    args: UpdateCommandArgs,
+  }
+
+  #[derive(Parser)]
+  struct RebuildCli {
+    #[command(flatten)]
+    args: UpdateArgs,
+  }
+
+  #[test]
+  fn update_command_parses_inputs_and_target() {
+    let args = UpdateCli::try_parse_from([
+      "update",
+      "nixpkgs",
+      "home-manager",
+      "--for",
+      "home",
+      "--commit-lock-file",
+    ])
+    .map(|cli| cli.args);
+    assert!(
+      args.as_ref().is_ok_and(|args| {
+        args.inputs == ["nixpkgs", "home-manager"]
+          && args.target == UpdateTarget::Home
+          && args.commit_lock_file
+          && args.flake.is_none()
+      }),
+      "{args:?}"
+    );
+
+    let args = UpdateCli::try_parse_from(["update"]).map(|cli| cli.args);
+    assert!(
+      args.as_ref().is_ok_and(|args| {
+        args.inputs.is_empty() && args.target == UpdateTarget::Os
+      }),
+      "{args:?}"
+    );
+
+    assert!(UpdateCli::try_parse_from(["update", "--for", "nixos"]).is_err());
+  }
+
+  #[test]
+  fn update_all_conflicts_with_update_input() {
+    assert!(RebuildCli::try_parse_from(["os", "-u"]).is_ok());
+    assert!(RebuildCli::try_parse_from(["os", "-U", "nixpkgs"]).is_ok());
+    assert!(RebuildCli::try_parse_from(["os", "-u", "-U", "nixpkgs"]).is_err());
+  }
+}
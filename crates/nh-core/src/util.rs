@@ -2,6 +2,7 @@ use std::{
   collections::HashSet,
   ffi::OsString,
   os::unix::process::CommandExt,
+  path::{Path, PathBuf},
   process::{Command as StdCommand, Stdio},
   sync::{LazyLock, OnceLock},
 };
@@ -14,7 +15,14 @@ use nix_command::{CommandKind, NixCommand};
 use regex::Regex;
 use tracing::{debug, warn};
 
-use crate::command::{Command, ElevationStrategy};
+use crate::command::{
+  Command,
+  ElevationStrategy,
+  EvalError,
+  ExitError,
+  is_eval_exit_code,
+  log_std_command,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum NixVariant {
@@ -23,6 +31,18 @@ pub enum NixVariant {
   Determinate,
 }
 
+impl NixVariant {
+  /// Human-readable name of the Nix implementation.
+  #[must_use]
+  pub const fn display_name(&self) -> &'static str {
+    match self {
+      Self::Nix => "Nix",
+      Self::Lix => "Lix",
+      Self::Determinate => "Determinate Nix",
+    }
+  }
+}
+
 static NIX_VERSION_OUTPUT: OnceLock<Option<String>> = OnceLock::new();
 static NIX_VARIANT: OnceLock<NixVariant> = OnceLock::new();
 static NIX_EXPERIMENTAL_FEATURES: OnceLock<HashSet<String>> = OnceLock::new();
@@ -39,9 +59,10 @@ fn format_argv(argv: &[OsString]) -> String {
     .join(" ")
 }
 
-fn capture_nix_stdout(command: &NixCommand) -> Result<String> {
+pub(crate) fn capture_nix_stdout(command: &NixCommand) -> Result<String> {
   let argv = command.argv();
   let command_text = format_argv(&argv);
+  debug!(command = %command_text);
   let output = command
     .output()
     .wrap_err_with(|| format!("Failed to run {command_text}"))?;
@@ -241,22 +262,23 @@ pub fn ensure_ssh_key_login() -> Result<()> {
   }
 
   // ssh-add -L checks if there are any currently usable ssh keys
-  if StdCommand::new("ssh-add")
+  let mut list_keys = StdCommand::new("ssh-add");
+  list_keys
     .arg("-L")
     .stdout(Stdio::null())
-    .stderr(Stdio::null())
-    .status()?
-    .success()
-  {
+    .stderr(Stdio::null());
+  log_std_command(&list_keys);
+  if list_keys.status()?.success() {
     return Ok(());
   }
 
-  StdCommand::new("ssh-add")
+  let mut add_keys = StdCommand::new("ssh-add");
+  add_keys
     .stdin(Stdio::inherit())
     .stdout(Stdio::inherit())
-    .stderr(Stdio::inherit())
-    .spawn()?
-    .wait()?;
+    .stderr(Stdio::inherit());
+  log_std_command(&add_keys);
+  add_keys.spawn()?.wait()?;
 
   Ok(())
 }
@@ -479,6 +501,7 @@ in
 /// # Arguments
 ///
 /// * `installable` - The flake installable to evaluate
+/// * `impure` - Whether to evaluate with `--impure`
 ///
 /// # Returns
 ///
@@ -492,9 +515,11 @@ in
 /// - The flake installable does not have images attribute
 pub fn get_build_image_variants_flake(
   installable: &nh_installable::Installable,
+  impure: bool,
 ) -> Result<Vec<String>> {
   let result = capture_nix_stdout(
     &NixCommand::new(CommandKind::Eval)
+      .impure(impure)
       .arg("--json")
       .args(installable.to_args())
       .arg("--apply")
@@ -507,6 +532,359 @@ pub fn get_build_image_variants_flake(
   Ok(variants)
 }
 
+/// The names of the specialisations in a `specialisation` directory of a built
+/// configuration, sorted. Empty if the directory does not exist.
+#[must_use]
+pub fn list_specialisations(dir: &Path) -> Vec<String> {
+  let mut names: Vec<String> = std::fs::read_dir(dir)
+    .map(|entries| {
+      entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect()
+    })
+    .unwrap_or_default();
+  names.sort_unstable();
+  names
+}
+
+/// Comma-separated names of the specialisations in a built configuration's
+/// `specialisation` directory, or "none", for error messages.
+#[must_use]
+pub fn available_specialisations(dir: &Path) -> String {
+  let names = list_specialisations(dir);
+  if names.is_empty() {
+    "none".to_string()
+  } else {
+    names.join(", ")
+  }
+}
+
+/// Whether the environment variable `name` is set to a truthy value: "1",
+/// "true" or "yes" (case-insensitive). Unset, empty and any other value count
+/// as off.
+#[must_use]
+pub fn env_flag_enabled(name: &str) -> bool {
+  std::env::var(name).is_ok_and(|val| is_truthy(&val))
+}
+
+fn is_truthy(value: &str) -> bool {
+  let value = value.trim().to_lowercase();
+  value == "1" || value == "true" || value == "yes"
+}
+
+/// `$XDG_STATE_HOME/nh`, falling back to `~/.local/state/nh`. `None` when
+/// neither variable is set.
+#[must_use]
+pub fn state_dir() -> Option<PathBuf> {
+  state_dir_from(std::env::var_os("XDG_STATE_HOME"), std::env::var_os("HOME"))
+}
+
+fn state_dir_from(
+  xdg_state_home: Option<OsString>,
+  home: Option<OsString>,
+) -> Option<PathBuf> {
+  xdg_dir_from(xdg_state_home, home, ".local/state").map(|dir| dir.join("nh"))
+}
+
+/// `$XDG_CACHE_HOME/nh`, falling back to `~/.cache/nh`. `None` when neither
+/// variable is set.
+#[must_use]
+pub fn cache_dir() -> Option<PathBuf> {
+  cache_dir_from(std::env::var_os("XDG_CACHE_HOME"), std::env::var_os("HOME"))
+}
+
+fn cache_dir_from(
+  xdg_cache_home: Option<OsString>,
+  home: Option<OsString>,
+) -> Option<PathBuf> {
+  xdg_dir_from(xdg_cache_home, home, ".cache").map(|dir| dir.join("nh"))
+}
+
+/// An XDG base directory: `xdg_home` if it is set and not empty, otherwise
+/// `fallback` inside `home`. `None` when neither is set.
+#[must_use]
+pub fn xdg_dir_from(
+  xdg_home: Option<OsString>,
+  home: Option<OsString>,
+  fallback: &str,
+) -> Option<PathBuf> {
+  let non_empty = |val: Option<OsString>| val.filter(|val| !val.is_empty());
+
+  non_empty(xdg_home)
+    .map(PathBuf::from)
+    .or_else(|| non_empty(home).map(|home| Path::new(&home).join(fallback)))
+}
+
+/// Create the temporary directory holding the out-link of a build. It is
+/// created in `TMPDIR`, or `/tmp` if that is unset.
+///
+/// # Errors
+///
+/// Returns an error naming the temporary directory if it cannot be created,
+/// with a hint to point `TMPDIR` elsewhere if it is full.
+pub fn result_tempdir(prefix: &str) -> Result<tempfile::TempDir> {
+  let tmp = std::env::temp_dir();
+  tempfile::Builder::new()
+    .prefix(prefix)
+    .tempdir_in(&tmp)
+    .map_err(|err| tempdir_error(err, &tmp))
+}
+
+fn tempdir_error(err: std::io::Error, tmp: &Path) -> eyre::Report {
+  let context = if err.kind() == std::io::ErrorKind::StorageFull {
+    format!(
+      "No space left in the temporary directory {}, set TMPDIR to a location \
+       with more free space",
+      tmp.display()
+    )
+  } else {
+    format!(
+      "Failed to create a temporary directory in {}, set TMPDIR to change \
+       where it is created",
+      tmp.display()
+    )
+  };
+  eyre::Report::new(err).wrap_err(context)
+}
+
+/// Wrap the error of a failed build of a system configuration, naming the
+/// attribute that was built. Evaluation failures, which include a missing
+/// attribute, also get a hint on how to select a different configuration.
+pub fn build_failure_context(
+  err: eyre::Report,
+  toplevel: &nh_installable::Installable,
+  hostname: &str,
+) -> eyre::Report {
+  let message = format!("Failed to build {}", toplevel.to_args().join(" "));
+  if !is_eval_failure(&err) {
+    return err.wrap_err(message);
+  }
+  err.wrap_err(format!(
+    "{message}\nIf the attribute does not exist, check that a configuration \
+     named '{hostname}' is defined, or select another one with --hostname or \
+     '<flake>#<name>'"
+  ))
+}
+
+/// Whether `err` comes from Nix failing to evaluate, rather than from a
+/// derivation failing to build or a check after the build.
+fn is_eval_failure(err: &eyre::Report) -> bool {
+  err.chain().any(|err| {
+    err.is::<EvalError>()
+      || is_eval_exit_code(
+        err.downcast_ref::<ExitError>().and_then(ExitError::code),
+      )
+  })
+}
+
+/// Check that a build produced the store path given with `--expect-path`.
+///
+/// `built` may be the out link of the build, which is resolved to its store
+/// path first. Results that only exist on a remote host are compared as is.
+///
+/// # Errors
+///
+/// Returns an error if the built store path differs from `expected`.
+pub fn check_expected_path(expected: &Path, built: &Path) -> Result<()> {
+  let built = std::fs::canonicalize(built).unwrap_or_else(|_| built.into());
+  if built != expected {
+    bail!(
+      "Built {} but expected {}. The inputs or the build are not reproducible",
+      built.display(),
+      expected.display()
+    );
+  }
+
+  debug!(
+    "Built store path matches the expected {}",
+    expected.display()
+  );
+  Ok(())
+}
+
+/// Evaluates the names of the attributes of an attribute set installable.
+///
+/// # Errors
+///
+/// Returns an error if the nix eval command fails, or the installable is not
+/// an attribute set.
+pub fn get_attr_names(
+  installable: &nh_installable::Installable,
+  impure: bool,
+) -> Result<Vec<String>> {
+  let result = capture_nix_stdout(
+    &NixCommand::new(CommandKind::Eval)
+      .impure(impure)
+      .arg("--json")
+      .args(installable.to_args())
+      .arg("--apply")
+      .arg("builtins.attrNames"),
+  )?;
+
+  serde_json::from_str(&result).wrap_err("Failed to parse attribute names")
+}
+
+/// Gets the total closure size of a store path, in bytes.
+///
+/// # Errors
+///
+/// Returns an error if `nix path-info` fails or its output does not contain a
+/// closure size.
+pub fn get_closure_size(path: &Path) -> Result<u64> {
+  let output = capture_nix_stdout(
+    &NixCommand::new(CommandKind::PathInfo)
+      .args(["--json", "--closure-size"])
+      .arg(path),
+  )?;
+  let json: serde_json::Value = serde_json::from_str(&output)
+    .wrap_err("Failed to parse nix path-info JSON")?;
+
+  closure_size_from_path_info(&json).ok_or_else(|| {
+    eyre!("nix path-info did not report a closure size for {path:?}")
+  })
+}
+
+/// Extract the closure size of the single path queried with `nix path-info
+/// --json --closure-size`. Older Nix versions print a list of entries, newer
+/// ones an object keyed by store path.
+fn closure_size_from_path_info(json: &serde_json::Value) -> Option<u64> {
+  let entry = match json {
+    serde_json::Value::Array(entries) => entries.first()?,
+    serde_json::Value::Object(entries) => entries.values().next()?,
+    _ => return None,
+  };
+  entry.get("closureSize")?.as_u64()
+}
+
+/// Gets the combined size of the closures of several store paths, in bytes.
+///
+/// Paths shared between the closures are only counted once, so this is the
+/// most that deleting all of `paths` could free.
+///
+/// # Errors
+///
+/// Returns an error if `nix path-info` fails or its output cannot be parsed.
+pub fn get_closure_union_size(paths: &[&Path]) -> Result<u64> {
+  if paths.is_empty() {
+    return Ok(0);
+  }
+
+  let output = capture_nix_stdout(
+    &NixCommand::new(CommandKind::PathInfo)
+      .args(["--json", "--recursive"])
+      .args(paths),
+  )?;
+  let json: serde_json::Value = serde_json::from_str(&output)
+    .wrap_err("Failed to parse nix path-info JSON")?;
+
+  Ok(total_nar_size(&json))
+}
+
+/// Gets the combined size of the closures of `paths`, leaving out the store
+/// paths that are also in the closure of one of `kept`, in bytes.
+///
+/// This estimates the space deleting `paths` frees while `kept` stay alive.
+/// Roots not in `kept` can still keep more paths alive.
+///
+/// # Errors
+///
+/// Returns an error if `nix path-info` fails or its output cannot be parsed.
+pub fn get_closure_size_excluding(
+  paths: &[&Path],
+  kept: &[&Path],
+) -> Result<u64> {
+  if paths.is_empty() {
+    return Ok(0);
+  }
+
+  let output = capture_nix_stdout(
+    &NixCommand::new(CommandKind::PathInfo)
+      .args(["--json", "--recursive"])
+      .args(paths),
+  )?;
+  let json: serde_json::Value = serde_json::from_str(&output)
+    .wrap_err("Failed to parse nix path-info JSON")?;
+  let kept: HashSet<PathBuf> = get_closure_paths(kept)?.into_iter().collect();
+
+  Ok(nar_size_excluding(&json, &kept))
+}
+
+/// Get every store path in the closures of `paths`.
+///
+/// # Errors
+///
+/// Returns an error if `nix path-info` fails.
+pub fn get_closure_paths(paths: &[&Path]) -> Result<Vec<PathBuf>> {
+  if paths.is_empty() {
+    return Ok(Vec::new());
+  }
+
+  let output = capture_nix_stdout(
+    &NixCommand::new(CommandKind::PathInfo)
+      .arg("--recursive")
+      .args(paths),
+  )?;
+
+  Ok(output.lines().map(PathBuf::from).collect())
+}
+
+/// Sum the `narSize` of every entry printed by `nix path-info --json`.
+fn total_nar_size(json: &serde_json::Value) -> u64 {
+  nar_size_excluding(json, &HashSet::new())
+}
+
+/// Sum the `narSize` of the entries printed by `nix path-info --json`,
+/// skipping the store paths in `excluded`.
+fn nar_size_excluding(
+  json: &serde_json::Value,
+  excluded: &HashSet<PathBuf>,
+) -> u64 {
+  // Older Nix versions print a list of entries with a `path` field, newer
+  // ones an object keyed by the store path
+  let entries: Vec<(Option<&str>, &serde_json::Value)> = match json {
+    serde_json::Value::Array(entries) => {
+      entries
+        .iter()
+        .map(|entry| (entry.get("path").and_then(|p| p.as_str()), entry))
+        .collect()
+    },
+    serde_json::Value::Object(entries) => {
+      entries
+        .iter()
+        .map(|(path, entry)| (Some(path.as_str()), entry))
+        .collect()
+    },
+    _ => Vec::new(),
+  };
+  entries
+    .into_iter()
+    .filter(|(path, _)| {
+      !path.is_some_and(|path| excluded.contains(Path::new(path)))
+    })
+    .filter_map(|(_, entry)| entry.get("narSize")?.as_u64())
+    .sum()
+}
+
+/// Format a size in bytes using binary units, e.g. `1.5 GiB`.
+#[must_use]
+#[expect(clippy::cast_precision_loss, reason = "Only used for display")]
+pub fn format_size(bytes: u64) -> String {
+  const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+  let mut size = bytes as f64;
+  let mut unit = 0;
+  while size >= 1024.0 && unit < UNITS.len() - 1 {
+    size /= 1024.0;
+    unit += 1;
+  }
+
+  if unit == 0 {
+    format!("{bytes} B")
+  } else {
+    format!("{size:.1} {}", UNITS[unit])
+  }
+}
+
 #[cfg(test)]
 #[expect(clippy::expect_used, clippy::unwrap_used, reason = "Fine in tests")]
 mod tests {
@@ -514,6 +892,193 @@ mod tests {
 
   use super::*;
 
+  #[test]
+  fn truthy_env_values() {
+    assert!(is_truthy("1"));
+    assert!(is_truthy(" Yes "));
+    assert!(!is_truthy("0"));
+    assert!(!is_truthy(""));
+  }
+
+  #[test]
+  fn full_tempdir_suggests_tmpdir() {
+    let err = tempdir_error(
+      std::io::Error::from(std::io::ErrorKind::StorageFull),
+      Path::new("/tmp"),
+    );
+    assert_eq!(
+      err.to_string(),
+      "No space left in the temporary directory /tmp, set TMPDIR to a \
+       location with more free space"
+    );
+  }
+
+  #[test]
+  fn test_closure_size_from_path_info() {
+    let list = serde_json::json!([
+      { "path": "/nix/store/abc-home-manager-generation", "closureSize": 42 }
+    ]);
+    let object = serde_json::json!({
+      "/nix/store/abc-home-manager-generation": { "closureSize": 42 }
+    });
+
+    assert_eq!(closure_size_from_path_info(&list), Some(42));
+    assert_eq!(closure_size_from_path_info(&object), Some(42));
+    assert_eq!(closure_size_from_path_info(&serde_json::json!([])), None);
+  }
+
+  #[test]
+  fn test_total_nar_size() {
+    let list = serde_json::json!([
+      { "path": "/nix/store/a", "narSize": 10 },
+      { "path": "/nix/store/b", "narSize": 32 }
+    ]);
+    let object = serde_json::json!({
+      "/nix/store/a": { "narSize": 10 },
+      "/nix/store/b": { "narSize": 32 },
+      "/nix/store/c": null
+    });
+
+    assert_eq!(total_nar_size(&list), 42);
+    assert_eq!(total_nar_size(&object), 42);
+  }
+
+  #[test]
+  fn test_nar_size_excluding_kept_paths() {
+    let list = serde_json::json!([
+      { "path": "/nix/store/a", "narSize": 10 },
+      { "path": "/nix/store/b", "narSize": 32 }
+    ]);
+    let object = serde_json::json!({
+      "/nix/store/a": { "narSize": 10 },
+      "/nix/store/b": { "narSize": 32 }
+    });
+    let kept = HashSet::from([PathBuf::from("/nix/store/b")]);
+
+    assert_eq!(nar_size_excluding(&list, &kept), 10);
+    assert_eq!(nar_size_excluding(&object, &kept), 10);
+  }
+
+  #[test]
+  fn test_list_specialisations() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(
+      list_specialisations(&dir.path().join("specialisation")).is_empty()
+    );
+    assert_eq!(
+      available_specialisations(&dir.path().join("specialisation")),
+      "none"
+    );
+
+    for name in ["work", "gaming"] {
+      std::fs::create_dir_all(dir.path().join("specialisation").join(name))
+        .unwrap();
+    }
+    assert_eq!(list_specialisations(&dir.path().join("specialisation")), [
+      "gaming", "work"
+    ]);
+    assert_eq!(
+      available_specialisations(&dir.path().join("specialisation")),
+      "gaming, work"
+    );
+  }
+
+  #[test]
+  fn test_state_dir_from() {
+    assert_eq!(
+      state_dir_from(Some("/state".into()), Some("/home/user".into())),
+      Some(PathBuf::from("/state/nh"))
+    );
+    assert_eq!(
+      state_dir_from(Some("".into()), Some("/home/user".into())),
+      Some(PathBuf::from("/home/user/.local/state/nh"))
+    );
+    assert_eq!(state_dir_from(None, None), None);
+  }
+
+  #[test]
+  fn test_cache_dir_from() {
+    assert_eq!(
+      cache_dir_from(Some("/xdg".into()), Some("/home/user".into())),
+      Some(PathBuf::from("/xdg/nh"))
+    );
+    assert_eq!(
+      cache_dir_from(Some("".into()), Some("/home/user".into())),
+      Some(PathBuf::from("/home/user/.cache/nh"))
+    );
+    assert_eq!(cache_dir_from(None, None), None);
+    assert_eq!(cache_dir_from(None, Some("".into())), None);
+  }
+
+  #[test]
+  fn test_build_failure_context_names_attribute() {
+    let toplevel = Installable::Flake {
+      reference: "/etc/nixos".to_owned(),
+      attribute: ["nixosConfigurations", "laptop", "config", "system"]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+    };
+    let exit = |code: u32| {
+      let status = subprocess::Exec::cmd("sh")
+        .args(["-c", &format!("exit {code}")])
+        .join()
+        .expect("failed to run sh");
+      ExitError(status)
+    };
+
+    let eval_failure =
+      build_failure_context(exit(1).into(), &toplevel, "laptop").to_string();
+    assert!(
+      eval_failure.starts_with(
+        "Failed to build /etc/nixos#nixosConfigurations.laptop.config.system\n"
+      ),
+      "{eval_failure}"
+    );
+    assert!(eval_failure.contains("named 'laptop'"), "{eval_failure}");
+
+    let remote_eval_failure = build_failure_context(
+      EvalError("attribute 'laptop' missing".to_owned()).into(),
+      &toplevel,
+      "laptop",
+    )
+    .to_string();
+    assert!(
+      remote_eval_failure.contains("named 'laptop'"),
+      "{remote_eval_failure}"
+    );
+
+    let build_failure =
+      build_failure_context(exit(100).into(), &toplevel, "laptop").to_string();
+    assert_eq!(
+      build_failure,
+      "Failed to build /etc/nixos#nixosConfigurations.laptop.config.system"
+    );
+  }
+
+  #[test]
+  fn test_check_expected_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let store_path = dir.path().join("abc-nixos-system");
+    let out_link = dir.path().join("result");
+    std::fs::create_dir(&store_path).unwrap();
+    std::os::unix::fs::symlink(&store_path, &out_link).unwrap();
+    let store_path = store_path.canonicalize().unwrap();
+
+    assert!(check_expected_path(&store_path, &out_link).is_ok());
+    assert!(
+      check_expected_path(Path::new("/nix/store/def-nixos-system"), &out_link)
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn test_format_size() {
+    assert_eq!(format_size(512), "512 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+  }
+
   #[test]
   fn test_get_build_image_variants_expression() {
     let installable = Installable::Expression {
@@ -618,7 +1183,7 @@ mod tests {
       ],
     };
 
-    let result = get_build_image_variants_flake(&installable);
+    let result = get_build_image_variants_flake(&installable, false);
 
     assert!(result.is_ok());
 
@@ -0,0 +1,86 @@
+use semver::Version;
+
+use crate::util::{self, NixVariant, normalize_version_string};
+
+/// Known differences in behavior between Nix variants.
+///
+/// Code that has to work around a variant should query these instead of
+/// matching on [`NixVariant`] directly, so every workaround we rely on is
+/// listed in one place. Only add quirks we are fully aware of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VariantQuirks {
+  /// `nix-command` and `flakes` are experimental features that have to be
+  /// enabled explicitly. Determinate Nix has stabilized both.
+  pub experimental_flakes: bool,
+  /// Flake repls need the `repl-flake` experimental feature on versions older
+  /// than this one.
+  pub repl_flake_before:   Option<&'static str>,
+}
+
+impl VariantQuirks {
+  /// Whether a flake repl needs the `repl-flake` experimental feature on the
+  /// given raw `nix --version` string. Versions that cannot be parsed are
+  /// assumed not to need it.
+  #[must_use]
+  pub fn needs_repl_flake(&self, version: &str) -> bool {
+    let Some(threshold) = self.repl_flake_before else {
+      return false;
+    };
+
+    Version::parse(&normalize_version_string(version))
+      .ok()
+      .zip(Version::parse(threshold).ok())
+      .is_some_and(|(current, threshold)| current < threshold)
+  }
+}
+
+/// The quirks of `variant`.
+#[must_use]
+pub const fn quirks_for(variant: &NixVariant) -> VariantQuirks {
+  match variant {
+    NixVariant::Nix => {
+      VariantQuirks {
+        experimental_flakes: true,
+        repl_flake_before:   None,
+      }
+    },
+    NixVariant::Lix => {
+      VariantQuirks {
+        experimental_flakes: true,
+        repl_flake_before:   Some("2.93.0"),
+      }
+    },
+    NixVariant::Determinate => {
+      VariantQuirks {
+        experimental_flakes: false,
+        repl_flake_before:   None,
+      }
+    },
+  }
+}
+
+/// The quirks of the installed Nix variant.
+#[must_use]
+pub fn quirks() -> VariantQuirks {
+  quirks_for(util::get_nix_variant())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn only_determinate_has_stable_flakes() {
+    assert!(quirks_for(&NixVariant::Nix).experimental_flakes);
+    assert!(quirks_for(&NixVariant::Lix).experimental_flakes);
+    assert!(!quirks_for(&NixVariant::Determinate).experimental_flakes);
+  }
+
+  #[test]
+  fn repl_flake_is_needed_on_old_lix_only() {
+    let lix = quirks_for(&NixVariant::Lix);
+    assert!(lix.needs_repl_flake("nix (Lix, like Nix) 2.91.1"));
+    assert!(!lix.needs_repl_flake("nix (Lix, like Nix) 2.93.3"));
+    assert!(!quirks_for(&NixVariant::Nix).needs_repl_flake("nix (Nix) 2.18.1"));
+  }
+}
@@ -7,8 +7,10 @@ use nh_core::{
     FlakeFeatures,
     LegacyFeatures,
   },
+  flake_check::FlakeCheckArgs,
   update::UpdateArgs,
 };
+use nh_diff::DiffGenerationsArgs;
 use nh_installable::{CommandContext, InstallableArgs};
 use nh_remote::RemoteHost;
 
@@ -36,6 +38,14 @@ impl DarwinArgs {
           Box::new(LegacyFeatures)
         }
       },
+      DarwinSubcommand::Check(args) => {
+        if args.uses_flakes(CommandContext::Darwin) {
+          Box::new(FlakeFeatures)
+        } else {
+          Box::new(LegacyFeatures)
+        }
+      },
+      DarwinSubcommand::DiffGenerations(_) => Box::new(LegacyFeatures),
     }
   }
 }
@@ -48,6 +58,10 @@ pub enum DarwinSubcommand {
   Build(DarwinRebuildArgs),
   /// Load a nix-darwin configuration in a Nix REPL
   Repl(DarwinReplArgs),
+  /// Run the checks of the configuration's flake with `nix flake check`
+  Check(FlakeCheckArgs),
+  /// Show what changed between two generations
+  DiffGenerations(DiffGenerationsArgs),
 }
 
 #[derive(Debug, Args)]
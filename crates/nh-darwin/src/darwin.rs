@@ -1,6 +1,6 @@
 pub mod args;
 
-use std::{convert::Into, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 use args::{DarwinArgs, DarwinRebuildArgs, DarwinReplArgs, DarwinSubcommand};
 use color_eyre::{
@@ -8,12 +8,24 @@ use color_eyre::{
   eyre::{Context, bail},
 };
 use nh_core::{
-  args::DiffType,
+  args::split_nom_args,
   command::{Command, CommandKind, ElevationStrategy, NixCommand},
+  confirm::confirm,
+  exit::{self, Aborted, Phase},
+  flake_metadata::show_flake_metadata,
+  outcome,
+  progress,
+  summary::RebuildSummary,
+  timings::Timings,
   update::update,
-  util::get_hostname,
+  util::{
+    build_failure_context,
+    check_expected_path,
+    get_hostname,
+    result_tempdir,
+  },
 };
-use nh_diff::print_dix_diff;
+use nh_diff::{print_dix_diff, wants_diff};
 use nh_installable::{CommandContext, Installable};
 use nh_remote::{self, RemoteBuildConfig};
 use tracing::{debug, info, warn};
@@ -52,6 +64,10 @@ impl DarwinArgs {
         args.rebuild(&Build, elevation)
       },
       DarwinSubcommand::Repl(args) => args.run(),
+      DarwinSubcommand::Check(args) => args.run(CommandContext::Darwin),
+      DarwinSubcommand::DiffGenerations(args) => {
+        args.run(Path::new(SYSTEM_PROFILE))
+      },
     }
   }
 }
@@ -61,47 +77,90 @@ enum DarwinRebuildVariant {
   Build,
 }
 
+impl DarwinRebuildVariant {
+  /// The `nh` subcommand this variant corresponds to.
+  const fn command(&self) -> &'static str {
+    match self {
+      Self::Build => "darwin build",
+      Self::Switch => "darwin switch",
+    }
+  }
+}
+
 impl DarwinRebuildArgs {
   fn rebuild(
-    self,
+    mut self,
     variant: &DarwinRebuildVariant,
     elevation: ElevationStrategy,
   ) -> Result<()> {
     use DarwinRebuildVariant::{Build, Switch};
 
+    exit::enter(Phase::Precheck);
     if nix::unistd::Uid::effective().is_root() && !self.bypass_root_check {
       bail!(
         "Don't run nh darwin as root. I will call sudo internally as needed"
       );
     }
 
+    self.common.passthrough.validate()?;
+    self
+      .common
+      .passthrough
+      .warn_no_local_build(self.build_host.is_some());
+    let nom_args = split_nom_args(self.common.nom_args.as_deref())?;
+    exit::enter(Phase::Build);
+
+    if self.common.dry {
+      progress::set_spinners_enabled(false);
+    }
+
     let hostname = get_hostname(self.hostname)?;
 
     let (out_path, _tempdir_guard): (PathBuf, Option<tempfile::TempDir>) =
       if let Some(ref p) = self.common.out_link {
         (p.clone(), None)
+      } else if self.common.keep_result {
+        (PathBuf::from("result"), None)
       } else {
-        let dir = tempfile::Builder::new().prefix("nh-darwin").tempdir()?;
+        let dir = result_tempdir("nh-darwin")?;
         (dir.as_ref().join("result"), Some(dir))
       };
 
     debug!("Output path: {out_path:?}");
 
+    let mut timings = Timings::new(self.common.timings);
+
     let installable = self
       .common
       .installable
       .clone()
       .resolve_or_default(CommandContext::Darwin)?;
+    self.common.passthrough.confirm_flake_config(&installable)?;
+    if self.common.show_metadata {
+      show_flake_metadata(&installable)?;
+    }
+    RebuildSummary {
+      command:     variant.command(),
+      installable: &installable,
+      hostname:    Some(&hostname),
+      update:      &self.update_args,
+      build_host:  self.build_host.as_ref().map(ToString::to_string),
+      target_host: None,
+      diff:        &self.common.diff,
+    }
+    .print();
 
     if self.update_args.update_all || self.update_args.update_input.is_some() {
-      update(
-        &installable,
-        self.update_args.update_input,
-        self.common.passthrough.commit_lock_file,
-      )?;
+      timings.time("update", || {
+        update(
+          &installable,
+          self.update_args.update_input,
+          self.common.passthrough.commit_lock_file,
+        )
+      })?;
     }
 
-    let toplevel = toplevel_for(hostname, installable, "toplevel")?;
+    let toplevel = toplevel_for(&hostname, installable, "toplevel")?;
 
     // If a build host is specified, use remote build semantics
     if let Some(build_host) = self.build_host.clone() {
@@ -111,39 +170,41 @@ impl DarwinRebuildArgs {
         build_host,
         target_host: None,
         use_nom: !self.common.no_nom,
+        nom_args,
         use_substitutes: self.common.passthrough.use_substitutes,
-        extra_args: self
-          .extra_args
-          .iter()
-          .map(Into::into)
-          .chain(
-            self
-              .common
-              .passthrough
-              .generate_passthrough_args()
-              .into_iter()
-              .map(Into::into),
-          )
-          .collect(),
+        extra_args: self.common.passthrough.remote_build_args(&self.extra_args),
       };
 
       // Initialize SSH control - guard will cleanup connections on drop
       let _ssh_guard = nh_remote::init_ssh_control();
 
-      nh_remote::build_remote(&toplevel, &config, Some(&out_path))
-        .wrap_err("Failed to build Darwin configuration")?;
+      timings.time("build", || {
+        nh_remote::build_remote(&toplevel, &config, Some(&out_path))
+          .wrap_err("Failed to build Darwin configuration")
+          .map_err(|err| build_failure_context(err, &toplevel, &hostname))
+      })?;
     } else {
-      nh_core::command::Build::new(toplevel)
-        .extra_arg("--out-link")
-        .extra_arg(&out_path)
-        .extra_args(&self.extra_args)
-        .passthrough(&self.common.passthrough)
-        .message("Building Darwin configuration")
-        .nom(!self.common.no_nom)
-        .run()
-        .wrap_err("Failed to build Darwin configuration")?;
+      let attr = toplevel.clone();
+      timings.time("build", || {
+        nh_core::command::Build::new(toplevel)
+          .extra_arg("--out-link")
+          .extra_arg(&out_path)
+          .extra_args(&self.extra_args)
+          .passthrough(&self.common.passthrough)
+          .message("Building Darwin configuration")
+          .nom(!self.common.no_nom)
+          .nom_args(nom_args)
+          .run()
+          .wrap_err("Failed to build Darwin configuration")
+          .map_err(|err| build_failure_context(err, &attr, &hostname))
+      })?;
     }
 
+    if let Some(expected) = &self.common.expect_path {
+      check_expected_path(expected, &out_path)?;
+    }
+    outcome::record_built(&out_path);
+
     let target_profile = out_path.clone();
 
     target_profile.try_exists().context("Doesn't exist")?;
@@ -154,57 +215,63 @@ impl DarwinRebuildArgs {
     );
 
     // Compare changes between current and target generation
-    if matches!(self.common.diff, DiffType::Never) {
-      debug!("Not running dix as the --diff flag is set to never.");
-    } else {
+    let current_profile = PathBuf::from(CURRENT_PROFILE);
+    if wants_diff(&self.common.diff, &current_profile, &target_profile) {
       debug!(
         "Comparing with target profile: {}",
         target_profile.display()
       );
-      let _ = print_dix_diff(&PathBuf::from(CURRENT_PROFILE), &target_profile);
+      let _ = timings
+        .time("diff", || print_dix_diff(&current_profile, &target_profile));
     }
 
     if self.common.ask && !self.common.dry && !matches!(variant, Build) {
-      let confirmation = inquire::Confirm::new("Apply the config?")
-        .with_default(false)
-        .prompt()?;
+      let confirmation = confirm("Apply the config?").prompt()?;
 
       if !confirmation {
-        bail!("User rejected the new config");
+        bail!(Aborted("User rejected the new config"));
       }
     }
 
     if matches!(variant, Switch) {
-      Command::new("nix")
-        .args(["build", "--no-link", "--profile", SYSTEM_PROFILE])
-        .arg(&out_path)
-        .elevate(Some(elevation.clone()))
-        .dry(self.common.dry)
-        .with_required_env()
-        .run()
-        .wrap_err("Failed to set Darwin system profile")?;
-
-      let darwin_rebuild = out_path.join("sw/bin/darwin-rebuild");
-      let activate_user = out_path.join("activate-user");
-
-      // Determine if we need to elevate privileges
-      let needs_elevation = !activate_user
-        .try_exists()
-        .context("Failed to check if activate-user file exists")?
-        || std::fs::read_to_string(&activate_user)
-          .context("Failed to read activate-user file")?
-          .contains("# nix-darwin: deprecated");
-
-      // Create and run the activation command with or without elevation
-      Command::new(darwin_rebuild)
-        .arg("activate")
-        .message("Activating configuration")
-        .elevate(needs_elevation.then_some(elevation))
-        .dry(self.common.dry)
-        .show_output(self.show_activation_logs)
-        .with_required_env()
-        .run()
-        .wrap_err("Darwin activation failed")?;
+      exit::enter(Phase::Activation);
+      timings.time("activation", || -> Result<()> {
+        Command::new("nix")
+          .args(["build", "--no-link", "--profile", SYSTEM_PROFILE])
+          .arg(&out_path)
+          .elevate(Some(elevation.clone()))
+          .dry(self.common.dry)
+          .with_required_env()
+          .run()
+          .wrap_err("Failed to set Darwin system profile")?;
+
+        let darwin_rebuild = out_path.join("sw/bin/darwin-rebuild");
+        let activate_user = out_path.join("activate-user");
+
+        // Determine if we need to elevate privileges
+        let needs_elevation = !activate_user
+          .try_exists()
+          .context("Failed to check if activate-user file exists")?
+          || std::fs::read_to_string(&activate_user)
+            .context("Failed to read activate-user file")?
+            .contains("# nix-darwin: deprecated");
+
+        // Create and run the activation command with or without elevation
+        Command::new(darwin_rebuild)
+          .arg("activate")
+          .message("Activating configuration")
+          .elevate(needs_elevation.then_some(elevation))
+          .dry(self.common.dry)
+          .show_output(self.show_activation_logs)
+          .with_required_env()
+          .run()
+          .wrap_err("Darwin activation failed")?;
+        Ok(())
+      })?;
+      if !self.common.dry {
+        outcome::record_activated();
+        self.common.passthrough.warn_unpersisted_overrides();
+      }
     }
 
     debug!("Completed operation with output path: {out_path:?}");
@@ -1,23 +1,31 @@
 use std::{
-  fmt,
-  io,
+  fs,
+  io::Write,
   path::{Path, PathBuf},
+  process::{Command, Stdio},
+  sync::OnceLock,
   thread,
 };
 
-use color_eyre::eyre::{Result, eyre};
-use nh_core::{args::DiffType, progress};
+use clap::Args;
+use color_eyre::eyre::{Result, bail, eyre};
+use nh_core::{
+  args::DiffType,
+  outcome::{self, DiffSummary},
+  progress,
+};
 use nh_remote::{RemoteHost, ResolvedRemoteStorePath};
 use tracing::{debug, info, warn};
 use yansi::Paint;
 
 const NIXOS_CURRENT_PROFILE: &str = "/run/current-system";
 
-struct WriteFmt<W: io::Write>(W);
-impl<W: io::Write> fmt::Write for WriteFmt<W> {
-  fn write_str(&mut self, string: &str) -> fmt::Result {
-    self.0.write_all(string.as_bytes()).map_err(|_| fmt::Error)
-  }
+static DIFF_TOOL: OnceLock<String> = OnceLock::new();
+
+/// Pipe package diffs into `tool`, e.g. `less -R`, instead of printing them.
+/// Set from `--diff-tool`.
+pub fn set_diff_tool(tool: String) {
+  let _ = DIFF_TOOL.set(tool);
 }
 
 struct QueriedDiff {
@@ -49,8 +57,20 @@ impl DiffEndpoint {
 
 impl QueriedDiff {
   fn write(&self) -> Result<()> {
-    print_dix_header_raw(&self.old_label, &self.new_label);
-    write_dix_report(&self.report)
+    outcome::record_diff(DiffSummary {
+      changed_packages: self.report.diffs().len(),
+      added_paths:      self.report.path_stats().added_count(),
+      removed_paths:    self.report.path_stats().removed_count(),
+    });
+    let mut text = dix_header(&self.old_label, &self.new_label);
+    let wrote = dix::write_diff_report(&mut text, &self.report)?;
+    show_diff(&text);
+
+    if wrote == 0 && self.report.size_old() == self.report.size_new() {
+      info!("No version or size changes.");
+    }
+
+    Ok(())
   }
 }
 
@@ -64,7 +84,82 @@ pub fn print_dix_diff(
   old_generation: &Path,
   new_generation: &Path,
 ) -> Result<()> {
-  query_local_dix_diff(old_generation, new_generation)?.write()
+  let spinner = progress::spinner("Comparing changes...");
+  let diff = query_local_dix_diff(old_generation, new_generation);
+  spinner.finish_and_clear();
+
+  diff?.write()
+}
+
+/// Whether a rebuild should show the diff from `current` to `target`.
+///
+/// `Auto` only shows it if the two are different store paths, and prints
+/// "No changes" otherwise. `Always` shows it even if nothing changed.
+#[must_use]
+pub fn wants_diff(diff: &DiffType, current: &Path, target: &Path) -> bool {
+  match diff {
+    DiffType::Never => {
+      debug!("Not running dix as the --diff flag is set to never.");
+      false
+    },
+    DiffType::Auto if same_store_path(current, target) => {
+      info!("No changes");
+      false
+    },
+    DiffType::Always | DiffType::Auto => true,
+  }
+}
+
+/// Whether both paths resolve to the same store path. Paths that cannot be
+/// resolved are treated as different.
+fn same_store_path(current: &Path, target: &Path) -> bool {
+  match (fs::canonicalize(current), fs::canonicalize(target)) {
+    (Ok(current), Ok(target)) => current == target,
+    _ => false,
+  }
+}
+
+#[derive(Debug, Args)]
+pub struct DiffGenerationsArgs {
+  /// The older generation number
+  pub old: u64,
+
+  /// The newer generation number
+  pub new: u64,
+}
+
+impl DiffGenerationsArgs {
+  /// Diff two generations of `profile`, e.g. `/nix/var/nix/profiles/system`.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if either generation does not exist or the diff fails.
+  pub fn run(&self, profile: &Path) -> Result<()> {
+    let old = existing_generation_link(profile, self.old)?;
+    let new = existing_generation_link(profile, self.new)?;
+    print_dix_diff(&old, &new)
+  }
+}
+
+fn existing_generation_link(profile: &Path, number: u64) -> Result<PathBuf> {
+  let Some(link) = generation_link(profile, number) else {
+    bail!("Invalid profile path {}", profile.display());
+  };
+  if !link.exists() {
+    bail!(
+      "Generation {number} of {} does not exist",
+      profile.display()
+    );
+  }
+  Ok(link)
+}
+
+/// The link of generation `number` of `profile`, which Nix names
+/// `<profile>-<number>-link` next to the profile itself.
+#[must_use]
+pub fn generation_link(profile: &Path, number: u64) -> Option<PathBuf> {
+  let name = profile.file_name()?.to_str()?;
+  Some(profile.with_file_name(format!("{name}-{number}-link")))
 }
 
 fn query_local_dix_diff(
@@ -114,6 +209,12 @@ pub fn handle_nixos_diff(
       );
       return Ok(());
     },
+    DiffType::Auto
+      if target_host.is_none()
+        && !wants_diff(diff, current_profile, target_profile) =>
+    {
+      return Ok(());
+    },
     DiffType::Auto => {
       debug!(
         "Comparing current profile {} with target profile: {}",
@@ -229,35 +330,169 @@ fn display_path(path: &Path) -> PathBuf {
   std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
 
-fn print_dix_header_raw(old_label: &Path, new_label: &Path) {
-  println!(
-    "{arrows} {old}",
-    arrows = Paint::new("<<<").bold(),
+fn dix_header(old_label: &Path, new_label: &Path) -> String {
+  format!(
+    "{old_arrows} {old}\n{new_arrows} {new}\n",
+    old_arrows = Paint::new("<<<").bold(),
     old = old_label.display(),
-  );
-  println!(
-    "{arrows} {new}",
-    arrows = Paint::new(">>>").bold(),
+    new_arrows = Paint::new(">>>").bold(),
     new = new_label.display(),
+  )
+}
+
+/// Print a rendered diff, or pipe it into the `--diff-tool` if one is set.
+/// If the tool cannot be run, the diff is printed instead.
+fn show_diff(text: &str) {
+  show_diff_with(
+    DIFF_TOOL.get().map(String::as_str),
+    text,
+    &mut std::io::stdout(),
   );
 }
 
-fn write_dix_report(report: &dix::DiffReport) -> Result<()> {
-  let stdout = io::stdout();
-  let mut out = WriteFmt(stdout.lock());
-  let wrote = dix::write_diff_report(&mut out, report)?;
+/// Pipe `text` into `tool`, or write it to `fallback` if there is no tool or
+/// it cannot be run.
+fn show_diff_with(tool: Option<&str>, text: &str, fallback: &mut impl Write) {
+  if let Some(tool) = tool {
+    match tool_command(tool).and_then(|command| pipe_to_tool(command, text)) {
+      Ok(()) => return,
+      Err(err) => {
+        warn!("Could not run diff tool '{tool}', printing the diff: {err}");
+      },
+    }
+  }
+  let _ = fallback.write_all(text.as_bytes());
+}
 
-  if wrote == 0 && report.size_old() == report.size_new() {
-    info!("No version or size changes.");
+/// The command for a diff tool given as a shell-quoted string, e.g. `less -R`.
+fn tool_command(tool: &str) -> Result<Command> {
+  let Some(args) = shlex::split(tool) else {
+    bail!("check its quoting");
+  };
+  let Some((program, args)) = args.split_first() else {
+    bail!("it is empty");
+  };
+
+  let mut command = Command::new(program);
+  command.args(args).stdin(Stdio::piped());
+  Ok(command)
+}
+
+fn pipe_to_tool(mut command: Command, text: &str) -> Result<()> {
+  let mut child = command.spawn()?;
+  if let Some(mut stdin) = child.stdin.take() {
+    // Tools like less may exit before reading all of it
+    let _ = stdin.write_all(text.as_bytes());
   }
 
+  let status = child.wait()?;
+  if !status.success() {
+    warn!(
+      "Diff tool '{}' exited with {status}",
+      command.get_program().display()
+    );
+  }
   Ok(())
 }
 
 #[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Fine in tests")]
 mod tests {
+  use std::io;
+
   use super::*;
 
+  #[test]
+  fn auto_diff_skips_unchanged_configuration() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let old = dir.path().join("old-system");
+    let new = dir.path().join("new-system");
+    fs::create_dir(&old)?;
+    fs::create_dir(&new)?;
+    let current = dir.path().join("current-system");
+    let result = dir.path().join("result");
+    std::os::unix::fs::symlink(&old, &current)?;
+    std::os::unix::fs::symlink(&old, &result)?;
+
+    assert!(!wants_diff(&DiffType::Auto, &current, &result));
+    assert!(wants_diff(&DiffType::Auto, &current, &new));
+    assert!(wants_diff(&DiffType::Always, &current, &result));
+    assert!(!wants_diff(&DiffType::Never, &current, &new));
+    Ok(())
+  }
+
+  #[test]
+  fn auto_diff_treats_missing_paths_as_changed() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let missing = dir.path().join("missing");
+
+    assert!(wants_diff(&DiffType::Auto, &missing, &missing));
+    Ok(())
+  }
+
+  #[test]
+  fn diff_tool_receives_the_diff() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let out = dir.path().join("out");
+    let tool = format!("sh -c 'cat > \"$0\"' {}", out.display());
+    let mut fallback = Vec::new();
+
+    show_diff_with(Some(&tool), "a diff\n", &mut fallback);
+
+    assert_eq!(fs::read_to_string(&out)?, "a diff\n");
+    assert!(fallback.is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn missing_diff_tool_falls_back_to_printing() {
+    let mut fallback = Vec::new();
+
+    show_diff_with(
+      Some("nh-test-no-such-diff-tool"),
+      "a diff\n",
+      &mut fallback,
+    );
+
+    assert_eq!(fallback, b"a diff\n");
+  }
+
+  #[test]
+  fn badly_quoted_diff_tool_falls_back_to_printing() {
+    let err = tool_command("less 'unterminated").map(drop).unwrap_err();
+    assert_eq!(err.to_string(), "check its quoting");
+    assert_eq!(
+      tool_command("").map(drop).unwrap_err().to_string(),
+      "it is empty"
+    );
+
+    let mut fallback = Vec::new();
+    show_diff_with(Some("less 'unterminated"), "a diff\n", &mut fallback);
+
+    assert_eq!(fallback, b"a diff\n");
+  }
+
+  #[test]
+  fn diff_tool_arguments_are_shell_split() -> Result<()> {
+    let command = tool_command("delta --side-by-side 'a b'")?;
+
+    assert_eq!(command.get_program(), "delta");
+    assert_eq!(command.get_args().collect::<Vec<_>>(), [
+      "--side-by-side",
+      "a b"
+    ]);
+    Ok(())
+  }
+
+  #[test]
+  fn generation_link_is_next_to_profile() {
+    assert_eq!(
+      generation_link(Path::new("/nix/var/nix/profiles/system"), 42),
+      Some(PathBuf::from("/nix/var/nix/profiles/system-42-link"))
+    );
+    assert_eq!(generation_link(Path::new("/"), 42), None);
+  }
+
   #[test]
   fn remote_profile_path_uses_store_path_for_base_profile() {
     let out_path = Path::new("result");
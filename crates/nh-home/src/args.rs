@@ -7,7 +7,9 @@ use nh_core::{
     HomeReplFeatures,
     LegacyFeatures,
   },
+  flake_check::FlakeCheckArgs,
 };
+use nh_diff::DiffGenerationsArgs;
 use nh_installable::{CommandContext, InstallableArgs};
 use nh_remote::RemoteHost;
 
@@ -21,6 +23,15 @@ pub enum HomeSubcommand {
 
   /// Load a home-manager configuration in a Nix REPL
   Repl(HomeReplArgs),
+
+  /// Run the checks of the configuration's flake with `nix flake check`
+  Check(FlakeCheckArgs),
+
+  /// List the specialisations of the current generation
+  ListSpecialisations,
+
+  /// Show what changed between two generations
+  DiffGenerations(DiffGenerationsArgs),
 }
 
 #[derive(Debug, Args)]
@@ -45,6 +56,15 @@ impl HomeArgs {
           Box::new(LegacyFeatures)
         }
       },
+      HomeSubcommand::Check(args) => {
+        if args.uses_flakes(CommandContext::Home) {
+          Box::new(FlakeFeatures)
+        } else {
+          Box::new(LegacyFeatures)
+        }
+      },
+      HomeSubcommand::DiffGenerations(_)
+      | HomeSubcommand::ListSpecialisations => Box::new(LegacyFeatures),
     }
   }
 }
@@ -68,7 +88,7 @@ pub struct HomeRebuildArgs {
   pub specialisation: Option<String>,
 
   /// Ignore specialisations
-  #[arg(long, short = 'S')]
+  #[arg(long, short = 'S', conflicts_with = "specialisation")]
   pub no_specialisation: bool,
 
   /// Extra arguments passed to nix build
@@ -79,6 +99,10 @@ pub struct HomeRebuildArgs {
   #[arg(long, short = 'b')]
   pub backup_extension: Option<String>,
 
+  /// Allow activating the configuration as root, into root's home directory
+  #[arg(short = 'R', long, env = "NH_BYPASS_ROOT_CHECK")]
+  pub bypass_root_check: bool,
+
   /// Show activation logs
   #[arg(long, env = "NH_SHOW_ACTIVATION_LOGS", value_parser = clap::builder::BoolishValueParser::new())]
   pub show_activation_logs: bool,
@@ -86,6 +110,14 @@ pub struct HomeRebuildArgs {
   /// Build the configuration on a different host over SSH
   #[arg(long)]
   pub build_host: Option<RemoteHost>,
+
+  /// Activate the configuration on a different host over SSH, as the SSH user
+  #[arg(long)]
+  pub target_host: Option<RemoteHost>,
+
+  /// Print the total closure size of the built configuration
+  #[arg(long)]
+  pub print_closure_size: bool,
 }
 
 impl HomeRebuildArgs {
@@ -117,3 +149,37 @@ impl HomeReplArgs {
     self.installable.uses_flakes(CommandContext::Home)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use clap::Parser;
+
+  use super::*;
+
+  #[derive(Parser)]
+  struct Cli {
+    #[command(flatten)]
+    home: HomeArgs,
+  }
+
+  #[test]
+  fn specialisation_conflicts_with_no_specialisation() {
+    for subcommand in ["switch", "build"] {
+      assert!(
+        Cli::try_parse_from([
+          "nh",
+          subcommand,
+          "--specialisation",
+          "gaming",
+          "--no-specialisation",
+        ])
+        .is_err(),
+        "{subcommand}"
+      );
+      assert!(
+        Cli::try_parse_from(["nh", subcommand, "--no-specialisation"]).is_ok(),
+        "{subcommand}"
+      );
+    }
+  }
+}
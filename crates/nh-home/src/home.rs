@@ -1,6 +1,10 @@
 pub mod args;
 
-use std::{convert::Into, env, ffi::OsString, path::PathBuf};
+use std::{
+  env,
+  ffi::{OsStr, OsString},
+  path::{Path, PathBuf},
+};
 
 use args::{HomeRebuildArgs, HomeReplArgs, HomeSubcommand};
 use color_eyre::{
@@ -8,13 +12,30 @@ use color_eyre::{
   eyre::{Context, bail, eyre},
 };
 use nh_core::{
+  args::split_nom_args,
   command::{self, Command, CommandKind, NixCommand},
+  confirm::confirm,
+  exit::{self, Aborted, Phase},
+  flake_metadata::show_flake_metadata,
+  outcome,
+  progress,
+  summary::RebuildSummary,
+  timings::Timings,
   update::update,
-  util::get_hostname,
+  util::{
+    available_specialisations,
+    check_expected_path,
+    format_size,
+    get_closure_size,
+    get_hostname,
+    list_specialisations,
+    result_tempdir,
+  },
 };
-use nh_diff::print_dix_diff;
+use nh_diff::{print_dix_diff, wants_diff};
 use nh_installable::{CommandContext, Installable};
-use nh_remote::{self, RemoteBuildConfig};
+use nh_remote::{self, RemoteBuildConfig, RemoteHost};
+use nix::unistd::{Uid, User};
 use tracing::{debug, info, warn};
 
 fn capture_nix_stdout(command: &NixCommand) -> Result<String> {
@@ -35,6 +56,43 @@ fn capture_nix_stdout(command: &NixCommand) -> Result<String> {
     .wrap_err("nix command emitted non-UTF-8 stdout")
 }
 
+/// Look up the passwd entry of the current user, for environments that do not
+/// set `USER` or `HOME` (cron, some sudo configurations).
+fn current_user_entry() -> Result<User> {
+  let uid = Uid::current();
+  User::from_uid(uid)
+    .wrap_err_with(|| format!("Failed to look up user {uid} in passwd"))?
+    .ok_or_else(|| eyre!("No passwd entry for user {uid}"))
+}
+
+/// The current username, from `USER` or the passwd database.
+fn current_username() -> Result<String> {
+  if let Ok(username) = env::var("USER") {
+    return Ok(username);
+  }
+
+  debug!("USER is not set, looking up the username in passwd");
+  current_user_entry()
+    .map(|user| user.name)
+    .wrap_err("Couldn't get username, USER is not set")
+}
+
+/// The current home directory, from `HOME` or the passwd database.
+fn home_directory() -> Result<String> {
+  if let Ok(home) = env::var("HOME") {
+    return Ok(home);
+  }
+
+  debug!("HOME is not set, looking up the home directory in passwd");
+  let user = current_user_entry()
+    .wrap_err("Couldn't get home directory, HOME is not set")?;
+  user
+    .dir
+    .into_os_string()
+    .into_string()
+    .map_err(|dir| eyre!("Home directory {} is not valid UTF-8", dir.display()))
+}
+
 impl args::HomeArgs {
   /// Run the `home` subcommand.
   ///
@@ -65,6 +123,24 @@ impl args::HomeArgs {
         args.rebuild(&Build)
       },
       HomeSubcommand::Repl(args) => args.run(),
+      HomeSubcommand::Check(args) => args.run(CommandContext::Home),
+      HomeSubcommand::ListSpecialisations => {
+        let profile = home_profile()?
+          .ok_or_else(|| eyre!("No Home Manager profile found"))?;
+        let names = list_specialisations(&profile.join("specialisation"));
+        if names.is_empty() {
+          info!("The current generation has no specialisations");
+        }
+        for name in names {
+          println!("{name}");
+        }
+        Ok(())
+      },
+      HomeSubcommand::DiffGenerations(args) => {
+        let profile = home_profile()?
+          .ok_or_else(|| eyre!("No Home Manager profile found"))?;
+        args.run(&profile)
+      },
     }
   }
 }
@@ -75,32 +151,88 @@ enum HomeRebuildVariant {
   Switch,
 }
 
+impl HomeRebuildVariant {
+  /// The `nh` subcommand this variant corresponds to.
+  const fn command(&self) -> &'static str {
+    match self {
+      Self::Build => "home build",
+      Self::Switch => "home switch",
+    }
+  }
+}
+
 impl HomeRebuildArgs {
-  fn rebuild(self, variant: &HomeRebuildVariant) -> Result<()> {
-    use HomeRebuildVariant::Build;
+  fn rebuild(mut self, variant: &HomeRebuildVariant) -> Result<()> {
+    use HomeRebuildVariant::{Build, Switch};
+
+    exit::enter(Phase::Precheck);
+    if matches!(variant, Switch)
+      && self.target_host.is_none()
+      && nix::unistd::Uid::effective().is_root()
+      && !self.bypass_root_check
+    {
+      bail!(
+        "Don't run nh home switch as root, it would activate the \
+         configuration into root's home directory. Run it as the user the \
+         configuration is for, or pass --bypass-root-check if you do mean to \
+         manage root's home"
+      );
+    }
+
+    self.common.passthrough.validate()?;
+    self
+      .common
+      .passthrough
+      .warn_no_local_build(self.build_host.is_some());
+    let nom_args = split_nom_args(self.common.nom_args.as_deref())?;
+    exit::enter(Phase::Build);
+
+    if self.common.dry {
+      progress::set_spinners_enabled(false);
+    }
 
     let (out_path, _tempdir_guard): (PathBuf, Option<tempfile::TempDir>) =
       if let Some(ref p) = self.common.out_link {
         (p.clone(), None)
+      } else if self.common.keep_result {
+        (PathBuf::from("result"), None)
       } else {
-        let dir = tempfile::Builder::new().prefix("nh-home").tempdir()?;
+        let dir = result_tempdir("nh-home")?;
         (dir.as_ref().join("result"), Some(dir))
       };
 
     debug!("Output path: {out_path:?}");
 
+    let mut timings = Timings::new(self.common.timings);
+
     let installable = self
       .common
       .installable
       .clone()
       .resolve_or_default(CommandContext::Home)?;
+    self.common.passthrough.confirm_flake_config(&installable)?;
+    if self.common.show_metadata {
+      show_flake_metadata(&installable)?;
+    }
+    RebuildSummary {
+      command:     variant.command(),
+      installable: &installable,
+      hostname:    self.configuration.as_deref(),
+      update:      &self.update_args,
+      build_host:  self.build_host.as_ref().map(ToString::to_string),
+      target_host: self.target_host.as_ref().map(ToString::to_string),
+      diff:        &self.common.diff,
+    }
+    .print();
 
     if self.update_args.update_all || self.update_args.update_input.is_some() {
-      update(
-        &installable,
-        self.update_args.update_input,
-        self.common.passthrough.commit_lock_file,
-      )?;
+      timings.time("update", || {
+        update(
+          &installable,
+          self.update_args.update_input.clone(),
+          self.common.passthrough.commit_lock_file,
+        )
+      })?;
     }
 
     let toplevel = toplevel_for(
@@ -110,104 +242,130 @@ impl HomeRebuildArgs {
       self.configuration.clone(),
     )?;
 
+    // Initialize SSH control if we have remote hosts - guard will keep
+    // connections alive for both build and activation
+    let _ssh_guard = if self.build_host.is_some() || self.target_host.is_some()
+    {
+      let guard = nh_remote::init_ssh_control();
+
+      if let Some(target_host) = &self.target_host {
+        nh_remote::open_ssh_control_master(target_host)
+          .context("Failed to establish SSH connection to target host")?;
+      }
+
+      Some(guard)
+    } else {
+      None
+    };
+
     // If a build host is specified, use remote build semantics
-    if let Some(build_host) = self.build_host {
+    let actual_store_path = if let Some(build_host) = self.build_host.clone() {
       info!("Building Home-Manager configuration");
 
       let config = RemoteBuildConfig {
         build_host,
-        target_host: None,
+        target_host: self.target_host.clone(),
         use_nom: !self.common.no_nom,
+        nom_args,
         use_substitutes: self.common.passthrough.use_substitutes,
-        extra_args: self
-          .extra_args
-          .iter()
-          .map(Into::into)
-          .chain(
-            self
-              .common
-              .passthrough
-              .generate_passthrough_args()
-              .into_iter()
-              .map(Into::into),
-          )
-          .collect(),
+        extra_args: self.common.passthrough.remote_build_args(&self.extra_args),
       };
 
-      // Initialize SSH control - guard will cleanup connections on drop
-      let _ssh_guard = nh_remote::init_ssh_control();
-
-      nh_remote::build_remote(&toplevel, &config, Some(&out_path))
-        .wrap_err("Failed to build Home-Manager configuration")?;
+      let store_path = timings.time("build", || {
+        nh_remote::build_remote(&toplevel, &config, Some(&out_path))
+          .wrap_err("Failed to build Home-Manager configuration")
+      })?;
+      Some(store_path)
     } else {
-      command::Build::new(toplevel)
-        .extra_arg("--out-link")
-        .extra_arg(&out_path)
-        .extra_args(&self.extra_args)
-        .passthrough(&self.common.passthrough)
-        .message("Building Home-Manager configuration")
-        .nom(!self.common.no_nom)
-        .run()
-        .wrap_err("Failed to build Home-Manager configuration")?;
-    }
+      timings.time("build", || {
+        command::Build::new(toplevel)
+          .extra_arg("--out-link")
+          .extra_arg(&out_path)
+          .extra_args(&self.extra_args)
+          .passthrough(&self.common.passthrough)
+          .message("Building Home-Manager configuration")
+          .nom(!self.common.no_nom)
+          .nom_args(nom_args)
+          .run()
+          .wrap_err("Failed to build Home-Manager configuration")
+      })?;
+      None
+    };
 
-    let username =
-      env::var("USER").map_err(|_| eyre!("Couldn't get username"))?;
-    let home_dir =
-      env::var("HOME").map_err(|_| eyre!("Couldn't get home directory"))?;
-    let state_home = env::var("XDG_STATE_HOME")
-      .unwrap_or_else(|_| format!("{home_dir}/.local/state"));
-    let data_home = env::var("XDG_DATA_HOME")
-      .unwrap_or_else(|_| format!("{home_dir}/.local/share"));
-
-    // Match Home Manager's profile discovery: prefer $XDG_STATE_HOME if set,
-    // otherwise fall back to the global per-user profile directory.
-    let prev_generation: Option<PathBuf> = [
-      PathBuf::from(&state_home).join("nix/profiles/home-manager"),
-      PathBuf::from("/nix/var/nix/profiles/per-user")
-        .join(&username)
-        .join("home-manager"),
-    ]
-    .into_iter()
-    .find(|next| next.exists());
+    // When the result was built for the target host and not copied back, it
+    // only exists there, so refer to it by store path
+    let result_path = match actual_store_path {
+      Some(store_path) if !out_path.exists() => store_path,
+      _ => out_path,
+    };
+    if let Some(expected) = &self.common.expect_path {
+      check_expected_path(expected, &result_path)?;
+    }
+    outcome::record_built(&result_path);
 
-    debug!("Previous generation: {prev_generation:?}");
+    if self.print_closure_size {
+      if result_path.exists() {
+        let size = get_closure_size(&result_path)
+          .wrap_err("Failed to compute the closure size")?;
+        println!("Closure size: {}", format_size(size));
+      } else {
+        warn!("--print-closure-size needs the result in the local store");
+      }
+    }
 
-    let spec_location =
-      PathBuf::from(data_home).join("home-manager/specialisation");
+    // The local generation and specialisation say nothing about the target
+    // host, so only the explicitly requested specialisation is used there
+    let (prev_generation, current_specialisation) =
+      if self.target_host.is_some() {
+        (None, None)
+      } else {
+        local_home_state()?
+      };
 
-    let current_specialisation = spec_location.to_str().map_or_else(
-      || {
-        tracing::warn!("spec_location path is not valid UTF-8");
-        None
-      },
-      |s| std::fs::read_to_string(s).ok().map(|s| s.trim().to_owned()),
-    );
+    debug!("Previous generation: {prev_generation:?}");
 
-    let target_specialisation = if self.no_specialisation {
+    let mut target_specialisation = if self.no_specialisation {
       None
     } else {
-      self.specialisation.or(current_specialisation)
+      self.specialisation.clone().or(current_specialisation)
     };
 
     debug!("target_specialisation: {target_specialisation:?}");
 
+    // Only checked when the result is local; a remote-only result is checked
+    // by the activation itself.
+    let specialisations = result_path.join("specialisation");
+    if let Some(spec) = &target_specialisation
+      && result_path.exists()
+      && !specialisations.join(spec).exists()
+    {
+      let available = available_specialisations(&specialisations);
+      if self.specialisation.is_some() {
+        bail!(
+          "Specialisation '{spec}' not found in {}, available: {available}",
+          specialisations.display()
+        );
+      }
+      warn!(
+        "The current specialisation '{spec}' does not exist in the new \
+         configuration (available: {available}), using the base configuration \
+         instead"
+      );
+      target_specialisation = None;
+    }
+
     let target_profile: PathBuf = if let Some(spec) = &target_specialisation {
-      out_path.join("specialisation").join(spec)
+      result_path.join("specialisation").join(spec)
     } else {
-      out_path
+      result_path
     };
 
     // just do nothing for None case (fresh installs)
-    if let Some(generation) = prev_generation {
-      match self.common.diff {
-        nh_core::args::DiffType::Never => {
-          debug!("Not running dix as the --diff flag is set to never.");
-        },
-        _ => {
-          let _ = print_dix_diff(&generation, &target_profile);
-        },
-      }
+    if let Some(generation) = prev_generation
+      && wants_diff(&self.common.diff, &generation, &target_profile)
+    {
+      let _ =
+        timings.time("diff", || print_dix_diff(&generation, &target_profile));
     }
 
     if self.common.dry || matches!(variant, Build) {
@@ -218,15 +376,14 @@ impl HomeRebuildArgs {
     }
 
     if self.common.ask {
-      let confirmation = inquire::Confirm::new("Apply the config?")
-        .with_default(false)
-        .prompt()?;
+      let confirmation = confirm("Apply the config?").prompt()?;
 
       if !confirmation {
-        bail!("User rejected the new config");
+        bail!(Aborted("User rejected the new config"));
       }
     }
 
+    exit::enter(Phase::Activation);
     if let Some(ext) = &self.backup_extension {
       info!("Using {} as the backup extension", ext);
       unsafe {
@@ -234,17 +391,152 @@ impl HomeRebuildArgs {
       }
     }
 
-    Command::new(target_profile.join("activate"))
-      .with_required_env()
-      .message("Activating configuration")
-      .show_output(self.show_activation_logs)
-      .run()
-      .wrap_err("Activation failed")?;
+    timings.time("activation", || {
+      self.target_host.as_ref().map_or_else(
+        || self.activate_locally(&target_profile),
+        |target_host| self.activate_on_target(target_host, &target_profile),
+      )
+    })?;
+    outcome::record_activated();
+    self.common.passthrough.warn_unpersisted_overrides();
 
     debug!("Completed operation with output path: {target_profile:?}");
 
     Ok(())
   }
+
+  /// Run the activation script of `target_profile`. On failure, the error
+  /// names the Home Manager activation step that failed, if it can be told
+  /// from the output.
+  fn activate_locally(&self, target_profile: &Path) -> Result<()> {
+    let Some((exit_status, output)) =
+      Command::new(target_profile.join("activate"))
+        .with_required_env()
+        .message("Activating configuration")
+        .show_output(self.show_activation_logs)
+        .run_with_output()?
+    else {
+      return Ok(());
+    };
+
+    if exit_status.success() {
+      return Ok(());
+    }
+
+    let mut err = eyre!("Activation script exited with status {exit_status:?}");
+    if !self.show_activation_logs && !output.trim().is_empty() {
+      err = err.wrap_err(format!("Activation output:\n{}", output.trim_end()));
+    }
+    let context = failed_activation_step(&output).map_or_else(
+      || "Activation failed".to_owned(),
+      |step| format!("Home Manager activation failed at step '{step}'"),
+    );
+    Err(err.wrap_err(context))
+  }
+
+  /// Copy the configuration to `target_host` if it is in the local store,
+  /// then activate it there over SSH.
+  fn activate_on_target(
+    &self,
+    target_host: &RemoteHost,
+    target_profile: &Path,
+  ) -> Result<()> {
+    let store_path = if target_profile.exists() {
+      let store_path = target_profile
+        .canonicalize()
+        .context("Failed to resolve output path to actual store path")?;
+      nh_remote::copy_to_remote(
+        target_host,
+        &store_path,
+        self.common.passthrough.use_substitutes,
+      )
+      .context("Failed to copy configuration to target host")?;
+      store_path
+    } else {
+      target_profile.to_path_buf()
+    };
+
+    nh_remote::activate_remote(
+      target_host,
+      &store_path,
+      &nh_remote::ActivateRemoteConfig {
+        platform:           nh_remote::Platform::HomeManager,
+        activation_type:    nh_remote::ActivationType::Switch,
+        install_bootloader: false,
+        show_logs:          self.show_activation_logs,
+        elevation:          None,
+        profile:            None,
+      },
+    )
+    .wrap_err("Activation failed")
+  }
+}
+
+/// Directory holding Home Manager's `specialisation` file. Defaults to
+/// `$XDG_DATA_HOME/home-manager` and can be overridden with
+/// `NH_HOME_SPECIALISATION_DIR` for non-default setups.
+fn specialisation_dir(data_home: &str) -> PathBuf {
+  env::var_os("NH_HOME_SPECIALISATION_DIR")
+    .filter(|dir| !dir.is_empty())
+    .map_or_else(
+      || PathBuf::from(data_home).join("home-manager"),
+      PathBuf::from,
+    )
+}
+
+/// The activation step that was running when the script stopped. Home
+/// Manager announces each step with an `Activating <step>` line.
+fn failed_activation_step(output: &str) -> Option<&str> {
+  output
+    .lines()
+    .rev()
+    .find_map(|line| line.trim().strip_prefix("Activating "))
+    .and_then(|step| step.split_whitespace().next())
+}
+
+/// Find the Home Manager profile of the local user.
+///
+/// Matches Home Manager's profile discovery: prefer `$XDG_STATE_HOME` if set,
+/// otherwise fall back to the global per-user profile directory.
+fn home_profile() -> Result<Option<PathBuf>> {
+  let username = current_username()?;
+  let home_dir = home_directory()?;
+  let state_home = env::var("XDG_STATE_HOME")
+    .unwrap_or_else(|_| format!("{home_dir}/.local/state"));
+
+  Ok(
+    [
+      PathBuf::from(&state_home).join("nix/profiles/home-manager"),
+      PathBuf::from("/nix/var/nix/profiles/per-user")
+        .join(&username)
+        .join("home-manager"),
+    ]
+    .into_iter()
+    .find(|next| next.exists()),
+  )
+}
+
+/// Find the current Home Manager generation and specialisation of the local
+/// user, the way Home Manager itself looks them up.
+fn local_home_state() -> Result<(Option<PathBuf>, Option<String>)> {
+  let home_dir = home_directory()?;
+  let data_home = env::var("XDG_DATA_HOME")
+    .unwrap_or_else(|_| format!("{home_dir}/.local/share"));
+
+  let prev_generation = home_profile()?;
+
+  let spec_location = specialisation_dir(&data_home).join("specialisation");
+  debug!("Specialisation file: {spec_location:?}");
+
+  let current_specialisation = spec_location.to_str().map_or_else(
+    || {
+      tracing::warn!("spec_location path is not valid UTF-8");
+      None
+    },
+    |s| std::fs::read_to_string(s).ok().map(|s| s.trim().to_owned()),
+  );
+
+  Ok((prev_generation, current_specialisation))
 }
 
 fn toplevel_for<I, S>(
@@ -354,23 +646,27 @@ where
             let mut attr_path = attribute.clone();
             attr_path.push(config_name);
             Installable::Flake {
-              reference: flake_reference,
+              reference: flake_reference.clone(),
               attribute: attr_path,
             }
             .to_args()
             .join(" ")
           };
+          let available = available_configurations_hint(
+            &flake_reference,
+            attribute,
+            &extra_args,
+          );
           bail!(
             "Explicitly specified home-manager configuration not found: \
-             {tried_attr_path}"
+             {tried_attr_path}{available}"
           );
         }
       }
 
       // If no explicit config was found via flag, try automatic detection
       if !found_config {
-        let username =
-          std::env::var("USER").map_err(|_| eyre!("Couldn't get username"))?;
+        let username = current_username()?;
         let hostname = get_hostname(None)?;
         let mut tried = vec![];
 
@@ -427,9 +723,14 @@ where
             })
             .collect::<Vec<_>>()
             .join(", ");
+          let available = available_configurations_hint(
+            &flake_reference,
+            attribute,
+            &extra_args,
+          );
           bail!(
             "Couldn't find home-manager configuration automatically, tried: \
-             {tried_str}"
+             {tried_str}{available}"
           );
         }
       }
@@ -450,6 +751,53 @@ where
   Ok(res)
 }
 
+/// Describe the `homeConfigurations` a flake does provide, to append to a
+/// "configuration not found" error.
+///
+/// Returns an empty string if they cannot be listed, so that the original
+/// error is still reported.
+fn available_configurations_hint<S: AsRef<OsStr>>(
+  flake_reference: &str,
+  attribute: &[String],
+  extra_args: &[S],
+) -> String {
+  let names = capture_nix_stdout(
+    &NixCommand::new(CommandKind::Eval)
+      .with_required_env()
+      .args(extra_args)
+      .arg("--json")
+      .arg("--apply")
+      .arg("builtins.attrNames")
+      .args(
+        (Installable::Flake {
+          reference: flake_reference.to_owned(),
+          attribute: attribute.to_vec(),
+        })
+        .to_args(),
+      ),
+  )
+  .and_then(|output| {
+    serde_json::from_str::<Vec<String>>(&output)
+      .wrap_err("Failed to parse homeConfigurations names")
+  });
+
+  match names {
+    Ok(names) if names.is_empty() => {
+      format!("\nThe flake does not define any {}", attribute.join("."))
+    },
+    Ok(names) => {
+      format!(
+        "\nAvailable configurations: {}\nUse --configuration to select one",
+        names.join(", ")
+      )
+    },
+    Err(err) => {
+      debug!("Failed to list available home-manager configurations: {err}");
+      String::new()
+    },
+  }
+}
+
 impl HomeReplArgs {
   fn run(self) -> Result<()> {
     let installable =
@@ -473,3 +821,26 @@ impl HomeReplArgs {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn failed_activation_step_is_the_last_announced_step() {
+    let output = "Starting Home Manager activation\nActivating \
+                  checkFilesChanged\nActivating linkGeneration\nCleaning up \
+                  orphan links...\nExisting file '/home/user/.bashrc' is in \
+                  the way";
+    assert_eq!(failed_activation_step(output), Some("linkGeneration"));
+  }
+
+  #[test]
+  fn failed_activation_step_needs_an_announced_step() {
+    assert_eq!(failed_activation_step(""), None);
+    assert_eq!(
+      failed_activation_step("error: building profile failed\n"),
+      None
+    );
+  }
+}
@@ -31,7 +31,11 @@ impl CommandContext {
 #[derive(Debug, Clone)]
 pub enum InstallableArgs {
   Specified(Installable),
-  Unspecified,
+  /// No installable was given. `attr` is the `--attr` value, if any, to select
+  /// from the installable the environment or the default resolves to.
+  Unspecified {
+    attr: Option<String>,
+  },
 }
 
 enum EnvInstallableSource {
@@ -109,6 +113,7 @@ impl FromArgMatches for InstallableArgs {
     let installable = matches.get_one::<String>("installable");
     let file = matches.get_one::<String>("file");
     let expr = matches.get_one::<String>("expr");
+    let attr = matches.get_one::<String>("attr");
 
     if let Some(i) = installable {
       let canonical = fs::canonicalize(i);
@@ -116,6 +121,12 @@ impl FromArgMatches for InstallableArgs {
       if let Ok(p) = canonical
         && p.starts_with("/nix/store")
       {
+        if attr.is_some() {
+          return Err(clap::Error::raw(
+            ErrorKind::ArgumentConflict,
+            "--attr cannot be used with a store path",
+          ));
+        }
         return Ok(Self::Specified(Installable::Store { path: p }));
       }
     }
@@ -129,8 +140,8 @@ impl FromArgMatches for InstallableArgs {
         )
       })?;
       return Ok(Self::Specified(Installable::File {
-        path: PathBuf::from(f),
-        attribute,
+        path:      PathBuf::from(f),
+        attribute: merge_attr(attribute, attr)?,
       }));
     }
 
@@ -144,7 +155,7 @@ impl FromArgMatches for InstallableArgs {
       })?;
       return Ok(Self::Specified(Installable::Expression {
         expression: e.clone(),
-        attribute,
+        attribute:  merge_attr(attribute, attr)?,
       }));
     }
 
@@ -157,11 +168,17 @@ impl FromArgMatches for InstallableArgs {
       })?;
       return Ok(Self::Specified(Installable::Flake {
         reference,
-        attribute,
+        attribute: merge_attr(attribute, attr)?,
       }));
     }
 
-    Ok(Self::Unspecified)
+    // Check the syntax now; the attribute path is merged once the installable
+    // is resolved from the environment or the default.
+    merge_attr(Vec::new(), attr)?;
+
+    Ok(Self::Unspecified {
+      attr: attr.cloned(),
+    })
   }
 
   fn update_from_arg_matches(
@@ -191,6 +208,17 @@ impl Args for InstallableArgs {
           .hide(true)
           .action(ArgAction::Set),
       )
+      .arg(
+        Arg::new("attr")
+          .short('A')
+          .long("attr")
+          .action(ArgAction::Set)
+          .value_name("ATTRPATH")
+          .help(
+            "Attribute path to select from the installable, as an alternative \
+             to FLAKEREF#ATTRPATH",
+          ),
+      )
       .arg(
         Arg::new("installable")
           .action(ArgAction::Set)
@@ -238,7 +266,13 @@ Nix accepts various kinds of installables:
   }
 }
 
-fn parse_attribute(s: &str) -> Result<Vec<String>, &'static str> {
+/// Split an attribute path like `config.system."build"` into its segments,
+/// honoring quoted segments that contain dots.
+///
+/// # Errors
+///
+/// Returns an error if a quoted segment or an escape in it is left open.
+pub fn parse_attribute(s: &str) -> Result<Vec<String>, &'static str> {
   let mut res = Vec::new();
 
   if s.is_empty() {
@@ -281,6 +315,33 @@ fn parse_attribute(s: &str) -> Result<Vec<String>, &'static str> {
   Ok(res)
 }
 
+/// Merge the attribute path given with `--attr` into the one parsed from the
+/// installable. Either may be empty, but if both are given they must match.
+fn merge_attr(
+  attribute: Vec<String>,
+  attr: Option<&String>,
+) -> Result<Vec<String>, clap::Error> {
+  let Some(attr) = attr else {
+    return Ok(attribute);
+  };
+  let attr = parse_attribute(attr).map_err(|err| {
+    clap::Error::raw(ErrorKind::ValueValidation, format!("--attr {err}"))
+  })?;
+
+  if attribute.is_empty() || attribute == attr {
+    Ok(attr)
+  } else {
+    Err(clap::Error::raw(
+      ErrorKind::ArgumentConflict,
+      format!(
+        "--attr {} conflicts with the attribute path {} of the installable",
+        attr.join("."),
+        attribute.join(".")
+      ),
+    ))
+  }
+}
+
 fn parse_flake_reference(
   value: &str,
 ) -> Result<(String, Vec<String>), &'static str> {
@@ -337,7 +398,7 @@ impl InstallableArgs {
     match self {
       Self::Specified(Installable::Flake { .. }) => true,
       Self::Specified(_) => false,
-      Self::Unspecified => {
+      Self::Unspecified { .. } => {
         env_installable_source(context)
           .is_some_and(|source| source.uses_flakes())
       },
@@ -353,7 +414,9 @@ impl InstallableArgs {
   /// - `NH_FILE`, with `NH_ATTRP` as the optional attribute path
   /// - `NH_FLAKE`
   ///
-  /// Returns `None` when no installable environment variable is set.
+  /// An `--attr` given without an installable is merged into the one found
+  /// in the environment. Returns `None` when no installable environment
+  /// variable is set.
   ///
   /// # Errors
   ///
@@ -364,9 +427,11 @@ impl InstallableArgs {
     context: CommandContext,
   ) -> color_eyre::Result<Option<Installable>> {
     match self {
-      Self::Unspecified => {
+      Self::Unspecified { attr } => {
         env_installable_source(context)
           .map(EnvInstallableSource::into_installable)
+          .transpose()?
+          .map(|installable| installable.with_attr(attr.as_ref()))
           .transpose()
       },
       Self::Specified(installable) => Ok(Some(installable)),
@@ -374,7 +439,8 @@ impl InstallableArgs {
   }
 
   /// Resolve an installable and fall back to the command-specific default when
-  /// the installable is unspecified.
+  /// the installable is unspecified. An `--attr` given without an installable
+  /// applies to the default as well.
   ///
   /// Explicit local flake references are validated before command execution. A
   /// supplied local path must point at the directory containing `flake.nix`;
@@ -389,11 +455,16 @@ impl InstallableArgs {
     self,
     context: CommandContext,
   ) -> color_eyre::Result<Installable> {
+    let attr = match &self {
+      Self::Unspecified { attr } => attr.clone(),
+      Self::Specified(_) => None,
+    };
     let Some(installable) = self.resolve(context)? else {
-      return default_installable_for(context);
+      return default_installable_for(context)?.with_attr(attr.as_ref());
     };
 
     installable.validate_local_flake_ref(context)?;
+    installable.validate_local_path()?;
     Ok(installable)
   }
 }
@@ -446,6 +517,40 @@ fn flake_from_env_var(
 }
 
 impl Installable {
+  /// Merge an attribute path given with `--attr` into the installable's own.
+  fn with_attr(self, attr: Option<&String>) -> color_eyre::Result<Self> {
+    Ok(match self {
+      Self::Flake {
+        reference,
+        attribute,
+      } => {
+        Self::Flake {
+          reference,
+          attribute: merge_attr(attribute, attr)?,
+        }
+      },
+      Self::File { path, attribute } => {
+        Self::File {
+          path,
+          attribute: merge_attr(attribute, attr)?,
+        }
+      },
+      Self::Expression {
+        expression,
+        attribute,
+      } => {
+        Self::Expression {
+          expression,
+          attribute: merge_attr(attribute, attr)?,
+        }
+      },
+      Self::Store { .. } if attr.is_some() => {
+        color_eyre::eyre::bail!("--attr cannot be used with a store path")
+      },
+      Self::Store { path } => Self::Store { path },
+    })
+  }
+
   #[must_use]
   pub fn to_args(&self) -> Vec<String> {
     let mut res = Vec::new();
@@ -531,6 +636,46 @@ impl Installable {
       },
     }
   }
+
+  /// Check that the path of a file or store path installable exists, so a
+  /// typo fails here instead of with an opaque error from Nix. Search paths
+  /// like `<nixpkgs>` and URLs are left to Nix.
+  fn validate_local_path(&self) -> color_eyre::Result<()> {
+    match self {
+      Self::File { path, .. } => {
+        let raw = path.to_string_lossy();
+        if raw.starts_with('<') || raw.contains("://") {
+          return Ok(());
+        }
+
+        match fs::File::open(path) {
+          Ok(_) => Ok(()),
+          Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            Err(color_eyre::eyre::eyre!(
+              "file not found: {}",
+              path.display()
+            ))
+          },
+          Err(err) => {
+            Err(color_eyre::eyre::eyre!(
+              "Cannot read {}: {err}",
+              path.display()
+            ))
+          },
+        }
+      },
+      Self::Store { path } => {
+        if !path.starts_with("/nix/store") {
+          color_eyre::eyre::bail!("{} is not in /nix/store", path.display());
+        }
+        if !path.exists() {
+          color_eyre::eyre::bail!("store path not found: {}", path.display());
+        }
+        Ok(())
+      },
+      Self::Flake { .. } | Self::Expression { .. } => Ok(()),
+    }
+  }
 }
 
 #[test]
@@ -590,7 +735,10 @@ where
   res
 }
 
-fn local_flake_reference_path(reference: &str) -> Option<PathBuf> {
+/// The local directory a flake reference points to, if the reference is
+/// unmistakably a filesystem path.
+#[must_use]
+pub fn local_flake_reference_path(reference: &str) -> Option<PathBuf> {
   // Only preflight references that are unmistakably filesystem paths. Bare
   // names like `nixpkgs`, plus URL/registry-style refs, stay in Nix's hands.
   // Parameterized local flake references such as `path:/repo?dir=nix/flakes`
@@ -121,7 +121,7 @@ fn test_resolve_or_default_uses_env_before_default() {
     &format!("{}#myhost", flake_dir.path().display()),
   );
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve_or_default(CommandContext::Os)
     .unwrap();
 
@@ -178,6 +178,57 @@ fn test_resolve_or_default_rejects_missing_absolute_path() {
   assert!(err.contains("NH_FLAKE/NH_OS_FLAKE"));
 }
 
+#[test]
+fn test_resolve_or_default_checks_file_exists() {
+  let dir = tempfile::tempdir().unwrap();
+  let file = dir.path().join("configuration.nix");
+  fs::write(&file, "{}").unwrap();
+
+  let resolved = specified(Installable::File {
+    path:      file,
+    attribute: vec![],
+  })
+  .resolve_or_default(CommandContext::Os);
+  assert!(resolved.is_ok());
+
+  let missing = dir.path().join("configuraton.nix");
+  let err = specified(Installable::File {
+    path:      missing.clone(),
+    attribute: vec![],
+  })
+  .resolve_or_default(CommandContext::Os)
+  .unwrap_err()
+  .to_string();
+  assert_eq!(err, format!("file not found: {}", missing.display()));
+
+  let search_path = specified(Installable::File {
+    path:      PathBuf::from("<nixpkgs/nixos>"),
+    attribute: vec![],
+  })
+  .resolve_or_default(CommandContext::Os);
+  assert!(search_path.is_ok());
+}
+
+#[test]
+fn test_resolve_or_default_checks_store_path() {
+  let dir = tempfile::tempdir().unwrap();
+  let err = specified(Installable::Store {
+    path: dir.path().to_path_buf(),
+  })
+  .resolve_or_default(CommandContext::Os)
+  .unwrap_err()
+  .to_string();
+  assert!(err.contains("is not in /nix/store"));
+
+  let err = specified(Installable::Store {
+    path: PathBuf::from("/nix/store/00000000000000000000000000000000-missing"),
+  })
+  .resolve_or_default(CommandContext::Os)
+  .unwrap_err()
+  .to_string();
+  assert!(err.contains("store path not found"));
+}
+
 #[test]
 fn test_resolve_or_default_rejects_existing_dir_without_flake_nix() {
   let dir = tempfile::tempdir().unwrap();
@@ -276,7 +327,7 @@ fn test_resolve_rejects_empty_nh_flake() {
   let env_guard = EnvGuard::clear();
   env_guard.set("NH_FLAKE", "");
 
-  let err = InstallableArgs::Unspecified
+  let err = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap_err()
     .to_string();
@@ -291,7 +342,7 @@ fn test_resolve_rejects_empty_command_specific_flake() {
   env_guard.set("NH_OS_FLAKE", "");
   env_guard.set("NH_FLAKE", "github:user/repo");
 
-  let err = InstallableArgs::Unspecified
+  let err = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap_err()
     .to_string();
@@ -305,7 +356,7 @@ fn test_resolve_rejects_env_flake_without_reference_before_attribute() {
   let env_guard = EnvGuard::clear();
   env_guard.set("NH_FLAKE", "#fallback");
 
-  let err = InstallableArgs::Unspecified
+  let err = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap_err()
     .to_string();
@@ -320,7 +371,7 @@ fn test_resolve_rejects_malformed_nh_attrp() {
   env_guard.set("NH_FILE", "/path/to/file.nix");
   env_guard.set("NH_ATTRP", r#"foo."bar"#);
 
-  let err = InstallableArgs::Unspecified
+  let err = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap_err()
     .to_string();
@@ -367,12 +418,118 @@ fn test_cli_file_rejects_malformed_attribute() {
   assert!(err.contains("attribute path contains an unclosed quoted attribute"));
 }
 
+fn parse_cli(args: &[&str]) -> Result<InstallableArgs, clap::Error> {
+  let cmd = InstallableArgs::augment_args(clap::Command::new("test"));
+  let matches = cmd.try_get_matches_from(
+    std::iter::once("test").chain(args.iter().copied()),
+  )?;
+  InstallableArgs::from_arg_matches(&matches)
+}
+
+#[test]
+fn test_cli_attr_sets_attribute_of_flake() {
+  let Ok(InstallableArgs::Specified(Installable::Flake {
+    reference,
+    attribute,
+  })) = parse_cli(&[".", "--attr", r#"nixosConfigurations."my.host""#])
+  else {
+    panic!("expected a flake installable");
+  };
+
+  assert_eq!(reference, ".");
+  assert_eq!(attribute, ["nixosConfigurations", "my.host"]);
+}
+
+#[test]
+fn test_cli_attr_merges_with_matching_attribute() {
+  let Ok(InstallableArgs::Specified(Installable::Flake { attribute, .. })) =
+    parse_cli(&[".#foo.bar", "-A", "foo.bar"])
+  else {
+    panic!("expected a flake installable");
+  };
+
+  assert_eq!(attribute, ["foo", "bar"]);
+}
+
+#[test]
+fn test_cli_attr_applies_to_file() {
+  let Ok(InstallableArgs::Specified(Installable::File { attribute, .. })) =
+    parse_cli(&["--file", "default.nix", "--attr", "foo"])
+  else {
+    panic!("expected a file installable");
+  };
+
+  assert_eq!(attribute, ["foo"]);
+}
+
+#[test]
+fn test_cli_attr_rejects_conflicting_attribute() {
+  let err = parse_cli(&[".#foo", "--attr", "bar"])
+    .unwrap_err()
+    .to_string();
+
+  assert!(err.contains("--attr bar conflicts with the attribute path foo"));
+}
+
+#[test]
+fn test_cli_attr_without_installable_is_kept_for_resolution() {
+  let Ok(InstallableArgs::Unspecified { attr }) = parse_cli(&["--attr", "foo"])
+  else {
+    panic!("expected an unspecified installable");
+  };
+
+  assert_eq!(attr.as_deref(), Some("foo"));
+  assert!(parse_cli(&["--attr", r#"foo."bar"#]).is_err());
+}
+
+#[test]
+#[serial]
+fn test_cli_attr_applies_to_env_flake() {
+  let env_guard = EnvGuard::clear();
+  let flake_dir = tempfile::tempdir().unwrap();
+  fs::write(flake_dir.path().join("flake.nix"), "{}").unwrap();
+  env_guard.set("NH_OS_FLAKE", &flake_dir.path().to_string_lossy());
+
+  let resolved = parse_cli(&["--attr", "nixosConfigurations.myhost"])
+    .unwrap()
+    .resolve_or_default(CommandContext::Os)
+    .unwrap();
+
+  let Installable::Flake {
+    reference,
+    attribute,
+  } = resolved
+  else {
+    panic!("Expected Flake, got {resolved:?}");
+  };
+  assert_eq!(reference, flake_dir.path().to_string_lossy());
+  assert_eq!(attribute, ["nixosConfigurations", "myhost"]);
+}
+
+#[test]
+#[serial]
+fn test_cli_attr_conflicts_with_env_flake_attribute() {
+  let env_guard = EnvGuard::clear();
+  env_guard.set("NH_OS_FLAKE", "github:owner/repo#foo");
+
+  let err = parse_cli(&["-A", "bar"])
+    .unwrap()
+    .resolve_or_default(CommandContext::Os)
+    .unwrap_err()
+    .to_string();
+
+  assert!(err.contains("--attr bar conflicts with the attribute path foo"));
+}
+
 #[test]
 #[serial]
 fn test_uses_flakes_checks_cli_and_env_inputs() {
   let env_guard = EnvGuard::clear();
 
-  assert!(!InstallableArgs::Unspecified.uses_flakes(CommandContext::Os));
+  assert!(
+    !InstallableArgs::Unspecified { attr: None }
+      .uses_flakes(CommandContext::Os)
+  );
 
   let file = specified(Installable::File {
     path:      PathBuf::from("/path/to/file.nix"),
@@ -387,9 +544,17 @@ fn test_uses_flakes_checks_cli_and_env_inputs() {
   assert!(flake.uses_flakes(CommandContext::Os));
 
   env_guard.set("NH_FLAKE", "github:user/repo");
-  assert!(InstallableArgs::Unspecified.uses_flakes(CommandContext::Os));
-  assert!(InstallableArgs::Unspecified.uses_flakes(CommandContext::Home));
-  assert!(InstallableArgs::Unspecified.uses_flakes(CommandContext::Darwin));
+  assert!(
+    InstallableArgs::Unspecified { attr: None }.uses_flakes(CommandContext::Os)
+  );
+  assert!(
+    InstallableArgs::Unspecified { attr: None }
+      .uses_flakes(CommandContext::Home)
+  );
+  assert!(
+    InstallableArgs::Unspecified { attr: None }
+      .uses_flakes(CommandContext::Darwin)
+  );
 }
 
 #[test]
@@ -398,9 +563,18 @@ fn test_uses_flakes_checks_context_specific_env() {
   let env_guard = EnvGuard::clear();
   env_guard.set("NH_HOME_FLAKE", "github:user/home");
 
-  assert!(!InstallableArgs::Unspecified.uses_flakes(CommandContext::Os));
-  assert!(InstallableArgs::Unspecified.uses_flakes(CommandContext::Home));
-  assert!(!InstallableArgs::Unspecified.uses_flakes(CommandContext::Darwin));
+  assert!(
+    !InstallableArgs::Unspecified { attr: None }
+      .uses_flakes(CommandContext::Os)
+  );
+  assert!(
+    InstallableArgs::Unspecified { attr: None }
+      .uses_flakes(CommandContext::Home)
+  );
+  assert!(
+    !InstallableArgs::Unspecified { attr: None }
+      .uses_flakes(CommandContext::Darwin)
+  );
 }
 
 #[test]
@@ -416,10 +590,15 @@ fn test_uses_flakes_respects_resolution_precedence() {
   assert!(!file.uses_flakes(CommandContext::Os));
 
   env_guard.set("NH_FILE", "/path/to/file.nix");
-  assert!(!InstallableArgs::Unspecified.uses_flakes(CommandContext::Os));
+  assert!(
+    !InstallableArgs::Unspecified { attr: None }
+      .uses_flakes(CommandContext::Os)
+  );
 
   env_guard.set("NH_OS_FLAKE", "github:user/os");
-  assert!(InstallableArgs::Unspecified.uses_flakes(CommandContext::Os));
+  assert!(
+    InstallableArgs::Unspecified { attr: None }.uses_flakes(CommandContext::Os)
+  );
 }
 
 #[test]
@@ -429,7 +608,10 @@ fn test_uses_flakes_ignores_empty_env_values() {
   env_guard.set("NH_OS_FLAKE", "");
   env_guard.set("NH_FLAKE", "");
 
-  assert!(!InstallableArgs::Unspecified.uses_flakes(CommandContext::Os));
+  assert!(
+    !InstallableArgs::Unspecified { attr: None }
+      .uses_flakes(CommandContext::Os)
+  );
 }
 
 #[test]
@@ -438,7 +620,7 @@ fn test_resolve_os_context_uses_nh_os_flake() {
   let env_guard = EnvGuard::clear();
   env_guard.set("NH_OS_FLAKE", "/etc/nixos#myhost");
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap()
     .unwrap();
@@ -461,7 +643,7 @@ fn test_resolve_os_context_prefers_os_flake_over_generic() {
   env_guard.set("NH_OS_FLAKE", "/etc/nixos#myhost");
   env_guard.set("NH_FLAKE", "/home/user/flake#other");
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap()
     .unwrap();
@@ -483,7 +665,7 @@ fn test_resolve_os_context_falls_back_to_nh_flake() {
   let env_guard = EnvGuard::clear();
   env_guard.set("NH_FLAKE", "/home/user/flake#fallback");
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap()
     .unwrap();
@@ -505,7 +687,7 @@ fn test_resolve_home_context_uses_nh_home_flake() {
   let env_guard = EnvGuard::clear();
   env_guard.set("NH_HOME_FLAKE", "~/.config/home-manager#myuser");
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Home)
     .unwrap()
     .unwrap();
@@ -528,7 +710,7 @@ fn test_resolve_home_context_prefers_home_flake_over_generic() {
   env_guard.set("NH_HOME_FLAKE", "~/.config/home-manager#myuser");
   env_guard.set("NH_FLAKE", "/other/flake#other");
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Home)
     .unwrap()
     .unwrap();
@@ -550,7 +732,7 @@ fn test_resolve_darwin_context_uses_nh_darwin_flake() {
   let env_guard = EnvGuard::clear();
   env_guard.set("NH_DARWIN_FLAKE", "/etc/nix-darwin#macbook");
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Darwin)
     .unwrap()
     .unwrap();
@@ -573,7 +755,7 @@ fn test_resolve_darwin_context_prefers_darwin_flake_over_generic() {
   env_guard.set("NH_DARWIN_FLAKE", "/etc/nix-darwin#macbook");
   env_guard.set("NH_FLAKE", "/other/flake#other");
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Darwin)
     .unwrap()
     .unwrap();
@@ -594,17 +776,17 @@ fn test_resolve_darwin_context_prefers_darwin_flake_over_generic() {
 fn test_resolve_no_env_vars_returns_unspecified() {
   let _env_guard = EnvGuard::clear();
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap();
   assert!(resolved.is_none());
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Home)
     .unwrap();
   assert!(resolved.is_none());
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Darwin)
     .unwrap();
   assert!(resolved.is_none());
@@ -616,7 +798,7 @@ fn test_resolve_with_empty_attribute() {
   let env_guard = EnvGuard::clear();
   env_guard.set("NH_OS_FLAKE", "/etc/nixos");
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap()
     .unwrap();
@@ -641,7 +823,7 @@ fn test_resolve_with_nested_attribute() {
     "~/.config/home-manager#homeConfigurations.user",
   );
 
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Home)
     .unwrap()
     .unwrap();
@@ -664,13 +846,13 @@ fn test_resolve_command_specific_isolation() {
   env_guard.set("NH_HOME_FLAKE", "~/.config/home-manager#user");
 
   // OS context should not pick up NH_HOME_FLAKE
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Os)
     .unwrap();
   assert!(resolved.is_none());
 
   // But Home context should
-  let resolved = InstallableArgs::Unspecified
+  let resolved = InstallableArgs::Unspecified { attr: None }
     .resolve(CommandContext::Home)
     .unwrap()
     .unwrap();
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use chrono::{DateTime, Utc};
 use clap::{Args, Subcommand};
 use nh_core::{
   args::{DiffType, NixBuildPassthroughArgs},
@@ -9,7 +10,10 @@ use nh_core::{
     LegacyFeatures,
     OsReplFeatures,
   },
+  flake_check::FlakeCheckArgs,
+  timings::TimingsFormat,
 };
+use nh_diff::DiffGenerationsArgs;
 use nh_installable::{CommandContext, InstallableArgs};
 use nh_remote::RemoteHost;
 
@@ -47,17 +51,29 @@ impl OsArgs {
         let is_flake = args.uses_flakes();
         Box::new(OsReplFeatures { is_flake })
       },
-      OsSubcommand::Switch(args)
-      | OsSubcommand::Boot(args)
-      | OsSubcommand::Test(args) => {
+      OsSubcommand::Switch(args) => {
+        if !args.rollback && args.activate.rebuild.uses_flakes() {
+          Box::new(FlakeFeatures)
+        } else {
+          Box::new(LegacyFeatures)
+        }
+      },
+      OsSubcommand::Test(args) => {
         if args.rebuild.uses_flakes() {
           Box::new(FlakeFeatures)
         } else {
           Box::new(LegacyFeatures)
         }
       },
+      OsSubcommand::Boot(args) => {
+        if args.activate.rebuild.uses_flakes() {
+          Box::new(FlakeFeatures)
+        } else {
+          Box::new(LegacyFeatures)
+        }
+      },
       OsSubcommand::Build(args) => {
-        if args.uses_flakes() {
+        if args.rebuild.uses_flakes() {
           Box::new(FlakeFeatures)
         } else {
           Box::new(LegacyFeatures)
@@ -70,9 +86,23 @@ impl OsArgs {
           Box::new(LegacyFeatures)
         }
       },
-      OsSubcommand::Info(_) | OsSubcommand::Rollback(_) => {
-        Box::new(LegacyFeatures)
+      OsSubcommand::ListSpecialisations(args) => {
+        if args.installable.uses_flakes(CommandContext::Os) {
+          Box::new(FlakeFeatures)
+        } else {
+          Box::new(LegacyFeatures)
+        }
       },
+      OsSubcommand::Check(args) => {
+        if args.uses_flakes(CommandContext::Os) {
+          Box::new(FlakeFeatures)
+        } else {
+          Box::new(LegacyFeatures)
+        }
+      },
+      OsSubcommand::Info(_)
+      | OsSubcommand::Rollback(_)
+      | OsSubcommand::DiffGenerations(_) => Box::new(LegacyFeatures),
 
       OsSubcommand::BuildImage(args) => {
         if args.common.uses_flakes() {
@@ -88,31 +118,80 @@ impl OsArgs {
 #[derive(Debug, Subcommand)]
 pub enum OsSubcommand {
   /// Build and activate the new configuration, and make it the boot default
-  Switch(OsRebuildActivateArgs),
+  Switch(OsSwitchArgs),
 
   /// Build the new configuration and make it the boot default
-  Boot(OsRebuildActivateArgs),
+  Boot(OsBootArgs),
 
   /// Build and activate the new configuration
   Test(OsRebuildActivateArgs),
 
   /// Build the new configuration
-  Build(OsRebuildArgs),
+  Build(OsBuildArgs),
 
   /// Load system in a repl
   Repl(OsReplArgs),
 
+  /// List the specialisations of the configuration
+  ListSpecialisations(OsListSpecialisationsArgs),
+
   /// List available generations from profile path
   Info(OsGenerationsArgs),
 
   /// Rollback to a previous generation
   Rollback(OsRollbackArgs),
 
+  /// Show what changed between two generations
+  DiffGenerations(DiffGenerationsArgs),
+
   /// Build a `NixOS` VM image
   BuildVm(OsBuildVmArgs),
 
   /// Build a `NixOS` disk-image variant
   BuildImage(OsBuildImageArgs),
+
+  /// Run the checks of the configuration's flake with `nix flake check`
+  Check(FlakeCheckArgs),
+}
+
+/// The arguments of `nh os build` that only apply to a build, rejected with
+/// `--eval-only`.
+const EVAL_ONLY_CONFLICTS: [&str; 24] = [
+  "build_host",
+  "target_host",
+  "update_all",
+  "update_input",
+  "out_link",
+  "keep_result",
+  "no_link",
+  "nom_args",
+  "diff",
+  "no_diff",
+  "expect_path",
+  "ask",
+  "dry",
+  "max_jobs",
+  "no_local_build",
+  "cores",
+  "keep_going",
+  "keep_failed",
+  "fallback",
+  "repair",
+  "builders",
+  "print_build_logs",
+  "no_build_output",
+  "json",
+];
+
+#[derive(Debug, Args)]
+pub struct OsBuildArgs {
+  #[command(flatten)]
+  pub rebuild: OsRebuildArgs,
+
+  /// Only evaluate the derivation of the configuration and print its path,
+  /// without building anything. Fails fast on evaluation errors
+  #[arg(long, conflicts_with_all = EVAL_ONLY_CONFLICTS)]
+  pub eval_only: bool,
 }
 
 #[derive(Debug, Args)]
@@ -161,13 +240,18 @@ pub struct OsRebuildArgs {
   pub specialisation: Option<String>,
 
   /// Ignore specialisations
-  #[arg(long, short = 'S')]
+  #[arg(long, short = 'S', conflicts_with = "specialisation")]
   pub no_specialisation: bool,
 
   /// Install bootloader for switch and boot commands
   #[arg(long)]
   pub install_bootloader: bool,
 
+  /// The profile to set instead of the system profile, from the `--profile`
+  /// of switch and boot
+  #[arg(skip)]
+  pub profile: Option<PathBuf>,
+
   /// Extra arguments passed to nix build
   #[arg(last = true)]
   pub extra_args: Vec<String>,
@@ -187,6 +271,28 @@ pub struct OsRebuildArgs {
   /// Skip pre-activation system validation checks
   #[arg(long, env = "NH_NO_VALIDATE")]
   pub no_validate: bool,
+
+  /// Attribute path of the system to build, relative to the configuration,
+  /// instead of `config.system.build.toplevel`
+  #[arg(long, value_name = "ATTR_PATH", value_parser = parse_attr_path)]
+  pub toplevel_attr: Option<AttrPath>,
+}
+
+/// A parsed attribute path, e.g. `config.system.build.myToplevel`.
+#[derive(Debug, Clone)]
+pub struct AttrPath(pub Vec<String>);
+
+fn parse_attr_path(s: &str) -> Result<AttrPath, String> {
+  match nh_installable::parse_attribute(s) {
+    Ok(path) if path.is_empty() => {
+      Err("attribute path must not be empty".to_owned())
+    },
+    Ok(path) if path.iter().any(String::is_empty) => {
+      Err("attribute path must not have empty segments".to_owned())
+    },
+    Ok(path) => Ok(AttrPath(path)),
+    Err(err) => Err(format!("attribute path {err}")),
+  }
 }
 
 #[derive(Debug, Args)]
@@ -199,6 +305,109 @@ pub struct OsRebuildActivateArgs {
   pub show_activation_logs: bool,
 }
 
+/// The arguments of `nh os switch` that only apply to a rebuild, rejected
+/// with `--rollback`. Arguments that can also be set from the environment are
+/// left out, so that a set variable does not make `--rollback` fail.
+const ROLLBACK_CONFLICTS: [&str; 48] = [
+  "installable",
+  "file",
+  "expr",
+  "attr",
+  "hostname",
+  "toplevel_attr",
+  "target_host",
+  "build_host",
+  "update_all",
+  "update_input",
+  "install_bootloader",
+  "extra_args",
+  "nom_args",
+  "out_link",
+  "keep_result",
+  "no_link",
+  "expect_path",
+  "timings",
+  "show_metadata",
+  "max_jobs",
+  "no_local_build",
+  "cores",
+  "log_format",
+  "keep_going",
+  "keep_failed",
+  "fallback",
+  "repair",
+  "builders",
+  "include",
+  "print_build_logs",
+  "show_trace",
+  "accept_flake_config",
+  "ask_flake_config",
+  "refresh",
+  "offline",
+  "no_net",
+  "recreate_lock_file",
+  "no_update_lock_file",
+  "no_write_lock_file",
+  "no_use_registries",
+  "no_registries",
+  "commit_lock_file",
+  "no_build_output",
+  "use_substitutes",
+  "json",
+  "option",
+  "override_input",
+  "vm_test",
+];
+
+#[derive(Debug, Args)]
+pub struct OsSwitchArgs {
+  #[command(flatten)]
+  pub activate: OsRebuildActivateArgs,
+
+  /// Set this profile instead of the system profile. It has to be in
+  /// /nix/var/nix/profiles/system-profiles, for the bootloader to list its
+  /// generations
+  #[arg(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+  pub profile: Option<PathBuf>,
+
+  /// Instead of building anything, roll back to the previous generation like
+  /// `nh os rollback`
+  #[arg(long, conflicts_with_all = ROLLBACK_CONFLICTS)]
+  pub rollback: bool,
+
+  /// Before activating, build the `vm` variant of the configuration and boot
+  /// it without a display. The switch is aborted unless the VM reaches
+  /// multi-user.target
+  #[arg(long, conflicts_with = "build_host")]
+  pub vm_test: bool,
+
+  /// How long to wait for the --vm-test VM to boot, in seconds
+  #[arg(
+    long,
+    requires = "vm_test",
+    value_name = "SECONDS",
+    default_value_t = 300
+  )]
+  pub vm_test_timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct OsBootArgs {
+  #[command(flatten)]
+  pub activate: OsRebuildActivateArgs,
+
+  /// Set this profile instead of the system profile. It has to be in
+  /// /nix/var/nix/profiles/system-profiles, for the bootloader to list its
+  /// generations
+  #[arg(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+  pub profile: Option<PathBuf>,
+
+  /// Reboot into the new configuration once it is the boot default. This ends
+  /// all running sessions; combine with --ask to confirm first
+  #[arg(long, conflicts_with = "target_host")]
+  pub reboot: bool,
+}
+
 impl OsRebuildArgs {
   #[must_use]
   pub fn uses_flakes(&self) -> bool {
@@ -221,14 +430,24 @@ pub struct OsRollbackArgs {
   pub specialisation: Option<String>,
 
   /// Ignore specialisations
-  #[arg(long, short = 'S')]
+  #[arg(long, short = 'S', conflicts_with = "specialisation")]
   pub no_specialisation: bool,
 
+  /// Roll back this profile instead of the system profile. It has to be in
+  /// /nix/var/nix/profiles/system-profiles, for the bootloader to list its
+  /// generations
+  #[arg(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+  pub profile: Option<PathBuf>,
+
   /// Rollback to a specific generation number (defaults to previous
   /// generation)
   #[arg(long, short)]
   pub to: Option<u64>,
 
+  /// Rollback to the generation the last `nh os switch` switched away from
+  #[arg(long, conflicts_with = "to")]
+  pub last_nh: bool,
+
   /// Don't panic if calling nh as root
   #[arg(short = 'R', long, env = "NH_BYPASS_ROOT_CHECK")]
   pub bypass_root_check: bool,
@@ -252,17 +471,68 @@ pub struct CommonRebuildArgs {
   pub installable: InstallableArgs,
 
   /// Don't use nix-output-monitor for the build process
-  #[arg(long)]
+  #[arg(
+    long,
+    env = "NH_NO_NOM",
+    value_parser = clap::builder::BoolishValueParser::new()
+  )]
   pub no_nom: bool,
 
+  /// Extra arguments for nix-output-monitor, split like a shell would, e.g.
+  /// `--nom-args="--foo bar"`. Ignored when nom is disabled
+  #[arg(long, allow_hyphen_values = true)]
+  pub nom_args: Option<String>,
+
   /// Path to save the result link, defaults to using a temporary directory
   #[arg(long, short)]
   pub out_link: Option<PathBuf>,
 
+  /// Keep the result link in the current directory instead of a temporary
+  /// directory, so it survives a failed activation
+  #[arg(long)]
+  pub keep_result: bool,
+
+  /// Don't create a result link at all, reading the built store path from
+  /// nix instead
+  #[arg(long, conflicts_with_all = ["out_link", "keep_result"])]
+  pub no_link: bool,
+
   /// Whether to display a package diff
-  #[arg(long, short, value_enum, default_value_t = DiffType::Auto)]
+  #[arg(
+    long,
+    short,
+    value_enum,
+    default_value_t = DiffType::Auto,
+    default_value_if("no_diff", "true", Some("never"))
+  )]
   pub diff: DiffType,
 
+  /// Skip the package diff and go straight from build to activation. Same as
+  /// `--diff never`
+  #[arg(long, conflicts_with = "diff")]
+  pub no_diff: bool,
+
+  /// Fail if the build does not produce this store path, e.g. to catch
+  /// unexpected input drift or non-reproducible builds in CI
+  #[arg(long, value_name = "STORE_PATH")]
+  pub expect_path: Option<PathBuf>,
+
+  /// Print how long each phase (update, build, diff, activation) took.
+  /// Use `--timings=json` for a machine-readable report on stdout
+  #[arg(
+    long,
+    value_enum,
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "text"
+  )]
+  pub timings: Option<TimingsFormat>,
+
+  /// Show the resolved URL, locked revision and last-modified date of the
+  /// flake before building
+  #[arg(long)]
+  pub show_metadata: bool,
+
   #[command(flatten)]
   pub passthrough: NixBuildPassthroughArgs,
 }
@@ -285,6 +555,25 @@ impl OsReplArgs {
   }
 }
 
+#[derive(Debug, Args)]
+pub struct OsListSpecialisationsArgs {
+  #[command(flatten)]
+  pub installable: InstallableArgs,
+
+  /// When using a flake installable, select this hostname from
+  /// nixosConfigurations
+  #[arg(long, short = 'H')]
+  pub hostname: Option<String>,
+
+  /// Allow impure evaluation
+  #[arg(
+    long,
+    env = "NH_IMPURE",
+    value_parser = clap::builder::BoolishValueParser::new()
+  )]
+  pub impure: bool,
+}
+
 #[derive(Debug, Args)]
 pub struct OsGenerationsArgs {
   /// Path to Nix' profiles directory
@@ -294,4 +583,146 @@ pub struct OsGenerationsArgs {
   /// Comma-delimited list of field(s) to display
   #[arg(long, value_delimiter = ',')]
   pub fields: Option<Vec<Field>>,
+
+  /// Only list generations built after this point, given as a duration like
+  /// `2weeks` or a date like `2025-01-31`
+  ///
+  /// See the documentation of humantime for possible duration formats: <https://docs.rs/humantime/latest/humantime/fn.parse_duration.html>
+  #[arg(long, value_parser = crate::generations::parse_since)]
+  pub since: Option<DateTime<Utc>>,
+
+  /// Only list the N most recent generations
+  #[arg(long, value_name = "N")]
+  pub limit: Option<usize>,
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used, reason = "Fine in tests")]
+mod tests {
+  use clap::Parser;
+
+  use super::*;
+
+  #[derive(Parser)]
+  struct Cli {
+    #[command(flatten)]
+    os: OsArgs,
+  }
+
+  #[test]
+  fn specialisation_conflicts_with_no_specialisation() {
+    for subcommand in ["switch", "rollback"] {
+      assert!(
+        Cli::try_parse_from([
+          "nh",
+          subcommand,
+          "--specialisation",
+          "gaming",
+          "--no-specialisation",
+        ])
+        .is_err(),
+        "{subcommand}"
+      );
+      assert!(
+        Cli::try_parse_from(["nh", subcommand, "--no-specialisation"]).is_ok(),
+        "{subcommand}"
+      );
+    }
+  }
+
+  #[test]
+  fn attr_path_segments_may_be_quoted_but_not_empty() {
+    let path = |s| parse_attr_path(s).map(|path| path.0);
+
+    assert_eq!(path("config.system.build.toplevel").unwrap(), [
+      "config", "system", "build", "toplevel"
+    ]);
+    assert_eq!(path(r#"config."my.attr".toplevel"#).unwrap(), [
+      "config", "my.attr", "toplevel"
+    ]);
+    assert!(path("").is_err());
+    assert!(path("config..toplevel").is_err());
+    assert!(path(r#"config."".toplevel"#).is_err());
+    assert!(path(r#"config."unterminated"#).is_err());
+  }
+
+  #[test]
+  fn profile_is_only_accepted_by_switch_and_boot() {
+    let profile = "/nix/var/nix/profiles/system-profiles/testing";
+    for subcommand in ["switch", "boot"] {
+      assert!(
+        Cli::try_parse_from(["nh", subcommand, "--profile", profile]).is_ok(),
+        "{subcommand}"
+      );
+    }
+    for subcommand in ["test", "build", "build-vm"] {
+      assert!(
+        Cli::try_parse_from(["nh", subcommand, "--profile", profile]).is_err(),
+        "{subcommand}"
+      );
+    }
+  }
+
+  #[test]
+  fn eval_only_rejects_build_arguments() {
+    assert!(
+      Cli::try_parse_from(["nh", "build", "--eval-only", "--impure"]).is_ok()
+    );
+    for args in [
+      &["--out-link", "result"][..],
+      &["--no-link"],
+      &["--diff", "always"],
+      &["--expect-path", "/nix/store/foo"],
+      &["--dry"],
+      &["--max-jobs", "4"],
+      &["--keep-going"],
+      &["--json"],
+    ] {
+      assert!(
+        Cli::try_parse_from(
+          ["nh", "build", "--eval-only"].iter().chain(args.iter())
+        )
+        .is_err(),
+        "{args:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn switch_rollback_rejects_rebuild_arguments() {
+    let profile = "/nix/var/nix/profiles/system-profiles/testing";
+    assert!(
+      Cli::try_parse_from(["nh", "switch", "--rollback", "--profile", profile])
+        .is_ok()
+    );
+    assert!(
+      Cli::try_parse_from([
+        "nh",
+        "switch",
+        "--rollback",
+        "--specialisation",
+        "gaming"
+      ])
+      .is_ok()
+    );
+    for args in [
+      &["--build-host", "builder"][..],
+      &["--hostname", "other"],
+      &["--update"],
+      &["--update-input", "nixpkgs"],
+      &["--toplevel-attr", "config.system.build.toplevel"],
+      &["--attr", "nixosConfigurations.host"],
+      &["--out-link", "result"],
+      &["--keep-going"],
+      &["--vm-test"],
+    ] {
+      assert!(
+        Cli::try_parse_from(
+          ["nh", "switch", "--rollback"].iter().chain(args.iter())
+        )
+        .is_err(),
+        "{args:?}"
+      );
+    }
+  }
 }
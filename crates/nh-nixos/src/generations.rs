@@ -5,10 +5,10 @@ use std::{
   process,
 };
 
-use chrono::{DateTime, Local, TimeZone, Utc};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
 use clap::ValueEnum;
 use color_eyre::eyre::Result;
-use nh_core::command::{CommandKind, NixCommand};
+use nh_core::command::{CommandKind, NixCommand, log_std_command};
 use tracing::{debug, warn};
 
 #[derive(Debug, Clone)]
@@ -29,6 +29,10 @@ pub struct GenerationInfo {
   /// set in `config.system.configurationRevision`
   pub configuration_revision: Option<String>,
 
+  /// Nixpkgs revision the generation was built from, as embedded in its
+  /// `NixOS` version
+  pub nixpkgs_revision: Option<String>,
+
   /// Specialisations, if any.
   pub specialisations: Option<Vec<String>>,
 
@@ -57,6 +61,10 @@ pub enum Field {
   #[clap(name = "confRev")]
   Confrev,
 
+  /// Nixpkgs Revision
+  #[clap(name = "nixpkgsRev")]
+  Nixrev,
+
   /// Specialisations
   Spec,
 
@@ -64,6 +72,62 @@ pub enum Field {
   Size,
 }
 
+/// Which generations `nh os info` lists.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GenerationFilter {
+  /// Only list generations built after this point
+  pub since: Option<DateTime<Utc>>,
+  /// Only list this many of the most recent generations
+  pub limit: Option<usize>,
+}
+
+impl GenerationFilter {
+  /// Drop the generations that should not be listed from `generations`, which
+  /// must be sorted by generation number. Generations without a readable
+  /// build date are dropped when `since` is set.
+  fn apply(&self, generations: &mut Vec<GenerationInfo>) {
+    if let Some(since) = self.since {
+      generations.retain(|generation| {
+        DateTime::parse_from_rfc3339(&generation.date)
+          .is_ok_and(|date| date > since)
+      });
+    }
+    if let Some(limit) = self.limit {
+      let excess = generations.len().saturating_sub(limit);
+      generations.drain(..excess);
+    }
+  }
+}
+
+/// Parse the value of `--since`: either a duration into the past like
+/// `2weeks`, a date like `2025-01-31` or a timestamp like
+/// `2025-01-31 12:00:00`. Dates and timestamps are in UTC.
+///
+/// # Errors
+///
+/// Returns an error if the value is neither a duration nor a date.
+pub fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+  if let Ok(duration) = humantime::parse_duration(value) {
+    return chrono::Duration::from_std(duration)
+      .ok()
+      .and_then(|duration| Utc::now().checked_sub_signed(duration))
+      .ok_or_else(|| format!("'{value}' reaches too far into the past"));
+  }
+
+  if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+    return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc());
+  }
+
+  humantime::parse_rfc3339_weak(value)
+    .map(DateTime::<Utc>::from)
+    .map_err(|_| {
+      format!(
+        "'{value}' is neither a duration like '2weeks' nor a date like \
+         '2025-01-31'"
+      )
+    })
+}
+
 #[derive(Clone, Copy)]
 struct ColumnWidths {
   id:      usize,
@@ -71,6 +135,7 @@ struct ColumnWidths {
   nver:    usize,
   kernel:  usize,
   confrev: usize,
+  nixrev:  usize,
   spec:    usize,
   size:    usize,
 }
@@ -83,11 +148,13 @@ impl Field {
       Self::Nver => ("NixOS Version", width.nver),
       Self::Kernel => ("Kernel", width.kernel),
       Self::Confrev => ("Configuration Revision", width.confrev),
+      Self::Nixrev => ("Nixpkgs Revision", width.nixrev),
       Self::Spec => ("Specialisations", width.spec),
       Self::Size => ("Closure Size", width.size),
     }
   }
 }
+
 #[must_use]
 pub fn from_dir(generation_dir: &Path) -> Option<u64> {
   generation_dir
@@ -233,6 +300,16 @@ pub fn get_closure_size(generation_dir: &Path) -> String {
   )
 }
 
+/// The nixpkgs revision in a `NixOS` version like `25.05.20250101.abcdef0`.
+/// Versions built from a local nixpkgs checkout, like `25.05pre-git`, have
+/// none.
+fn nixpkgs_revision(nixos_version: &str) -> Option<String> {
+  let version = nixos_version.split_whitespace().next()?;
+  let (_, revision) = version.rsplit_once('.')?;
+  (revision.len() >= 7 && revision.chars().all(|c| c.is_ascii_hexdigit()))
+    .then(|| revision.to_owned())
+}
+
 #[must_use]
 pub fn describe(
   generation_dir: &Path,
@@ -258,6 +335,7 @@ pub fn describe(
 
   let nixos_version = fs::read_to_string(generation_dir.join("nixos-version"))
     .unwrap_or_else(|_| "Unknown".to_string());
+  let nixpkgs_revision = nixpkgs_revision(&nixos_version);
 
   // XXX: Nixpkgs appears to have changed where kernel modules are stored in a
   // recent change. I do not care to track which, but we should try the new path
@@ -306,8 +384,10 @@ pub fn describe(
   let configuration_revision = {
     let nixos_version_path = generation_dir.join("sw/bin/nixos-version");
     if nixos_version_path.exists() {
-      process::Command::new(&nixos_version_path)
-        .arg("--configuration-revision")
+      let mut cmd = process::Command::new(&nixos_version_path);
+      cmd.arg("--configuration-revision");
+      log_std_command(&cmd);
+      cmd
         .output()
         .ok()
         .and_then(|output| String::from_utf8(output.stdout).ok())
@@ -346,6 +426,7 @@ pub fn describe(
       nixos_version,
       kernel_version,
       configuration_revision,
+      nixpkgs_revision,
       specialisations,
       current: false,
       closure_size,
@@ -362,6 +443,7 @@ pub fn describe(
       nixos_version,
       kernel_version,
       configuration_revision,
+      nixpkgs_revision,
       specialisations,
       current: false,
       closure_size,
@@ -376,12 +458,52 @@ pub fn describe(
     nixos_version,
     kernel_version,
     configuration_revision,
+    nixpkgs_revision,
     specialisations,
     current,
     closure_size,
   })
 }
 
+/// The number of the boot default generation of `profile`, if a reboot is
+/// needed to fully activate it.
+///
+/// That is the case when the default was only added to the bootloader, e.g.
+/// by `nh os boot`, and when it was activated but boots a different kernel
+/// or initrd than the running system.
+#[must_use]
+pub fn pending_reboot(profile: &Path) -> Option<u64> {
+  pending_reboot_with(
+    profile,
+    Path::new("/run/booted-system"),
+    Path::new("/run/current-system"),
+  )
+}
+
+fn pending_reboot_with(
+  profile: &Path,
+  booted_system: &Path,
+  current_system: &Path,
+) -> Option<u64> {
+  let resolve = |path: &Path| fs::canonicalize(path).ok();
+
+  let default = resolve(profile)?;
+  let booted = resolve(booted_system)?;
+  if default == booted {
+    return None;
+  }
+
+  let current = resolve(current_system)?;
+  let boot_files_differ = ["kernel", "initrd", "kernel-modules"]
+    .iter()
+    .any(|file| resolve(&booted.join(file)) != resolve(&default.join(file)));
+  if default == current && !boot_files_differ {
+    return None;
+  }
+
+  from_dir(&fs::read_link(profile).ok()?)
+}
+
 /// Print information about the given generations.
 ///
 /// # Errors
@@ -391,6 +513,7 @@ pub fn describe(
 pub fn print_info(
   mut generations: Vec<GenerationInfo>,
   fields: Option<&[Field]>,
+  filter: GenerationFilter,
 ) -> Result<()> {
   // Parse all dates at once and cache them
   let mut parsed_dates = HashMap::with_capacity(generations.len());
@@ -427,22 +550,26 @@ pub fn print_info(
     println!("NixOS {fallback_version} (profile may need sync)");
   }
 
+  filter.apply(&mut generations);
+
   // Conditionally hide columns if they are empty for all generations
   let has_confrev = generations
     .iter()
     .any(|g| g.configuration_revision.is_some());
+  let has_nixrev = generations.iter().any(|g| g.nixpkgs_revision.is_some());
   let has_spec = generations.iter().any(|g| g.specialisations.is_some());
 
   let visible_fields: Vec<Field> = fields.map_or_else(
     || {
-      use Field::{Confrev, Date, Id, Kernel, Nver, Size, Spec};
-      let all_fields = [Id, Date, Nver, Kernel, Confrev, Spec, Size];
+      use Field::{Confrev, Date, Id, Kernel, Nixrev, Nver, Size, Spec};
+      let all_fields = [Id, Date, Nver, Kernel, Confrev, Nixrev, Spec, Size];
 
       all_fields
         .into_iter()
         .filter(|f| {
           match f {
             Confrev => has_confrev,
+            Nixrev => has_nixrev,
             Spec => has_spec,
             _ => true,
           }
@@ -477,6 +604,7 @@ pub fn print_info(
     nver:    max_nixos_version_len,
     kernel:  max_kernel_len,
     confrev: 22, // "Configuration Revision"
+    nixrev:  16, // "Nixpkgs Revision"
     spec:    15, // "Specialisations"
     size:    12, // "Closure Size"
   };
@@ -525,7 +653,13 @@ pub fn print_info(
             generation
               .configuration_revision
               .clone()
-              .unwrap_or_default()
+              .unwrap_or_else(|| "Unknown".to_string())
+          },
+          Field::Nixrev => {
+            generation
+              .nixpkgs_revision
+              .clone()
+              .unwrap_or_else(|| "Unknown".to_string())
           },
           Field::Spec => specialisations.clone().unwrap_or_default(),
           Field::Size => generation.closure_size.clone(),
@@ -539,3 +673,102 @@ pub fn print_info(
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  #![allow(clippy::unwrap_used, reason = "Fine in tests")]
+
+  use super::*;
+
+  fn generation(number: u64, date: &str) -> GenerationInfo {
+    GenerationInfo {
+      number,
+      date: date.to_owned(),
+      nixos_version: "25.05".to_owned(),
+      kernel_version: "6.12.0".to_owned(),
+      configuration_revision: None,
+      nixpkgs_revision: None,
+      specialisations: None,
+      current: false,
+      closure_size: "Unknown".to_owned(),
+    }
+  }
+
+  #[test]
+  fn filter_keeps_recent_generations() {
+    let mut generations = vec![
+      generation(1, "2024-01-01T00:00:00+00:00"),
+      generation(2, "2025-01-01T00:00:00+00:00"),
+      generation(3, "2025-02-01T00:00:00+00:00"),
+      generation(4, "2025-03-01T00:00:00+00:00"),
+    ];
+    let filter = GenerationFilter {
+      since: Some(parse_since("2024-06-01").unwrap()),
+      limit: Some(2),
+    };
+
+    filter.apply(&mut generations);
+
+    let numbers: Vec<_> = generations.iter().map(|g| g.number).collect();
+    assert_eq!(numbers, [3, 4]);
+  }
+
+  #[test]
+  fn nixpkgs_revision_is_read_from_version() {
+    assert_eq!(
+      nixpkgs_revision("25.05.20250101.abcdef0 (Warbler)\n").as_deref(),
+      Some("abcdef0")
+    );
+    assert_eq!(nixpkgs_revision("25.05pre-git"), None);
+    assert_eq!(nixpkgs_revision("Unknown"), None);
+  }
+
+  #[test]
+  fn since_accepts_durations_and_dates() {
+    let week_ago = parse_since("1week").unwrap();
+    assert!(week_ago < Utc::now() - chrono::Duration::days(6));
+    assert_eq!(
+      parse_since("2025-01-31 12:00:00").unwrap().to_rfc3339(),
+      "2025-01-31T12:00:00+00:00"
+    );
+    assert!(parse_since("last tuesday").is_err());
+  }
+
+  #[test]
+  fn pending_reboot_is_reported_for_boot_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let link = |target: &str, name: &str| {
+      let _ = fs::remove_file(dir.path().join(name));
+      std::os::unix::fs::symlink(
+        dir.path().join(target),
+        dir.path().join(name),
+      )
+      .unwrap();
+    };
+    for (system, kernel) in [("a", "6.1"), ("b", "6.1"), ("c", "6.6")] {
+      fs::create_dir_all(dir.path().join(kernel)).unwrap();
+      fs::create_dir(dir.path().join(system)).unwrap();
+      link(kernel, &format!("{system}/kernel"));
+    }
+    let pending = |default: &str, booted: &str, current: &str| {
+      link(default, "system-7-link");
+      link("system-7-link", "system");
+      link(booted, "booted");
+      link(current, "current");
+      pending_reboot_with(
+        &dir.path().join("system"),
+        &dir.path().join("booted"),
+        &dir.path().join("current"),
+      )
+    };
+
+    // Booted into the default generation
+    assert_eq!(pending("a", "a", "a"), None);
+    // Switched to a generation with the same kernel
+    assert_eq!(pending("b", "a", "b"), None);
+    // Switched to a generation with a different kernel
+    assert_eq!(pending("c", "a", "c"), Some(7));
+    // Only added to the bootloader, like nh os boot
+    assert_eq!(pending("b", "a", "a"), Some(7));
+  }
+}
@@ -0,0 +1,82 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{Context, Result, bail, eyre};
+use nh_core::util::state_dir;
+
+/// Holds the store path of the generation `nh os switch` last switched away
+/// from, so `nh os rollback --last-nh` can return to it even when the profile
+/// has changed in between. Generation numbers are not stored, as they no
+/// longer point to the same system once `nh clean` removed the generation.
+const STATE_FILE: &str = "last-os-switch";
+
+/// Remember that nh switched away from the system at `store_path`.
+///
+/// # Errors
+///
+/// Returns an error if the state directory cannot be determined or written.
+pub fn record(store_path: &Path) -> Result<()> {
+  let dir = state_dir().ok_or_else(|| {
+    eyre!("Neither XDG_STATE_HOME nor HOME is set, cannot record the switch")
+  })?;
+  record_in(&dir, store_path)
+}
+
+/// The store path of the system nh last switched away from, if any was
+/// recorded.
+///
+/// # Errors
+///
+/// Returns an error if the state file exists but cannot be read or parsed.
+pub fn read() -> Result<Option<PathBuf>> {
+  state_dir().map_or(Ok(None), |dir| read_from(&dir))
+}
+
+fn record_in(dir: &Path, store_path: &Path) -> Result<()> {
+  fs::create_dir_all(dir)
+    .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+  let path = dir.join(STATE_FILE);
+  fs::write(&path, format!("{}\n", store_path.display()))
+    .wrap_err_with(|| format!("Failed to write {}", path.display()))
+}
+
+fn read_from(dir: &Path) -> Result<Option<PathBuf>> {
+  let path = dir.join(STATE_FILE);
+  if !path.exists() {
+    return Ok(None);
+  }
+  let contents = fs::read_to_string(&path)
+    .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+  let store_path = PathBuf::from(contents.trim());
+  if !store_path.is_absolute() {
+    bail!("Invalid store path in {}", path.display());
+  }
+  Ok(Some(store_path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn recorded_store_path_round_trips() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let state = dir.path().join("nh");
+    assert_eq!(read_from(&state)?, None);
+
+    let store_path = Path::new("/nix/store/abc-nixos-system-laptop");
+    record_in(&state, store_path)?;
+    assert_eq!(read_from(&state)?.as_deref(), Some(store_path));
+    Ok(())
+  }
+
+  #[test]
+  fn generation_numbers_are_rejected() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join(STATE_FILE), "42\n")?;
+    assert!(read_from(dir.path()).is_err());
+    Ok(())
+  }
+}
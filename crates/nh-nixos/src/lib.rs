@@ -1,3 +1,5 @@
 pub mod args;
 pub mod generations;
+pub mod last_switch;
 pub mod nixos;
+pub mod vm_test;
@@ -1,22 +1,41 @@
 use std::{
-  convert::Into,
   fs,
   path::{Path, PathBuf},
+  time::Duration,
 };
 
 use color_eyre::eyre::{Context, Result, bail, eyre};
 use nh_core::{
-  args::DiffType,
-  command::{self, Command, CommandKind, ElevationStrategy, NixCommand},
+  args::{DiffType, split_nom_args},
+  command::{
+    self,
+    Command,
+    CommandKind,
+    ElevationStrategy,
+    EvalError,
+    NixCommand,
+  },
+  confirm::confirm,
+  exit::{self, Aborted, Phase},
+  flake_metadata::show_flake_metadata,
+  outcome,
+  progress,
+  summary::RebuildSummary,
+  timings::Timings,
   update::update,
   util::{
+    available_specialisations,
+    build_failure_context,
+    check_expected_path,
     ensure_ssh_key_login,
+    get_attr_names,
     get_build_image_variants,
     get_build_image_variants_flake,
     get_hostname,
+    result_tempdir,
   },
 };
-use nh_diff::{handle_nixos_diff, print_dix_diff};
+use nh_diff::{generation_link, handle_nixos_diff, print_dix_diff};
 use nh_installable::{CommandContext, Installable};
 use nh_remote::{self, RemoteBuildConfig, RemoteHost};
 use tracing::{debug, info, warn};
@@ -24,20 +43,29 @@ use tracing::{debug, info, warn};
 use crate::{
   args::{
     self,
+    OsBootArgs,
+    OsBuildArgs,
     OsBuildImageArgs,
     OsBuildVmArgs,
     OsGenerationsArgs,
+    OsListSpecialisationsArgs,
     OsRebuildActivateArgs,
     OsRebuildArgs,
     OsReplArgs,
     OsRollbackArgs,
     OsSubcommand::{self},
+    OsSwitchArgs,
   },
   generations,
+  last_switch,
+  vm_test,
 };
 
 const SYSTEM_PROFILE: &str = "/nix/var/nix/profiles/system";
 const CURRENT_PROFILE: &str = "/run/current-system";
+/// Profiles in this directory are listed in the bootloader next to the system
+/// profile.
+const SYSTEM_PROFILES_DIR: &str = "/nix/var/nix/profiles/system-profiles";
 
 const SPEC_LOCATION: &str = "/etc/specialisation";
 
@@ -72,28 +100,24 @@ impl args::OsArgs {
   /// - Nix evaluation or building fails
   /// - File system operations fail
   pub fn run(self, elevation: ElevationStrategy) -> Result<()> {
-    use OsRebuildVariant::{Boot, Build, Switch, Test};
+    use OsRebuildVariant::Test;
     match self.subcommand {
-      OsSubcommand::Boot(args) => {
-        args.rebuild_and_activate(&Boot, None, elevation)
-      },
+      OsSubcommand::Boot(args) => args.run(elevation),
       OsSubcommand::Test(args) => {
-        args.rebuild_and_activate(&Test, None, elevation)
-      },
-      OsSubcommand::Switch(args) => {
-        args.rebuild_and_activate(&Switch, None, elevation)
-      },
-      OsSubcommand::Build(args) => {
-        if args.common.ask || args.common.dry {
-          warn!("`--ask` and `--dry` have no effect for `nh os build`");
-        }
-        args.build_only(&Build, None, &elevation)
+        args.rebuild_and_activate(&Test, None, None, elevation)
       },
+      OsSubcommand::Switch(args) => args.run(elevation),
+      OsSubcommand::Build(args) => args.run(&elevation),
       OsSubcommand::BuildVm(args) => args.build_vm(&elevation),
       OsSubcommand::Repl(args) => args.run(),
+      OsSubcommand::ListSpecialisations(args) => args.run(),
       OsSubcommand::Info(args) => args.info(),
       OsSubcommand::Rollback(args) => args.rollback(elevation),
+      OsSubcommand::DiffGenerations(args) => {
+        args.run(Path::new(SYSTEM_PROFILE))
+      },
       OsSubcommand::BuildImage(args) => args.build_image(&elevation),
+      OsSubcommand::Check(args) => args.run(CommandContext::Os),
     }
   }
 }
@@ -108,6 +132,35 @@ enum OsRebuildVariant {
   BuildIso,
 }
 
+impl OsRebuildVariant {
+  /// The `nh` subcommand this variant corresponds to.
+  const fn command(&self) -> &'static str {
+    match self {
+      Self::Build => "os build",
+      Self::Switch => "os switch",
+      Self::Boot => "os boot",
+      Self::Test => "os test",
+      Self::BuildVm => "os build-vm",
+      Self::BuildIso => "os build-image",
+    }
+  }
+}
+
+impl OsBuildArgs {
+  fn run(self, elevation: &ElevationStrategy) -> Result<()> {
+    if self.rebuild.common.ask || self.rebuild.common.dry {
+      warn!("`--ask` and `--dry` have no effect for `nh os build`");
+    }
+    if self.eval_only {
+      return self.rebuild.eval_only(elevation);
+    }
+    self
+      .rebuild
+      .build_only(&OsRebuildVariant::Build, None, elevation)
+      .map(drop)
+  }
+}
+
 impl OsBuildVmArgs {
   fn build_vm(self, elevation: &ElevationStrategy) -> Result<()> {
     let attr = if self.with_bootloader {
@@ -115,12 +168,6 @@ impl OsBuildVmArgs {
     } else {
       "vm"
     };
-    let out_path = self
-      .common
-      .common
-      .out_link
-      .clone()
-      .unwrap_or_else(|| PathBuf::from("result"));
 
     debug!("Building VM with attribute: {}", attr);
 
@@ -133,7 +180,7 @@ impl OsBuildVmArgs {
       );
     }
 
-    self.common.build_only(
+    let out_path = self.common.build_only(
       &OsRebuildVariant::BuildVm,
       Some(&[attr]),
       elevation,
@@ -150,32 +197,60 @@ impl OsBuildVmArgs {
 
 impl OsRebuildActivateArgs {
   // final_attr is the attribute of config.system.build.X to evaluate.
+  // vm_test is the boot timeout of a VM test to run before activating.
   fn rebuild_and_activate(
-    self,
+    mut self,
     variant: &OsRebuildVariant,
     final_attrs: Option<&[&str]>,
+    vm_test: Option<Duration>,
     elevation: ElevationStrategy,
   ) -> Result<()> {
     use OsRebuildVariant::{Build, BuildVm};
 
+    self.rebuild.normalize_local_target_host();
+
+    if self.rebuild.common.dry {
+      progress::set_spinners_enabled(false);
+    }
+
+    let mut timings = Timings::new(self.rebuild.common.timings);
+
+    exit::enter(Phase::Precheck);
+    if matches!(variant, OsRebuildVariant::Switch | OsRebuildVariant::Boot) {
+      check_boot_profile(self.rebuild.profile.as_deref())?;
+    }
     let (local_elevate, target_hostname) =
       self.rebuild.setup_build_context(&elevation)?;
+    exit::enter(Phase::Build);
 
-    let (out_path, _tempdir_guard) =
+    let (mut out_path, _tempdir_guard) =
       self.rebuild.determine_output_path(variant)?;
 
     let toplevel = self
       .rebuild
       .resolve_installable_and_toplevel(&target_hostname, final_attrs)?;
+    self
+      .rebuild
+      .common
+      .passthrough
+      .confirm_flake_config(&toplevel)?;
+    if self.rebuild.common.show_metadata {
+      show_flake_metadata(&toplevel)?;
+    }
+    self
+      .rebuild
+      .print_summary(variant, &toplevel, &target_hostname);
 
     if self.rebuild.update_args.update_all
       || self.rebuild.update_args.update_input.is_some()
     {
-      update(
-        &toplevel,
-        self.rebuild.update_args.update_input.clone(),
-        self.rebuild.common.passthrough.commit_lock_file,
-      )?;
+      timings.time("update", || {
+        update(
+          &toplevel,
+          self.rebuild.update_args.update_input.clone(),
+          self.rebuild.common.passthrough.commit_lock_file,
+        )
+      })?;
     }
 
     let message = match variant {
@@ -216,19 +291,33 @@ impl OsRebuildActivateArgs {
       local_elevate
     };
 
-    let actual_store_path =
-      self.rebuild.execute_build(toplevel, &out_path, message)?;
+    let attr = toplevel.clone();
+    let actual_store_path = timings.time("build", || {
+      self
+        .rebuild
+        .execute_build(toplevel, &out_path, message)
+        .map_err(|err| build_failure_context(err, &attr, &target_hostname))
+    })?;
+
+    if let Some(store_path) = actual_store_path.as_ref()
+      && self.rebuild.common.no_link
+    {
+      out_path.clone_from(store_path);
+    }
 
+    outcome::record_built(actual_store_path.as_deref().unwrap_or(&out_path));
     let target_profile =
       self.rebuild.resolve_specialisation_and_profile(&out_path)?;
 
-    handle_nixos_diff(
-      &self.rebuild.common.diff,
-      self.rebuild.target_host.as_ref(),
-      &target_profile,
-      actual_store_path.as_deref(),
-      &out_path,
-    )?;
+    timings.time("diff", || {
+      handle_nixos_diff(
+        &self.rebuild.common.diff,
+        self.rebuild.target_host.as_ref(),
+        &target_profile,
+        actual_store_path.as_deref(),
+        &out_path,
+      )
+    })?;
 
     if self.rebuild.common.dry || matches!(variant, Build | BuildVm) {
       if self.rebuild.common.ask {
@@ -243,18 +332,83 @@ impl OsRebuildActivateArgs {
       return Ok(());
     }
 
-    self.activate_rebuilt_config(
-      variant,
-      &out_path,
-      &target_profile,
-      actual_store_path.as_deref(),
-      elevate,
-      elevation,
-    )?;
+    if let Some(timeout) = vm_test {
+      timings.time("vm test", || {
+        self.rebuild.vm_test(&target_hostname, timeout)
+      })?;
+    }
+
+    // Remember what a local switch replaces, for `nh os rollback --last-nh`
+    let local_switch = matches!(variant, OsRebuildVariant::Switch)
+      && self.rebuild.target_host.is_none()
+      && self.rebuild.profile.is_none();
+    let system_profile = Path::new(SYSTEM_PROFILE);
+    let switched_from = local_switch
+      .then(|| list_generations(system_profile))
+      .and_then(Result::ok)
+      .and_then(|generations| generations.into_iter().find(|g| g.current))
+      .and_then(|generation| {
+        let link =
+          profile_generation_link(system_profile, generation.number).ok()?;
+        fs::canonicalize(link).ok()
+      });
+
+    exit::enter(Phase::Activation);
+    timings.time("activation", || {
+      self.activate_rebuilt_config(
+        variant,
+        &out_path,
+        &target_profile,
+        actual_store_path.as_deref(),
+        elevate,
+        elevation,
+      )
+    })?;
+    outcome::record_activated();
+    self.rebuild.common.passthrough.warn_unpersisted_overrides();
+
+    if let Some(store_path) = switched_from
+      && let Err(err) = last_switch::record(&store_path)
+    {
+      warn!(
+        "Failed to record {} for rollback: {err}",
+        store_path.display()
+      );
+    }
 
     Ok(())
   }
 
+  /// Run `switch-to-configuration dry-activate` so the user can see which
+  /// units would be started, stopped, restarted or reloaded before confirming.
+  ///
+  /// Failures are only logged, as this is purely informational.
+  fn show_planned_service_actions(
+    &self,
+    target_profile: &Path,
+    elevate: bool,
+    elevation: &ElevationStrategy,
+  ) {
+    if self.rebuild.target_host.is_some() {
+      debug!("Not showing planned service actions for a remote target host");
+      return;
+    }
+
+    let switch_to_configuration =
+      target_profile.join("bin").join("switch-to-configuration");
+    if let Err(err) = Command::new(switch_to_configuration)
+      .arg("dry-activate")
+      .message("Checking which services would change")
+      .elevate(elevate.then(|| elevation.clone()))
+      .preserve_envs(["NIXOS_NO_CHECK"])
+      .with_required_env()
+      .show_output(true)
+      .run()
+    {
+      warn!("Could not determine planned service actions: {err}");
+    }
+  }
+
   fn activate_rebuilt_config(
     &self,
     variant: &OsRebuildVariant,
@@ -267,12 +421,14 @@ impl OsRebuildActivateArgs {
     use OsRebuildVariant::{Boot, Switch, Test};
 
     if self.rebuild.common.ask {
-      let confirmation = inquire::Confirm::new("Apply the config?")
-        .with_default(false)
-        .prompt()?;
+      if matches!(variant, Switch | Test) {
+        self.show_planned_service_actions(target_profile, elevate, &elevation);
+      }
+
+      let confirmation = confirm("Apply the config?").prompt()?;
 
       if !confirmation {
-        bail!("User rejected the new config");
+        bail!(Aborted("User rejected the new config"));
       }
     }
 
@@ -290,22 +446,26 @@ impl OsRebuildActivateArgs {
     }
 
     // Validate system closure before activation, unless bypassed. For remote
-    // builds, use the actual store path returned from the build. For local
-    // builds, canonicalize the target_profile.
+    // targets, use the actual store path returned from the build. For local
+    // activation, canonicalize the target_profile.
     let is_remote_build = self.rebuild.target_host.is_some();
-    let resolved_profile: PathBuf = if let Some(store_path) = actual_store_path
-    {
-      // Remote build - use the actual store path from the build output
-      store_path.to_path_buf()
-    } else if is_remote_build && !out_path.exists() {
+    let resolved_profile: PathBuf = match actual_store_path {
+      // Remote target - use the actual store path from the build output
+      Some(store_path) if is_remote_build => store_path.to_path_buf(),
+
       // Remote build with no local result and no store path captured
       // (shouldn't happen, but fallback)
-      target_profile.to_path_buf()
-    } else {
-      // Local build - canonicalize the symlink to get the store path
-      target_profile
-        .canonicalize()
-        .context("Failed to resolve output path to actual store path")?
+      _ if is_remote_build && !out_path.exists() => {
+        target_profile.to_path_buf()
+      },
+
+      // Local activation, including results built on --build-host and copied
+      // back. Canonicalize the symlink so the selected specialisation is kept.
+      _ => {
+        target_profile
+          .canonicalize()
+          .context("Failed to resolve output path to actual store path")?
+      },
     };
 
     let should_skip = self.rebuild.no_validate;
@@ -370,6 +530,7 @@ impl OsRebuildActivateArgs {
             install_bootloader: false,
             show_logs: self.show_activation_logs,
             elevation: elevate.then_some(elevation.clone()),
+            profile: None,
           },
         )
         .wrap_err(format!(
@@ -411,6 +572,7 @@ impl OsRebuildActivateArgs {
             install_bootloader: self.rebuild.install_bootloader,
             show_logs:          false,
             elevation:          elevate.then_some(elevation),
+            profile:            self.rebuild.profile.clone(),
           },
         )
         .wrap_err("Bootloader activation failed")?;
@@ -418,12 +580,11 @@ impl OsRebuildActivateArgs {
         // Use the base system closure instead of the specialisation one.
         // This is what makes all specialisations visible in the bootloader
         // instead of only the generation with the specialisation.
-        let base_store_path = out_path
-          .canonicalize()
-          .context("Failed to resolve base output path to store path")?;
+        let base_store_path = built_store_path(out_path, actual_store_path)?;
 
         Command::new("nix")
-          .args(["build", "--no-link", "--profile", SYSTEM_PROFILE])
+          .args(["build", "--no-link", "--profile"])
+          .arg(self.rebuild.system_profile())
           .arg(&base_store_path)
           .elevate(elevate.then_some(elevation.clone()))
           .with_required_env()
@@ -458,7 +619,107 @@ impl OsRebuildActivateArgs {
   }
 }
 
+impl OsSwitchArgs {
+  fn run(mut self, elevation: ElevationStrategy) -> Result<()> {
+    if !self.rollback {
+      self.activate.rebuild.profile = self.profile;
+      let vm_test = self
+        .vm_test
+        .then(|| Duration::from_secs(self.vm_test_timeout));
+      return self.activate.rebuild_and_activate(
+        &OsRebuildVariant::Switch,
+        None,
+        vm_test,
+        elevation,
+      );
+    }
+
+    self.into_rollback().rollback(elevation)
+  }
+
+  /// The `nh os rollback` that `--rollback` stands for.
+  fn into_rollback(self) -> OsRollbackArgs {
+    let rebuild = self.activate.rebuild;
+    OsRollbackArgs {
+      dry:               rebuild.common.dry,
+      ask:               rebuild.common.ask,
+      specialisation:    rebuild.specialisation,
+      no_specialisation: rebuild.no_specialisation,
+      profile:           self.profile,
+      to:                None,
+      last_nh:           false,
+      bypass_root_check: rebuild.bypass_root_check,
+      diff:              rebuild.common.diff,
+    }
+  }
+}
+
+impl OsBootArgs {
+  fn run(self, elevation: ElevationStrategy) -> Result<()> {
+    let Self {
+      mut activate,
+      profile,
+      reboot,
+    } = self;
+    activate.rebuild.profile = profile;
+    let ask = activate.rebuild.common.ask;
+    let dry = activate.rebuild.common.dry;
+    let bypass_root_check = activate.rebuild.bypass_root_check;
+
+    activate.rebuild_and_activate(
+      &OsRebuildVariant::Boot,
+      None,
+      None,
+      elevation.clone(),
+    )?;
+
+    if !reboot {
+      return Ok(());
+    }
+
+    if dry {
+      info!("Dry run, not rebooting");
+      return Ok(());
+    }
+
+    warn!("Rebooting will end all running sessions and unsaved work");
+    if ask {
+      let confirmation = inquire::Confirm::new("Reboot now?")
+        .with_default(false)
+        .prompt()?;
+
+      if !confirmation {
+        bail!(Aborted("User rejected the reboot"));
+      }
+    }
+
+    let elevate = has_elevation_status(bypass_root_check, &elevation)?;
+    Command::new("systemctl")
+      .arg("reboot")
+      .message("Rebooting into the new configuration")
+      .elevate(elevate.then_some(elevation))
+      .run()
+      .wrap_err("Failed to reboot")
+  }
+}
+
 impl OsRebuildArgs {
+  /// Drops a `--target-host` that points at the local machine.
+  ///
+  /// `--build-host builder --target-host localhost` is equivalent to passing
+  /// only `--build-host`: the result is copied back to the local store and
+  /// activated here, without going through SSH to ourselves.
+  fn normalize_local_target_host(&mut self) {
+    if self
+      .target_host
+      .as_ref()
+      .is_some_and(RemoteHost::is_localhost)
+    {
+      debug!("Target host is localhost, activating locally");
+      self.target_host = None;
+    }
+  }
+
   /// Performs initial setup and gathers context for an OS rebuild operation.
   ///
   /// This includes:
@@ -482,6 +743,12 @@ impl OsRebuildArgs {
     &self,
     elevation: &ElevationStrategy,
   ) -> Result<(bool, String)> {
+    self.common.passthrough.validate()?;
+    self
+      .common
+      .passthrough
+      .warn_no_local_build(self.build_host.is_some());
+
     // Only check SSH key login if remote hosts are involved
     if self.build_host.is_some() || self.target_host.is_some() {
       ensure_ssh_key_login()?;
@@ -528,18 +795,50 @@ impl OsRebuildArgs {
     Ok(uid != 0)
   }
 
+  /// The profile switch and boot set, `--profile` or the system profile.
+  fn system_profile(&self) -> &Path {
+    self
+      .profile
+      .as_deref()
+      .unwrap_or_else(|| Path::new(SYSTEM_PROFILE))
+  }
+
+  fn print_summary(
+    &self,
+    variant: &OsRebuildVariant,
+    toplevel: &Installable,
+    target_hostname: &str,
+  ) {
+    RebuildSummary {
+      command:     variant.command(),
+      installable: toplevel,
+      hostname:    Some(target_hostname),
+      update:      &self.update_args,
+      build_host:  self.build_host.as_ref().map(ToString::to_string),
+      target_host: self.target_host.as_ref().map(ToString::to_string),
+      diff:        &self.common.diff,
+    }
+    .print();
+  }
+
   fn determine_output_path(
     &self,
     variant: &OsRebuildVariant,
   ) -> Result<(PathBuf, Option<tempfile::TempDir>)> {
     use OsRebuildVariant::{Build, BuildIso, BuildVm};
+    if self.common.no_link {
+      // Replaced by the store path the build reports
+      return Ok((PathBuf::new(), None));
+    }
     if let Some(p) = self.common.out_link.clone() {
       Ok((p, None))
     } else {
-      let (path, guard) = if matches!(variant, BuildVm | BuildIso | Build) {
+      let (path, guard) = if self.common.keep_result
+        || matches!(variant, BuildVm | BuildIso | Build)
+      {
         (PathBuf::from("result"), None)
       } else {
-        let dir = tempfile::Builder::new().prefix("nh-os").tempdir()?;
+        let dir = result_tempdir("nh-os")?;
         (dir.as_ref().join("result"), Some(dir))
       };
       Ok((path, guard))
@@ -557,6 +856,15 @@ impl OsRebuildArgs {
       .clone()
       .resolve_or_default(CommandContext::Os)?;
 
+    if let Some(toplevel_attr) = &self.toplevel_attr {
+      if final_attrs.is_none() {
+        let attrs: Vec<&str> =
+          toplevel_attr.0.iter().map(String::as_str).collect();
+        return configuration_attr_for(target_hostname, installable, &attrs);
+      }
+      warn!("--toplevel-attr has no effect for this command");
+    }
+
     toplevel_for(
       target_hostname,
       installable,
@@ -570,6 +878,8 @@ impl OsRebuildArgs {
     out_path: &Path,
     message: &str,
   ) -> Result<Option<PathBuf>> {
+    let nom_args = split_nom_args(self.common.nom_args.as_deref())?;
+
     // If a build host is specified, use proper remote build semantics:
     //
     // 1. Evaluate derivation locally
@@ -582,40 +892,105 @@ impl OsRebuildArgs {
         build_host,
         target_host: self.target_host.clone(),
         use_nom: !self.common.no_nom,
+        nom_args,
         use_substitutes: self.common.passthrough.use_substitutes,
-        extra_args: self
-          .extra_args
-          .iter()
-          .map(Into::into)
-          .chain(
-            self
-              .common
-              .passthrough
-              .generate_passthrough_args()
-              .into_iter()
-              .map(Into::into),
-          )
-          .collect(),
+        extra_args: self.common.passthrough.remote_build_args(&self.extra_args),
       };
 
+      let out_link = (!self.common.no_link).then_some(out_path);
       let actual_store_path =
-        nh_remote::build_remote(&toplevel, &config, Some(out_path))?;
+        nh_remote::build_remote(&toplevel, &config, out_link)?;
 
+      self.check_expected_path(&actual_store_path)?;
       Ok(Some(actual_store_path))
     } else {
-      // Local build - use the existing path
-      command::Build::new(toplevel)
-        .extra_arg("--out-link")
-        .extra_arg(out_path)
+      let out_link = (!self.common.no_link).then_some(out_path);
+      let store_path = command::Build::new(toplevel)
         .extra_args(&self.extra_args)
         .passthrough(&self.common.passthrough)
         .message(message)
         .nom(!self.common.no_nom)
-        .run()
+        .nom_args(nom_args)
+        .run_out_path(out_link)
         .wrap_err("Failed to build configuration")?;
 
-      Ok(None) // Local builds don't have separate store path
+      self.check_expected_path(&store_path)?;
+      Ok(Some(store_path))
+    }
+  }
+
+  /// Evaluate the derivation path of the configuration without building it,
+  /// for `nh os build --eval-only`. The path is printed to stdout.
+  fn eval_only(mut self, elevation: &ElevationStrategy) -> Result<()> {
+    self.normalize_local_target_host();
+    exit::enter(Phase::Precheck);
+    // This also validates the passthrough arguments
+    let (_, target_hostname) = self.setup_build_context(elevation)?;
+    exit::enter(Phase::Build);
+
+    let toplevel =
+      self.resolve_installable_and_toplevel(&target_hostname, None)?;
+    self.common.passthrough.confirm_flake_config(&toplevel)?;
+    if self.common.show_metadata {
+      show_flake_metadata(&toplevel)?;
+    }
+    self.print_summary(&OsRebuildVariant::Build, &toplevel, &target_hostname);
+
+    let attr = toplevel.clone();
+    let drv_path = drv_path_attr(toplevel)?;
+
+    info!("Evaluating NixOS configuration");
+    // The path and any evaluation errors go straight to the terminal
+    let status = NixCommand::new(CommandKind::Eval)
+      .arg("--raw")
+      .args(drv_path.to_args())
+      .args(self.common.passthrough.eval_args())
+      .args(&self.extra_args)
+      .with_required_env()
+      .run_with_logs()?;
+    if !status.success() {
+      return Err(build_failure_context(
+        EvalError(format!("nix eval failed (exit status {status:?})")).into(),
+        &attr,
+        &target_hostname,
+      ));
     }
+
+    // `--raw` does not end the path with a newline
+    println!();
+    info!("Configuration evaluated successfully");
+    Ok(())
+  }
+
+  /// Build the `vm` variant of the configuration locally and check that it
+  /// boots, for `nh os switch --vm-test`.
+  fn vm_test(&self, target_hostname: &str, timeout: Duration) -> Result<()> {
+    let vm =
+      self.resolve_installable_and_toplevel(target_hostname, Some(&["vm"]))?;
+    let dir = result_tempdir("nh-os-vm-test")?;
+    let out_path = dir.path().join("result");
+
+    command::Build::new(vm)
+      .extra_arg("--out-link")
+      .extra_arg(&out_path)
+      .extra_args(&self.extra_args)
+      .passthrough(&self.common.passthrough)
+      .message("Building NixOS VM to test the configuration")
+      .nom(!self.common.no_nom)
+      .nom_args(split_nom_args(self.common.nom_args.as_deref())?)
+      .run()
+      .wrap_err("Failed to build VM for --vm-test")?;
+
+    vm_test::boot(&find_vm_script(&out_path)?, dir.path(), timeout)
+      .wrap_err("VM test failed, not activating the configuration")
+  }
+
+  fn check_expected_path(&self, built: &Path) -> Result<()> {
+    self
+      .common
+      .expect_path
+      .as_deref()
+      .map_or(Ok(()), |expected| check_expected_path(expected, built))
   }
 
   fn resolve_specialisation_and_profile(
@@ -643,9 +1018,11 @@ impl OsRebuildArgs {
 
         // For local builds, check if specialisation exists and fall back if not
         if out_path.exists() && !spec_path.exists() {
+          let available =
+            available_specialisations(&out_path.join("specialisation"));
           bail!(
-            "Specialisation '{}' does not exist in the built configuration",
-            spec
+            "Specialisation '{spec}' does not exist in the built \
+             configuration, available: {available}"
           );
         }
 
@@ -670,26 +1047,37 @@ impl OsRebuildArgs {
   // final_attr is the attribute of config.system.build.X to evaluate.
   // Used by Build and BuildVm subcommands which don't activate
   fn build_only(
-    self,
+    mut self,
     variant: &OsRebuildVariant,
     final_attrs: Option<&[&str]>,
     elevation: &ElevationStrategy,
-  ) -> Result<()> {
+  ) -> Result<PathBuf> {
     use OsRebuildVariant::{Build, BuildIso, BuildVm};
 
+    self.normalize_local_target_host();
+    let mut timings = Timings::new(self.common.timings);
+    exit::enter(Phase::Precheck);
     let (_, target_hostname) = self.setup_build_context(elevation)?;
+    exit::enter(Phase::Build);
 
-    let (out_path, _tempdir_guard) = self.determine_output_path(variant)?;
+    let (mut out_path, _tempdir_guard) = self.determine_output_path(variant)?;
 
     let toplevel =
       self.resolve_installable_and_toplevel(&target_hostname, final_attrs)?;
+    self.common.passthrough.confirm_flake_config(&toplevel)?;
+    if self.common.show_metadata {
+      show_flake_metadata(&toplevel)?;
+    }
+    self.print_summary(variant, &toplevel, &target_hostname);
 
     if self.update_args.update_all || self.update_args.update_input.is_some() {
-      update(
-        &toplevel,
-        self.update_args.update_input.clone(),
-        self.common.passthrough.commit_lock_file,
-      )?;
+      timings.time("update", || {
+        update(
+          &toplevel,
+          self.update_args.update_input.clone(),
+          self.common.passthrough.commit_lock_file,
+        )
+      })?;
     }
 
     let message = match variant {
@@ -703,39 +1091,77 @@ impl OsRebuildArgs {
       _ => "Building NixOS configuration",
     };
 
-    let actual_store_path = self.execute_build(toplevel, &out_path, message)?;
+    let attr = toplevel.clone();
+    let actual_store_path = timings.time("build", || {
+      self
+        .execute_build(toplevel, &out_path, message)
+        .map_err(|err| build_failure_context(err, &attr, &target_hostname))
+    })?;
 
+    if let Some(store_path) = actual_store_path.as_ref()
+      && self.common.no_link
+    {
+      out_path.clone_from(store_path);
+    }
+
+    outcome::record_built(actual_store_path.as_deref().unwrap_or(&out_path));
     let target_profile = self.resolve_specialisation_and_profile(&out_path)?;
 
-    handle_nixos_diff(
-      &self.common.diff,
-      self.target_host.as_ref(),
-      &target_profile,
-      actual_store_path.as_deref(),
-      &out_path,
-    )?;
+    timings.time("diff", || {
+      handle_nixos_diff(
+        &self.common.diff,
+        self.target_host.as_ref(),
+        &target_profile,
+        actual_store_path.as_deref(),
+        &out_path,
+      )
+    })?;
 
     // Build, BuildVm and BuildIso subcommands never activate
     debug_assert!(matches!(variant, Build | BuildVm | BuildIso));
 
-    Ok(())
+    Ok(out_path)
   }
 }
 
 impl OsRollbackArgs {
   #[expect(clippy::too_many_lines)]
   fn rollback(&self, elevation: ElevationStrategy) -> Result<()> {
+    if self.dry {
+      progress::set_spinners_enabled(false);
+    }
+
+    exit::enter(Phase::Precheck);
+    check_boot_profile(self.profile.as_deref())?;
     let elevate = has_elevation_status(self.bypass_root_check, &elevation)?;
+    exit::leave();
 
-    let generations = list_generations()?;
+    let profile = self
+      .profile
+      .as_deref()
+      .unwrap_or_else(|| Path::new(SYSTEM_PROFILE));
+    let generations = list_generations(profile)?;
 
     let current_generation = generations
       .iter()
       .find(|g| g.current)
       .ok_or_else(|| eyre!("Current generation not found"))?;
 
+    let to = if self.last_nh {
+      let store_path = last_switch::read()?.ok_or_else(|| {
+        eyre!("No generation recorded, nh os switch has not been run yet")
+      })?;
+      Some(generation_with_store_path(
+        profile,
+        &store_path,
+        &generations,
+      )?)
+    } else {
+      self.to
+    };
+
     // Find previous generation or specific generation
-    let target_generation = if let Some(gen_number) = self.to {
+    let target_generation = if let Some(gen_number) = to {
       get_generation_by_number(gen_number, &generations)?
     } else {
       &find_previous_generation(current_generation.number, &generations)?
@@ -743,15 +1169,8 @@ impl OsRollbackArgs {
 
     info!("Rolling back to generation {}", target_generation.number);
 
-    // Construct path to the generation
-    let profile_dir = Path::new(SYSTEM_PROFILE).parent().unwrap_or_else(|| {
-      tracing::warn!(
-        "SYSTEM_PROFILE has no parent, defaulting to /nix/var/nix/profiles"
-      );
-      Path::new("/nix/var/nix/profiles")
-    });
     let generation_link =
-      profile_dir.join(format!("system-{}-link", target_generation.number));
+      profile_generation_link(profile, target_generation.number)?;
 
     // Handle specialisations
     let current_specialisation = fs::read_to_string(SPEC_LOCATION)
@@ -789,26 +1208,26 @@ impl OsRollbackArgs {
     }
 
     if self.ask {
-      let confirmation = inquire::Confirm::new(&format!(
+      let confirmation = confirm(&format!(
         "Roll back to generation {}?",
         target_generation.number
       ))
-      .with_default(false)
       .prompt()?;
 
       if !confirmation {
-        bail!("User rejected the rollback");
+        bail!(Aborted("User rejected the rollback"));
       }
     }
 
     // Set the system profile
+    exit::enter(Phase::Activation);
     info!("Setting system profile...");
 
     // Instead of direct symlink operations, use a command with proper elevation
     Command::new("ln")
             .arg("-sfn") // force, symbolic link
             .arg(&generation_link)
-            .arg(SYSTEM_PROFILE)
+            .arg(profile)
             .elevate(elevate.then_some(elevation.clone()))
             .message("Setting system profile")
             .with_required_env()
@@ -859,13 +1278,13 @@ impl OsRollbackArgs {
       Err(e) => {
         // If activation fails, rollback the profile
         if current_generation.number > 0 {
-          let current_gen_link = profile_dir
-            .join(format!("system-{}-link", current_generation.number));
+          let current_gen_link =
+            profile_generation_link(profile, current_generation.number)?;
 
           Command::new("ln")
                         .arg("-sfn") // Force, symbolic link
                         .arg(&current_gen_link)
-                        .arg(SYSTEM_PROFILE)
+                        .arg(profile)
                         .elevate(elevate.then_some(elevation))
                         .message("Rolling back system profile")
                         .with_required_env()
@@ -907,7 +1326,10 @@ impl OsBuildImageArgs {
       Installable::Flake { .. } => {
         let images_installable =
           toplevel_for(&target_hostname, installable.clone(), &["images"])?;
-        get_build_image_variants_flake(&images_installable)?
+        get_build_image_variants_flake(
+          &images_installable,
+          self.common.common.passthrough.impure,
+        )?
       },
       Installable::File { .. } | Installable::Expression { .. } => {
         get_build_image_variants(&installable, &target_hostname)?
@@ -1185,6 +1607,22 @@ fn has_elevation_status(
   Ok(!is_root)
 }
 
+/// The store path that was built, as reported by the build, or resolved from
+/// the out link if the build did not report one. The profile is set to
+/// exactly this path, so activation never evaluates or builds the
+/// configuration a second time.
+fn built_store_path(
+  out_path: &Path,
+  actual_store_path: Option<&Path>,
+) -> Result<PathBuf> {
+  if let Some(store_path) = actual_store_path {
+    return Ok(store_path.to_path_buf());
+  }
+  out_path
+    .canonicalize()
+    .context("Failed to resolve base output path to store path")
+}
+
 fn find_previous_generation(
   current_number: u64,
   generations: &[generations::GenerationInfo],
@@ -1204,6 +1642,35 @@ fn find_previous_generation(
   Ok(previous_generation)
 }
 
+/// The link of generation `number` of `profile`.
+fn profile_generation_link(profile: &Path, number: u64) -> Result<PathBuf> {
+  generation_link(profile, number)
+    .ok_or_else(|| eyre!("{} is not a valid profile path", profile.display()))
+}
+
+/// The newest generation of `profile` that points to `store_path`.
+fn generation_with_store_path(
+  profile: &Path,
+  store_path: &Path,
+  generations: &[generations::GenerationInfo],
+) -> Result<u64> {
+  generations
+    .iter()
+    .map(|generation| generation.number)
+    .filter(|&number| {
+      profile_generation_link(profile, number)
+        .and_then(|link| Ok(fs::canonicalize(link)?))
+        .is_ok_and(|path| path == store_path)
+    })
+    .max()
+    .ok_or_else(|| {
+      eyre!(
+        "No generation of {} is left, it may have been removed by nh clean",
+        store_path.display()
+      )
+    })
+}
+
 fn get_generation_by_number(
   number: u64,
   generations: &[generations::GenerationInfo],
@@ -1214,11 +1681,17 @@ fn get_generation_by_number(
     .ok_or_else(|| eyre!("Generation {} not found", number))
 }
 
-fn list_generations() -> Result<Vec<generations::GenerationInfo>> {
-  let profile_path = PathBuf::from(SYSTEM_PROFILE);
-  let profiles_dir = profile_path
+/// The generations of `profile`, oldest first.
+fn list_generations(
+  profile: &Path,
+) -> Result<Vec<generations::GenerationInfo>> {
+  let profiles_dir = profile
     .parent()
     .unwrap_or_else(|| Path::new("/nix/var/nix/profiles"));
+  let prefix = format!(
+    "{}-",
+    profile.file_name().unwrap_or_default().to_string_lossy()
+  );
 
   let mut generations = Vec::new();
   for entry in fs::read_dir(profiles_dir)? {
@@ -1232,8 +1705,10 @@ fn list_generations() -> Result<Vec<generations::GenerationInfo>> {
 
     let path = entry.path();
     if let Some(name) = path.file_name().and_then(|s| s.to_str())
-      && name.starts_with("system-")
-      && name.ends_with("-link")
+      && name
+        .strip_prefix(&prefix)
+        .and_then(|rest| rest.strip_suffix("-link"))
+        .is_some_and(|number| number.parse::<u64>().is_ok())
       && let Some(gen_info) = generations::describe(&path, None)
     {
       generations.push(gen_info);
@@ -1261,14 +1736,94 @@ pub fn toplevel_for<S: AsRef<str>>(
   hostname: S,
   installable: Installable,
   final_attrs: &[&str],
+) -> Result<Installable> {
+  let attrs: Vec<&str> = ["system", "build"]
+    .into_iter()
+    .chain(final_attrs.iter().copied())
+    .collect();
+  config_attr_for(hostname, installable, &attrs)
+}
+
+/// Check that the bootloader can boot into generations of `profile`.
+/// `switch-to-configuration boot` only adds the system profile and the
+/// profiles in [`SYSTEM_PROFILES_DIR`] to the bootloader.
+///
+/// # Errors
+///
+/// Returns an error if `profile` is any other profile.
+fn check_boot_profile(profile: Option<&Path>) -> Result<()> {
+  let Some(profile) = profile else {
+    return Ok(());
+  };
+  if profile == Path::new(SYSTEM_PROFILE)
+    || profile.parent() == Some(Path::new(SYSTEM_PROFILES_DIR))
+  {
+    return Ok(());
+  }
+  bail!(
+    "The bootloader would not list generations of {}. Use --profile with a \
+     profile in {SYSTEM_PROFILES_DIR}, or activate it with `nh os test` \
+     instead",
+    profile.display()
+  );
+}
+
+/// The `drvPath` attribute of `installable`, which evaluates to its
+/// derivation without building it.
+///
+/// # Errors
+///
+/// Returns an error for store paths, which are already built.
+fn drv_path_attr(mut installable: Installable) -> Result<Installable> {
+  match &mut installable {
+    Installable::Flake { attribute, .. }
+    | Installable::File { attribute, .. }
+    | Installable::Expression { attribute, .. } => {
+      attribute.push(String::from("drvPath"));
+    },
+    Installable::Store { path } => {
+      bail!(
+        "Cannot evaluate store path '{}', it is already built",
+        path.display()
+      );
+    },
+  }
+  Ok(installable)
+}
+
+/// Resolve a NixOS installable to an attribute of its `config`.
+///
+/// # Errors
+///
+/// Returns an error if the flake attribute path is too specific to infer the
+/// requested attribute.
+fn config_attr_for<S: AsRef<str>>(
+  hostname: S,
+  installable: Installable,
+  config_attrs: &[&str],
+) -> Result<Installable> {
+  let attrs: Vec<&str> = std::iter::once("config")
+    .chain(config_attrs.iter().copied())
+    .collect();
+  configuration_attr_for(hostname, installable, &attrs)
+}
+
+/// Resolve a NixOS installable to an attribute path relative to the
+/// configuration itself, i.e. `nixosConfigurations.<hostname>` for flakes.
+///
+/// # Errors
+///
+/// Returns an error if the flake attribute path is too specific to infer the
+/// requested attribute.
+fn configuration_attr_for<S: AsRef<str>>(
+  hostname: S,
+  installable: Installable,
+  attrs: &[&str],
 ) -> Result<Installable> {
   let mut res = installable;
   let hostname_str = hostname.as_ref();
 
-  let toplevel = vec!["config", "system", "build"]
-    .into_iter()
-    .map(String::from)
-    .chain(final_attrs.iter().map(|&s| String::from(s)));
+  let toplevel = attrs.iter().copied().map(String::from);
 
   match res {
     Installable::Flake {
@@ -1346,6 +1901,31 @@ impl OsReplArgs {
   }
 }
 
+impl OsListSpecialisationsArgs {
+  fn run(self) -> Result<()> {
+    let installable =
+      self.installable.resolve_or_default(CommandContext::Os)?;
+    if matches!(installable, Installable::Store { .. }) {
+      bail!("Nix doesn't support nix store installables.");
+    }
+
+    let hostname = get_hostname(self.hostname)?;
+    let specialisations =
+      config_attr_for(&hostname, installable, &["specialisation"])?;
+    let names = get_attr_names(&specialisations, self.impure)
+      .wrap_err("Failed to evaluate the specialisations")?;
+
+    if names.is_empty() {
+      info!("The configuration has no specialisations");
+    }
+    for name in names {
+      println!("{name}");
+    }
+
+    Ok(())
+  }
+}
+
 impl OsGenerationsArgs {
   fn info(&self) -> Result<()> {
     let profile = match self.profile {
@@ -1391,8 +1971,154 @@ impl OsGenerationsArgs {
       })
       .collect();
 
-    generations::print_info(descriptions, self.fields.as_deref())?;
+    generations::print_info(
+      descriptions,
+      self.fields.as_deref(),
+      generations::GenerationFilter {
+        since: self.since,
+        limit: self.limit,
+      },
+    )?;
+
+    if profile == Path::new(SYSTEM_PROFILE)
+      && let Some(number) = generations::pending_reboot(&profile)
+    {
+      warn!("Reboot required to activate generation {number}");
+    }
 
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  #![allow(clippy::panic, clippy::unwrap_used, reason = "Fine in tests")]
+
+  use super::*;
+
+  #[derive(clap::Parser)]
+  struct Cli {
+    #[command(flatten)]
+    os: args::OsArgs,
+  }
+
+  #[test]
+  fn switch_rollback_targets_given_profile() {
+    let profile = "/nix/var/nix/profiles/system-profiles/testing";
+    let args::OsSubcommand::Switch(args) = <Cli as clap::Parser>::parse_from([
+      "nh",
+      "switch",
+      "--rollback",
+      "--profile",
+      profile,
+      "--specialisation",
+      "gaming",
+    ])
+    .os
+    .subcommand
+    else {
+      panic!("expected switch");
+    };
+
+    let rollback = args.into_rollback();
+    assert_eq!(rollback.profile.as_deref(), Some(Path::new(profile)));
+    assert_eq!(rollback.specialisation.as_deref(), Some("gaming"));
+  }
+
+  #[test]
+  fn no_link_builds_do_not_create_a_result_tempdir() {
+    let args::OsSubcommand::Switch(args) =
+      <Cli as clap::Parser>::parse_from(["nh", "switch", "--no-link"])
+        .os
+        .subcommand
+    else {
+      panic!("expected switch");
+    };
+
+    let (_, tempdir) = args
+      .activate
+      .rebuild
+      .determine_output_path(&OsRebuildVariant::Switch)
+      .unwrap();
+    assert!(tempdir.is_none());
+  }
+
+  #[test]
+  fn generations_are_listed_for_the_given_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["testing-1-link", "testing-2-link", "system-3-link"] {
+      fs::create_dir(dir.path().join(name)).unwrap();
+    }
+    let profile = dir.path().join("testing");
+
+    let numbers: Vec<u64> = list_generations(&profile)
+      .unwrap()
+      .iter()
+      .map(|generation| generation.number)
+      .collect();
+    assert_eq!(numbers, [1, 2]);
+    assert_eq!(
+      profile_generation_link(&profile, 2).unwrap(),
+      dir.path().join("testing-2-link")
+    );
+  }
+
+  #[test]
+  fn boot_profile_must_be_listed_by_bootloader() {
+    assert!(check_boot_profile(None).is_ok());
+    assert!(check_boot_profile(Some(Path::new(SYSTEM_PROFILE))).is_ok());
+    assert!(
+      check_boot_profile(Some(Path::new(
+        "/nix/var/nix/profiles/system-profiles/testing"
+      )))
+      .is_ok()
+    );
+    assert!(
+      check_boot_profile(Some(Path::new("/home/user/profiles/system")))
+        .is_err()
+    );
+  }
+
+  #[test]
+  fn drv_path_attr_appends_to_attribute() {
+    let toplevel = Installable::Flake {
+      reference: String::from("/etc/nixos"),
+      attribute: vec![
+        String::from("nixosConfigurations"),
+        String::from("laptop"),
+      ],
+    };
+    let drv = drv_path_attr(toplevel).unwrap();
+    assert_eq!(drv.to_args(), [
+      "/etc/nixos#nixosConfigurations.laptop.drvPath"
+    ]);
+
+    let store = Installable::Store {
+      path: PathBuf::from("/nix/store/abc-nixos-system"),
+    };
+    assert!(drv_path_attr(store).is_err());
+  }
+
+  #[test]
+  fn profile_is_set_to_the_built_store_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let store_path = dir.path().join("abc-nixos-system");
+    let out_link = dir.path().join("result");
+    fs::create_dir(&store_path).unwrap();
+    std::os::unix::fs::symlink(&store_path, &out_link).unwrap();
+
+    assert_eq!(
+      built_store_path(&out_link, Some(&store_path)).unwrap(),
+      store_path.canonicalize().unwrap()
+    );
+    assert_eq!(
+      built_store_path(&out_link, None).unwrap(),
+      store_path.canonicalize().unwrap()
+    );
+    // --no-link builds only have the path nix build reported
+    assert_eq!(
+      built_store_path(&dir.path().join("missing"), Some(&store_path)).unwrap(),
+      store_path
+    );
+  }
+}
@@ -0,0 +1,135 @@
+use std::{
+  collections::VecDeque,
+  io::{BufRead, BufReader},
+  path::Path,
+  process::{Command, Stdio},
+  sync::mpsc::{self, RecvTimeoutError},
+  thread,
+  time::{Duration, Instant},
+};
+
+use color_eyre::{
+  Result,
+  eyre::{Context, bail, eyre},
+};
+use tracing::{debug, info};
+
+/// How many lines of console output are shown when the VM fails to boot.
+const CONSOLE_TAIL_LINES: usize = 20;
+
+/// Boot the VM started by `vm_script` without a display and wait until it
+/// reaches `multi-user.target`. The VM's disk image is created in `dir`, so
+/// it is thrown away with it.
+///
+/// # Errors
+///
+/// Returns an error if the VM cannot be started, exits early, or does not
+/// reach `multi-user.target` within `timeout`. The error includes the last
+/// lines of the VM console.
+pub fn boot(vm_script: &Path, dir: &Path, timeout: Duration) -> Result<()> {
+  info!("Booting {} to test the configuration", vm_script.display());
+
+  let mut child = Command::new(vm_script)
+    .env("NIX_DISK_IMAGE", dir.join("vm-test.qcow2"))
+    .env("TMPDIR", dir)
+    .env("QEMU_OPTS", "-nographic")
+    .env(
+      "QEMU_KERNEL_PARAMS",
+      "console=ttyS0 systemd.show_status=true",
+    )
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .wrap_err_with(|| {
+      format!("Failed to start VM script {}", vm_script.display())
+    })?;
+
+  let stdout = child
+    .stdout
+    .take()
+    .ok_or_else(|| eyre!("Failed to capture VM console output"))?;
+  let (tx, lines) = mpsc::channel();
+  // Not joined: it finishes once the VM is killed and the pipe is closed
+  thread::spawn(move || {
+    for line in BufReader::new(stdout).split(b'\n').map_while(Result::ok) {
+      if tx
+        .send(String::from_utf8_lossy(&line).into_owned())
+        .is_err()
+      {
+        break;
+      }
+    }
+  });
+
+  let deadline = Instant::now() + timeout;
+  let mut tail = VecDeque::with_capacity(CONSOLE_TAIL_LINES);
+  let outcome = loop {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    match lines.recv_timeout(remaining) {
+      Ok(line) => {
+        if reached_multi_user(&line) {
+          break Ok(());
+        }
+        if tail.len() == CONSOLE_TAIL_LINES {
+          tail.pop_front();
+        }
+        tail.push_back(line);
+      },
+      Err(RecvTimeoutError::Timeout) => {
+        break Err(format!(
+          "did not reach multi-user.target within {}s",
+          timeout.as_secs()
+        ));
+      },
+      Err(RecvTimeoutError::Disconnected) => {
+        break Err("exited before reaching multi-user.target".to_string());
+      },
+    }
+  };
+
+  if let Err(err) = child.kill() {
+    debug!("Failed to stop test VM: {err}");
+  }
+  let _ = child.wait();
+
+  if let Err(reason) = outcome {
+    let console: Vec<_> = tail.into();
+    bail!(
+      "The test VM {reason}. Last console output:\n{}",
+      console.join("\n")
+    );
+  }
+
+  info!("Test VM reached multi-user.target");
+  Ok(())
+}
+
+/// Whether `line` is systemd's console message for reaching
+/// `multi-user.target`. Older systemd versions only print the description.
+fn reached_multi_user(line: &str) -> bool {
+  line.contains("Reached target")
+    && (line.contains("multi-user.target")
+      || line.contains("Multi-User System"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_multi_user_target() {
+    assert!(reached_multi_user(
+      "[  OK  ] Reached target multi-user.target - Multi-User System."
+    ));
+    assert!(reached_multi_user(
+      "[  OK  ] Reached target Multi-User System."
+    ));
+    assert!(!reached_multi_user(
+      "[  OK  ] Reached target basic.target - Basic System."
+    ));
+    assert!(!reached_multi_user(
+      "         Starting multi-user.target..."
+    ));
+  }
+}
@@ -9,11 +9,11 @@ use color_eyre::{
   eyre::{Context, eyre},
 };
 use nh_core::{
-  command::{CommandKind, NixCommand, exec_with_streaming},
+  command::{CommandKind, NixCommand, log_command},
   progress::{self, Spinner},
 };
 use subprocess::{Exec, Redirection};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use super::{RemoteHost, get_flake_flags, get_nix_sshopts_env};
 
@@ -72,6 +72,31 @@ impl CopyDirection<'_> {
   }
 }
 
+impl CopyDirection<'_> {
+  /// `NIX_SSHOPTS` for the copy. Nix uses the same options for both sides of
+  /// a copy between remotes, so a port is only passed if both hosts agree.
+  fn nix_sshopts(self) -> String {
+    match self {
+      Self::FromRemote(host) | Self::ToRemote { host, .. } => {
+        host.nix_sshopts_env()
+      },
+      Self::BetweenRemotes {
+        from_host, to_host, ..
+      } => {
+        if from_host.port == to_host.port {
+          from_host.nix_sshopts_env()
+        } else {
+          warn!(
+            "{from_host} and {to_host} use different SSH ports, which nix \
+             copy cannot pass to both; configure them in ~/.ssh/config instead"
+          );
+          get_nix_sshopts_env()
+        }
+      },
+    }
+  }
+}
+
 fn push_substitute_on_destination(
   args: &mut Vec<String>,
   use_substitutes: bool,
@@ -93,27 +118,33 @@ fn build_nix_copy_command<P: Into<OsString>>(
     .global_args(get_flake_flags())
     .args(direction.args())
     .arg(path.into())
-    .env("NIX_SSHOPTS", get_nix_sshopts_env())
+    .env("NIX_SSHOPTS", direction.nix_sshopts())
     .to_exec()
 }
 
 /// Copy a Nix closure from a remote host to localhost.
 pub fn copy_closure_from(host: &RemoteHost, path: &str) -> Result<()> {
-  info!("Copying result from build host '{host}'");
-
   let cmd = build_nix_copy_command(CopyDirection::FromRemote(host), path);
-  debug!(?cmd, "nix copy --from");
+  debug!("nix copy --from");
+  log_command(&cmd);
 
-  let (exit_status, _stdout, stderr) = exec_with_streaming(cmd, true)
-    .wrap_err("Failed to copy closure from remote host")?;
+  let spinner =
+    progress::spinner(format!("Copying result from build host '{host}'..."));
+  let copy_result = exec_with_spinner_streaming(cmd, &spinner);
+  spinner.finish_and_clear();
+
+  let (exit_status, _stdout, stderr) =
+    copy_result.wrap_err("Failed to copy closure from remote host")?;
 
   if !exit_status.success() {
+    error!("Failed to copy result from build host '{host}'");
     color_eyre::eyre::bail!(format_copy_failure(
       &format!("nix copy --from '{host}' failed"),
       exit_status,
       &stderr,
     ));
   }
+  info!("Copied result from build host '{host}'");
 
   Ok(())
 }
@@ -144,7 +175,13 @@ where
       let message = String::from_utf8_lossy(&line)
         .trim_end_matches(['\r', '\n'])
         .to_string();
-      spinner.println(message);
+
+      // A hidden spinner swallows `println`, so write the line ourselves
+      if spinner.is_hidden() {
+        eprintln!("{message}");
+      } else {
+        spinner.println(message);
+      }
       output.push_str(&String::from_utf8_lossy(&line));
     }
 
@@ -248,7 +285,8 @@ pub fn copy_to_remote(
     },
     path,
   );
-  debug!(?cmd, "nix copy --to");
+  debug!("nix copy --to");
+  log_command(&cmd);
 
   let spinner =
     progress::spinner(format!("Copying closure to remote host '{host}'..."));
@@ -283,8 +321,6 @@ pub fn copy_closure_between_remotes(
   path: &str,
   use_substitutes: bool,
 ) -> Result<()> {
-  info!("Copying closure from '{}' to '{}'", from_host, to_host);
-
   let cmd = build_nix_copy_command(
     CopyDirection::BetweenRemotes {
       from_host,
@@ -293,10 +329,17 @@ pub fn copy_closure_between_remotes(
     },
     path,
   );
-  debug!(?cmd, "nix copy between remotes");
+  debug!("nix copy between remotes");
+  log_command(&cmd);
 
-  let (exit_status, _stdout, stderr) = exec_with_streaming(cmd, true)
-    .wrap_err("Failed to copy closure between remote hosts")?;
+  let spinner = progress::spinner(format!(
+    "Copying closure from '{from_host}' to '{to_host}'..."
+  ));
+  let copy_result = exec_with_spinner_streaming(cmd, &spinner);
+  spinner.finish_and_clear();
+
+  let (exit_status, _stdout, stderr) =
+    copy_result.wrap_err("Failed to copy closure between remote hosts")?;
 
   if !exit_status.success() {
     color_eyre::eyre::bail!(format_copy_failure(
@@ -305,6 +348,7 @@ pub fn copy_closure_between_remotes(
       &stderr,
     ));
   }
+  info!("Copied closure from '{from_host}' to '{to_host}'");
 
   Ok(())
 }
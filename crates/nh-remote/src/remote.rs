@@ -20,16 +20,21 @@ use nh_core::{
   command::{
     CommandKind,
     ElevationStrategy,
+    EvalError,
     NixCommand,
     cache_password,
     get_cached_password,
     get_sudo_opts,
+    log_command,
+    log_pipeline,
+    nom_available,
   },
-  util::NixVariant,
+  util,
+  variant_quirks,
 };
 use nh_installable::Installable;
 use secrecy::{ExposeSecret, SecretString};
-use subprocess::{Exec, Redirection};
+use subprocess::{Capture, Exec, Redirection};
 use tracing::{debug, error, info, warn};
 
 mod copy;
@@ -180,6 +185,19 @@ fn nixos_activation_command(
   parts.join(" ")
 }
 
+/// Build the remote shell command that runs a Home Manager activation script,
+/// forwarding the local `HOME_MANAGER_BACKUP_EXT` if set.
+fn home_activation_command(activate: &str) -> String {
+  let mut parts = Vec::new();
+
+  if let Ok(ext) = env::var("HOME_MANAGER_BACKUP_EXT") {
+    parts.push(format!("HOME_MANAGER_BACKUP_EXT={}", shell_quote(&ext)));
+  }
+
+  parts.push(shell_quote(activate));
+  parts.join(" ")
+}
+
 /// Register a SIGINT handler that sets the global interrupt flag.
 ///
 /// This function is idempotent - multiple calls are safe and will not
@@ -257,12 +275,13 @@ fn cleanup_ssh_control_sockets(control_dir: &std::path::Path) {
       debug!("Closing SSH control socket: {}", path.display());
 
       // Run: ssh -o ControlPath=<socket> -O exit dummyhost
-      let result = Exec::cmd("ssh")
-        .args(["-o", &format!("ControlPath={}", path.display())])
-        .args(["-O", "exit", "dummyhost"])
-        .stdout(Redirection::Pipe)
-        .stderr(Redirection::Pipe)
-        .capture();
+      let result = capture_logged(
+        Exec::cmd("ssh")
+          .args(["-o", &format!("ControlPath={}", path.display())])
+          .args(["-O", "exit", "dummyhost"])
+          .stdout(Redirection::Pipe)
+          .stderr(Redirection::Pipe),
+      );
 
       match result {
         Ok(capture) => {
@@ -314,7 +333,7 @@ pub fn init_ssh_control() -> SshControlGuard {
 ///
 /// Returns an error if the SSH connection cannot be established.
 pub fn open_ssh_control_master(host: &RemoteHost) -> Result<()> {
-  let ssh_opts = get_ssh_opts();
+  let ssh_opts = host.ssh_opts();
   debug!("Establishing SSH ControlMaster to '{host}'");
 
   let mut cmd = Exec::cmd("ssh");
@@ -323,8 +342,7 @@ pub fn open_ssh_control_master(host: &RemoteHost) -> Result<()> {
   }
   cmd = cmd.arg("-T").arg(host.ssh_host()).arg("true");
 
-  let capture = cmd
-    .capture()
+  let capture = capture_logged(cmd)
     .wrap_err_with(|| format!("Failed to connect to remote host '{host}'"))?;
 
   if !capture.exit_status.success() {
@@ -338,6 +356,13 @@ pub fn open_ssh_control_master(host: &RemoteHost) -> Result<()> {
   Ok(())
 }
 
+/// Run `cmd` and capture its output, printing it first with
+/// `--print-commands` like every other external command.
+fn capture_logged(cmd: Exec) -> std::io::Result<Capture> {
+  log_command(&cmd);
+  cmd.capture()
+}
+
 /// Probe the effective uid on the remote host after SSH login.
 ///
 /// This runs `id -u` over the already-opened `ControlMaster` connection and
@@ -352,15 +377,14 @@ pub fn open_ssh_control_master(host: &RemoteHost) -> Result<()> {
 /// Returns an error if the SSH connection fails, `id -u` exits non-zero, or
 /// its stdout cannot be parsed as a `u32`.
 pub fn probe_remote_uid(host: &RemoteHost) -> Result<u32> {
-  let ssh_opts = get_ssh_opts();
+  let ssh_opts = host.ssh_opts();
   let mut cmd = Exec::cmd("ssh");
   for opt in &ssh_opts {
     cmd = cmd.arg(opt);
   }
   cmd = cmd.arg("-T").arg(host.ssh_host()).arg("id -u");
 
-  let capture = cmd
-    .capture()
+  let capture = capture_logged(cmd)
     .wrap_err_with(|| format!("Failed to probe remote uid on '{host}'"))?;
 
   if !capture.exit_status.success() {
@@ -484,6 +508,8 @@ impl NixStoreScheme {
 pub struct RemoteHost {
   /// The host string (may include user@)
   host:         String,
+  /// SSH port given as `host:port`
+  port:         Option<u16>,
   store_scheme: NixStoreScheme,
 }
 
@@ -516,6 +542,9 @@ impl RemoteHost {
   /// - `ssh://[user@]hostname`
   /// - `ssh-ng://[user@]hostname`
   ///
+  /// Each form may end in `:port`, e.g. `user@hostname:2222` or
+  /// `[2001:db8::1]:2222`.
+  ///
   /// URI schemes are stripped for raw SSH calls and preserved when constructing
   /// Nix store URIs. Bare hosts default to `ssh-ng://`.
   ///
@@ -535,6 +564,8 @@ impl RemoteHost {
       |host| (host, NixStoreScheme::SshNg),
     );
 
+    let (host, port) = split_port(host)?;
+
     if host.is_empty() {
       bail!("Empty hostname in host specification");
     }
@@ -565,8 +596,9 @@ impl RemoteHost {
 
       if !is_bracketed_ipv6 {
         bail!(
-          "Invalid hostname '{}': contains ':'. Ports should be specified via \
-           NIX_SSHOPTS=\"-p 2222\" or ~/.ssh/config",
+          "Invalid hostname '{}': contains ':'. IPv6 addresses must be in \
+           brackets. Ports can be given as host:2222, or via NIX_SSHOPTS=\"-p \
+           2222\" or ~/.ssh/config",
           hostname_part
         );
       }
@@ -592,10 +624,30 @@ impl RemoteHost {
 
     Ok(Self {
       host: host.to_string(),
+      port,
       store_scheme,
     })
   }
 
+  /// SSH options for connecting to this host: [`get_ssh_opts`], followed by
+  /// the port if one was given.
+  #[must_use]
+  pub fn ssh_opts(&self) -> Vec<String> {
+    let mut opts = get_ssh_opts();
+    if let Some(port) = self.port {
+      opts.push("-p".to_string());
+      opts.push(port.to_string());
+    }
+    opts
+  }
+
+  /// The `NIX_SSHOPTS` value for Nix store commands talking to this host.
+  /// Nix store URIs cannot carry a port, so it is passed here instead.
+  #[must_use]
+  pub fn nix_sshopts_env(&self) -> String {
+    nix_sshopts_env_with_port(self.port)
+  }
+
   /// Get the SSH-compatible host string.
   ///
   /// Strips brackets from IPv6 addresses since SSH doesn't accept them.
@@ -638,6 +690,18 @@ impl RemoteHost {
   pub fn nix_store_uri(&self) -> String {
     format!("{}://{}", self.store_scheme.as_str(), self.host)
   }
+
+  /// Whether this host refers to the local machine.
+  ///
+  /// Only bare `localhost`, `127.0.0.1` and `[::1]` are considered local. A
+  /// `user@` prefix keeps the host remote, since the caller presumably wants
+  /// to connect as that user over SSH, and so does an explicit port.
+  #[must_use]
+  pub fn is_localhost(&self) -> bool {
+    !self.host.contains('@')
+      && self.port.is_none()
+      && matches!(self.hostname(), "localhost" | "127.0.0.1" | "[::1]")
+  }
 }
 
 impl std::str::FromStr for RemoteHost {
@@ -650,15 +714,69 @@ impl std::str::FromStr for RemoteHost {
 
 impl std::fmt::Display for RemoteHost {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{}", self.host)
+    match self.port {
+      Some(port) => write!(f, "{}:{port}", self.host),
+      None => write!(f, "{}", self.host),
+    }
+  }
+}
+
+/// Split a `:port` suffix off a host specification, e.g. `user@host:2222` or
+/// `[2001:db8::1]:2222`. Unbracketed IPv6 addresses are returned unchanged,
+/// so that they are rejected by the hostname validation.
+fn split_port(host: &str) -> Result<(&str, Option<u16>)> {
+  let hostname = host.rsplit('@').next().unwrap_or(host);
+  let port = if hostname.starts_with('[') {
+    hostname.rsplit_once("]:").map(|(_, port)| port)
+  } else {
+    hostname
+      .split_once(':')
+      .map(|(_, port)| port)
+      .filter(|port| !port.contains(':'))
+  };
+
+  let Some(port) = port else {
+    return Ok((host, None));
+  };
+  let parsed = port
+    .parse::<u16>()
+    .ok()
+    .filter(|port| *port != 0)
+    .ok_or_else(|| {
+      eyre!("Invalid port '{port}' in host specification: {host}")
+    })?;
+
+  Ok((&host[..host.len() - port.len() - 1], Some(parsed)))
+}
+
+static SSH_OPTS: OnceLock<String> = OnceLock::new();
+
+/// Use `opts` instead of `NH_SSHOPTS` and `NIX_SSHOPTS` for every SSH
+/// connection. Set from `--ssh-opts`.
+pub fn set_ssh_opts(opts: String) {
+  let _ = SSH_OPTS.set(opts);
+}
+
+/// The user's SSH options and where they came from: `--ssh-opts`, then
+/// `NH_SSHOPTS`, then `NIX_SSHOPTS`.
+fn user_ssh_opts() -> (&'static str, String) {
+  if let Some(opts) = SSH_OPTS.get() {
+    return ("--ssh-opts", opts.clone());
   }
+  env::var("NH_SSHOPTS").map_or_else(
+    |_| {
+      env::var("NIX_SSHOPTS")
+        .map_or_else(|_| ("", String::new()), |v| ("NIX_SSHOPTS", v))
+    },
+    |v| ("NH_SSHOPTS", v),
+  )
 }
 
 /// Get the default SSH options for connection multiplexing.
 /// Includes a `ControlPath` pointing to our control socket directory.
 fn get_default_ssh_opts() -> Vec<String> {
   let control_dir = get_ssh_control_dir();
-  let control_path = control_dir.join("ssh-%n");
+  let control_path = control_dir.join("ssh-%n-%p");
 
   vec![
     "-o".to_string(),
@@ -680,21 +798,14 @@ fn shell_quote(s: &str) -> String {
   )
 }
 
-/// Get SSH options from `NH_SSHOPTS` (or `NIX_SSHOPTS` for compatibility)
-/// plus our defaults. This includes connection multiplexing options
-/// (`ControlMaster`, `ControlPath`, `ControlPersist`) which enable efficient
-/// reuse of SSH connections.
+/// Get SSH options from `--ssh-opts`, `NH_SSHOPTS` or `NIX_SSHOPTS` (for
+/// compatibility) plus our defaults. This includes connection multiplexing
+/// options (`ControlMaster`, `ControlPath`, `ControlPersist`) which enable
+/// efficient reuse of SSH connections.
 pub fn get_ssh_opts() -> Vec<String> {
   let mut opts: Vec<String> = Vec::new();
 
-  // NH_SSHOPTS takes precedence; NIX_SSHOPTS is the compatibility fallback.
-  let (sshopts_var, sshopts_val) = env::var("NH_SSHOPTS").map_or_else(
-    |_| {
-      env::var("NIX_SSHOPTS")
-        .map_or_else(|_| ("", String::new()), |v| ("NIX_SSHOPTS", v))
-    },
-    |v| ("NH_SSHOPTS", v),
-  );
+  let (sshopts_var, sshopts_val) = user_ssh_opts();
 
   if !sshopts_val.is_empty() {
     if let Some(parsed) = shlex::split(&sshopts_val) {
@@ -728,10 +839,18 @@ pub fn get_ssh_opts() -> Vec<String> {
 /// this mechanism. Users needing complex SSH options should use
 /// `~/.ssh/config` instead.
 fn get_nix_sshopts_env() -> String {
-  // NH_SSHOPTS takes precedence; NIX_SSHOPTS is the compatibility fallback.
-  let user_opts = env::var("NH_SSHOPTS")
-    .or_else(|_| env::var("NIX_SSHOPTS"))
-    .unwrap_or_default();
+  nix_sshopts_env_with_port(None)
+}
+
+fn nix_sshopts_env_with_port(port: Option<u16>) -> String {
+  let (_, mut user_opts) = user_ssh_opts();
+  if let Some(port) = port {
+    if !user_opts.is_empty() {
+      user_opts.push(' ');
+    }
+    user_opts.push_str("-p ");
+    user_opts.push_str(&port.to_string());
+  }
   let default_opts = get_default_ssh_opts();
 
   if user_opts.is_empty() {
@@ -751,10 +870,7 @@ fn get_nix_sshopts_env() -> String {
 ///
 /// Returns `false` if unset, empty, or set to any other value.
 fn should_cleanup_remote() -> bool {
-  env::var("NH_REMOTE_CLEANUP").is_ok_and(|val| {
-    let val = val.trim().to_lowercase();
-    val == "1" || val == "true" || val == "yes"
-  })
+  util::env_flag_enabled("NH_REMOTE_CLEANUP")
 }
 
 /// Attempt to clean up a remote process using pkill.
@@ -774,7 +890,7 @@ fn attempt_remote_cleanup(host: &RemoteHost, remote_cmd: &str) {
     return;
   }
 
-  let ssh_opts = get_ssh_opts();
+  let ssh_opts = host.ssh_opts();
   let quoted_cmd = shell_quote(remote_cmd); // for safe passing through pkill's --full argument
 
   // Build the pkill command:
@@ -789,6 +905,7 @@ fn attempt_remote_cleanup(host: &RemoteHost, remote_cmd: &str) {
   ssh_cmd = ssh_cmd.arg(host.ssh_host()).arg(&pkill_cmd);
 
   debug!("Attempting remote cleanup on '{host}': pkill -INT --full <command>");
+  log_command(&ssh_cmd);
 
   // Use popen with timeout to avoid hanging on unresponsive hosts
   let mut job = match ssh_cmd.start() {
@@ -869,12 +986,10 @@ fn attempt_remote_cleanup(host: &RemoteHost, remote_cmd: &str) {
 /// safer to assist the user instead. Without those features, remote deployment
 /// may never succeed.
 fn get_flake_flags() -> Vec<&'static str> {
-  let variant = nh_core::util::get_nix_variant();
-  match variant {
-    NixVariant::Determinate => vec![],
-    NixVariant::Nix | NixVariant::Lix => {
-      vec!["--extra-experimental-features", "nix-command flakes"]
-    },
+  if variant_quirks::quirks().experimental_flakes {
+    vec!["--extra-experimental-features", "nix-command flakes"]
+  } else {
+    vec![]
   }
 }
 
@@ -910,7 +1025,7 @@ fn run_remote_command(
   args: &[&str],
   capture_stdout: bool,
 ) -> Result<Option<String>> {
-  let ssh_opts = get_ssh_opts();
+  let ssh_opts = host.ssh_opts();
 
   debug!("Running remote command on {}: {}", host, args.join(" "));
 
@@ -925,8 +1040,7 @@ fn run_remote_command(
   if capture_stdout {
     cmd = cmd.stdout(Redirection::Pipe).stderr(Redirection::Pipe);
   }
-
-  let capture = cmd.capture().wrap_err_with(|| {
+  let capture = capture_logged(cmd).wrap_err_with(|| {
     format!("Failed to execute command on remote host '{host}'")
   })?;
 
@@ -981,7 +1095,7 @@ pub fn validate_closure_remote(
   essential_files: &[(&str, &str)],
   context_info: Option<&str>,
 ) -> Result<()> {
-  let ssh_opts = get_ssh_opts();
+  let ssh_opts = host.ssh_opts();
 
   let mut missing = Vec::new();
   let mut ssh_stderr = String::new();
@@ -996,17 +1110,20 @@ pub fn validate_closure_remote(
     })?;
     let test_cmd = format!("test -e {quoted_path}");
 
-    let check_result = std::process::Command::new("ssh")
-      .args(&ssh_opts)
-      .arg(host.ssh_host())
-      .arg(&test_cmd)
-      .output();
+    let check_result = capture_logged(
+      Exec::cmd("ssh")
+        .args(&ssh_opts)
+        .arg(host.ssh_host())
+        .arg(&test_cmd)
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Pipe),
+    );
 
     match check_result {
-      Ok(output) if !output.status.success() => {
-        let stderr = String::from_utf8_lossy(&output.stderr);
+      Ok(capture) if !capture.exit_status.success() => {
+        let stderr = capture.stderr_str();
         if !stderr.is_empty() {
-          ssh_stderr = stderr.to_string();
+          ssh_stderr = stderr;
           break;
         }
         missing.push(format!("  - {file} ({description})"));
@@ -1095,13 +1212,16 @@ impl ActivationType {
 /// Represents the target platform for remote operations.
 ///
 /// This enum allows the remote module to support multiple platforms while
-/// keeping the implementation generic. Currently only NixOS is implemented.
-/// Other platforms can be added in the future.
+/// keeping the implementation generic. Currently NixOS and Home Manager are
+/// implemented. Other platforms can be added in the future.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Platform {
   /// NixOS system configuration
   NixOS,
-  // TODO: Add Darwin and HomeManager support
+
+  /// Home Manager configuration, activated as the SSH user
+  HomeManager,
+  // TODO: Add Darwin support
   //
   // To add support for other platforms:
   //
@@ -1109,7 +1229,6 @@ pub enum Platform {
   // 2. Implement platform-specific activation logic in a (private) function
   // 3. Update `activate_remote()` to dispatch to the new platform handler
   // Darwin,
-  // HomeManager,
 }
 
 /// Configuration for remote activation operations.
@@ -1133,17 +1252,21 @@ pub struct ActivateRemoteConfig {
   /// - `Some(strategy)`: Use the specified elevation strategy (sudo, doas,
   ///   etc.)
   pub elevation: Option<ElevationStrategy>,
+
+  /// Profile to set for boot and switch activations. Defaults to the NixOS
+  /// system profile.
+  pub profile: Option<PathBuf>,
 }
 
 /// Activate a system configuration on a remote host.
 ///
-/// Currently only supports NixOS.
+/// Currently supports NixOS and Home Manager.
 ///
 /// # Arguments
 ///
 /// * `host` - The remote host to activate on
 /// * `system_profile` - The path to the NixOS system profile (e.g.,
-///   /nix/var/nix/profiles/system)
+///   /nix/var/nix/profiles/system), or the Home Manager activation package
 /// * `config` - Activation configuration options
 ///
 /// # Errors
@@ -1156,13 +1279,74 @@ pub fn activate_remote(
 ) -> Result<()> {
   match config.platform {
     Platform::NixOS => activate_nixos_remote(host, system_profile, config),
+    Platform::HomeManager => activate_home_remote(host, system_profile, config),
     // TODO:
     // Platform::Darwin => activate_darwin_remote(host, system_profile, config),
-    // Platform::HomeManager => activate_home_remote(host, system_profile,
-    // config),
   }
 }
 
+/// Activate a Home Manager configuration on a remote host.
+///
+/// The activation script runs as the SSH user, without elevation, since Home
+/// Manager manages the home directory of the user running it. Only
+/// [`ActivationType::Switch`] is supported.
+///
+/// # Errors
+///
+/// Returns an error if elevation or an unsupported activation type is
+/// requested, or if the SSH command or the activation script fails.
+fn activate_home_remote(
+  host: &RemoteHost,
+  activation_package: &Path,
+  config: &ActivateRemoteConfig,
+) -> Result<()> {
+  if config.activation_type != ActivationType::Switch {
+    bail!(
+      "Home Manager does not support '{}' activation",
+      config.activation_type.as_str()
+    );
+  }
+  if config
+    .elevation
+    .as_ref()
+    .is_some_and(|strategy| !matches!(strategy, ElevationStrategy::None))
+  {
+    bail!("Home Manager activation must not be elevated");
+  }
+
+  let activate = activation_package.join("activate");
+  let activate_str = activate
+    .to_str()
+    .ok_or_else(|| eyre!("activate path contains invalid UTF-8"))?;
+
+  let mut ssh_cmd = Exec::cmd("ssh");
+  for opt in host.ssh_opts() {
+    ssh_cmd = ssh_cmd.arg(opt);
+  }
+  ssh_cmd = ssh_cmd
+    .arg("-T")
+    .arg(host.ssh_host())
+    .arg(home_activation_command(activate_str));
+
+  debug!("Activating Home Manager configuration");
+  let capture = capture_logged(ssh_cmd)
+    .wrap_err("Failed to activate Home Manager configuration")?;
+
+  if config.show_logs {
+    println!("{}", capture.stdout_str());
+  }
+
+  if !capture.exit_status.success() {
+    bail!(
+      "Home Manager activation failed on '{}':\n{}",
+      host,
+      capture.stderr_str()
+    );
+  }
+
+  Ok(())
+}
+
 /// Activate a NixOS system configuration on a remote host.
 ///
 /// Handles the SSH commands required to activate a NixOS system. Supports
@@ -1182,7 +1366,7 @@ fn activate_nixos_remote(
   system_profile: &Path,
   config: &ActivateRemoteConfig,
 ) -> Result<()> {
-  let ssh_opts = get_ssh_opts();
+  let ssh_opts = host.ssh_opts();
 
   // Prompt for password if elevation is needed
   // Skip for None (no elevation) and Passwordless (remote has NOPASSWD
@@ -1248,10 +1432,8 @@ fn activate_nixos_remote(
           ssh_cmd.stdin(format!("{}\n", password.expose_secret()).into_bytes());
       }
 
-      debug!(?ssh_cmd, "Activating NixOS configuration");
-
-      let capture = ssh_cmd
-        .capture()
+      debug!("Activating NixOS configuration");
+      let capture = capture_logged(ssh_cmd)
         .wrap_err("Failed to activate NixOS configuration")?;
 
       if config.show_logs {
@@ -1278,9 +1460,13 @@ fn activate_nixos_remote(
       profile_ssh_cmd = profile_ssh_cmd.arg(host.ssh_host());
 
       // Build the remote command using helper function
+      let profile = config
+        .profile
+        .as_deref()
+        .unwrap_or_else(|| Path::new(NIXOS_SYSTEM_PROFILE));
       let base_cmd = format!(
         "nix build --no-link --profile {} {}",
-        NIXOS_SYSTEM_PROFILE,
+        shell_quote(&profile.to_string_lossy()),
         shell_quote(&system_profile.to_string_lossy())
       );
       let profile_remote_cmd =
@@ -1294,10 +1480,8 @@ fn activate_nixos_remote(
           .stdin(format!("{}\n", password.expose_secret()).into_bytes());
       }
 
-      debug!(?profile_ssh_cmd, "Setting NixOS profile");
-
-      let profile_capture = profile_ssh_cmd
-        .capture()
+      debug!("Setting NixOS profile");
+      let profile_capture = capture_logged(profile_ssh_cmd)
         .wrap_err("Failed to set NixOS profile")?;
 
       if !profile_capture.exit_status.success() {
@@ -1333,10 +1517,8 @@ fn activate_nixos_remote(
           .stdin(format!("{}\n", password.expose_secret()).into_bytes());
       }
 
-      debug!(?boot_ssh_cmd, "Bootloader activation");
-
-      let boot_capture = boot_ssh_cmd
-        .capture()
+      debug!("Bootloader activation");
+      let boot_capture = capture_logged(boot_ssh_cmd)
         .wrap_err("Bootloader activation failed")?;
 
       if !boot_capture.exit_status.success() {
@@ -1411,12 +1593,15 @@ fn eval_drv_path(installable: &Installable) -> Result<PathBuf> {
     .stdout(Redirection::Pipe)
     .stderr(Redirection::Pipe);
 
-  let capture = cmd.capture().wrap_err("Failed to run nix eval")?;
+  let capture = capture_logged(cmd).wrap_err("Failed to run nix eval")?;
 
   if !capture.exit_status.success() {
-    bail!(
-      "Failed to evaluate derivation path:\n{}",
-      capture.stderr_str()
+    return Err(
+      EvalError(format!(
+        "Failed to evaluate derivation path:\n{}",
+        capture.stderr_str()
+      ))
+      .into(),
     );
   }
 
@@ -1467,6 +1652,9 @@ pub struct RemoteBuildConfig {
   /// Whether to use nix-output-monitor for build output
   pub use_nom: bool,
 
+  /// Extra arguments for nix-output-monitor
+  pub nom_args: Vec<String>,
+
   /// Whether to use substitutes when copying closures
   pub use_substitutes: bool,
 
@@ -1599,11 +1787,8 @@ fn build_on_remote(
   // Build command: nix build <drv>^* --print-out-paths [extra_args...]
   let drv_with_outputs = format!("{}^*", drv_path.display());
 
-  if config.use_nom {
-    // Check that nom is available before attempting to use it
-    which::which("nom")
-      .wrap_err("nom (nix-output-monitor) is required but not found in PATH")?;
-
+  // nom runs locally, so fall back to plain output when it is not installed
+  if config.use_nom && nom_available() {
     // With nom: pipe through nix-output-monitor
     build_on_remote_with_nom(host, &drv_with_outputs, config)
   } else {
@@ -1640,7 +1825,7 @@ fn build_on_remote_simple(
   // Register interrupt handler at start
   register_interrupt_handler()?;
 
-  let ssh_opts = get_ssh_opts();
+  let ssh_opts = host.ssh_opts();
 
   let args = build_nix_command(
     drv_with_outputs,
@@ -1665,6 +1850,7 @@ fn build_on_remote_simple(
     .arg(&remote_cmd)
     .stdout(Redirection::Pipe)
     .stderr(Redirection::Pipe);
+  log_command(&ssh_cmd);
 
   // Execute with start() to get a Job handle
   let mut job = ssh_cmd.start()?;
@@ -1733,7 +1919,7 @@ fn build_on_remote_with_nom(
   // Register interrupt handler at start
   register_interrupt_handler()?;
 
-  let ssh_opts = get_ssh_opts();
+  let ssh_opts = host.ssh_opts();
 
   // Build the remote command with JSON output for nom
   let remote_args = build_nix_command(
@@ -1763,11 +1949,11 @@ fn build_on_remote_with_nom(
     .stderr(Redirection::Merge);
 
   // Pipe through nom
-  let nom_cmd = Exec::cmd("nom").arg("--json");
+  let nom_cmd = Exec::cmd("nom").arg("--json").args(&config.nom_args);
+  debug!("Running remote build with nom");
+  log_pipeline(&[&ssh_cmd, &nom_cmd]);
   let pipeline = (ssh_cmd | nom_cmd).stdout(Redirection::None);
 
-  debug!(?pipeline, "Running remote build with nom");
-
   // Use popen() to get access to individual processes so we can check
   // ssh's exit status, not nom's. The pipeline's join() only returns
   // the exit status of the last command (nom), which always succeeds
@@ -1880,6 +2066,10 @@ mod tests {
       }
     }
 
+    #[allow(
+      clippy::unused_self,
+      reason = "Ties the env mutation to a live guard"
+    )]
     fn clear(&self) {
       unsafe {
         env::remove_var("NH_SSHOPTS");
@@ -1910,6 +2100,7 @@ mod tests {
     fn hostname_always_returns_suffix_after_last_at(s in "\\PC*") {
         let host = RemoteHost {
           host:         s.clone(),
+          port:         None,
           store_scheme: NixStoreScheme::SshNg,
         };
         let expected = s.rsplit('@').next().unwrap();
@@ -1920,6 +2111,7 @@ mod tests {
     fn hostname_is_substring_of_host(s in "\\PC*") {
         let host = RemoteHost {
           host:         s.clone(),
+          port:         None,
           store_scheme: NixStoreScheme::SshNg,
         };
         prop_assert!(s.contains(host.hostname()));
@@ -1929,6 +2121,7 @@ mod tests {
     fn hostname_no_at_means_whole_string(s in "[^@]*") {
         let host = RemoteHost {
           host:         s.clone(),
+          port:         None,
           store_scheme: NixStoreScheme::SshNg,
         };
         prop_assert_eq!(host.hostname(), s);
@@ -1939,6 +2132,7 @@ mod tests {
         let full = format!("{user}@{hostname}");
         let host = RemoteHost {
           host: full,
+          port:         None,
           store_scheme: NixStoreScheme::SshNg,
         };
         prop_assert_eq!(host.hostname(), hostname);
@@ -2018,11 +2212,21 @@ mod tests {
   }
 
   #[test]
-  fn test_parse_port_rejected() {
-    let Err(err) = RemoteHost::parse("hostname:22") else {
-      panic!("expected error for port in hostname");
-    };
-    assert!(err.to_string().contains("NIX_SSHOPTS"));
+  fn test_parse_port() {
+    let host = RemoteHost::parse("user@hostname:2222").expect("should parse");
+    assert_eq!(host.hostname(), "hostname");
+    assert_eq!(host.port, Some(2222));
+    assert_eq!(host.to_string(), "user@hostname:2222");
+    assert_eq!(host.nix_store_uri(), "ssh-ng://user@hostname");
+    assert!(host.ssh_opts().ends_with(&["-p".into(), "2222".into()]));
+  }
+
+  #[test]
+  fn test_parse_invalid_port_rejected() {
+    assert!(RemoteHost::parse("hostname:").is_err());
+    assert!(RemoteHost::parse("hostname:0").is_err());
+    assert!(RemoteHost::parse("hostname:65536").is_err());
+    assert!(RemoteHost::parse("hostname:ssh").is_err());
   }
 
   #[test]
@@ -2111,10 +2315,11 @@ mod tests {
   }
 
   #[test]
-  fn test_parse_ipv6_with_port_rejected() {
-    // IPv6 with port syntax should be rejected (use NIX_SSHOPTS)
-    let result = RemoteHost::parse("[2001:db8::1]:22");
-    assert!(result.is_err());
+  fn test_parse_ipv6_with_port() {
+    let host =
+      RemoteHost::parse("[2001:db8::1]:22").expect("should parse IPv6 port");
+    assert_eq!(host.hostname(), "[2001:db8::1]");
+    assert_eq!(host.port, Some(22));
   }
 
   #[test]
@@ -2228,6 +2433,22 @@ mod tests {
     assert_eq!(host.nix_store_uri(), "ssh-ng://user@[2001:db8::1]");
   }
 
+  #[test]
+  fn test_is_localhost_bare_names() {
+    for input in ["localhost", "127.0.0.1", "[::1]", "ssh-ng://localhost"] {
+      let host = RemoteHost::parse(input).expect("should parse");
+      assert!(host.is_localhost(), "{input} should be local");
+    }
+  }
+
+  #[test]
+  fn test_is_localhost_rejects_user_and_remote_hosts() {
+    for input in ["root@localhost", "buildserver", "[2001:db8::1]"] {
+      let host = RemoteHost::parse(input).expect("should parse");
+      assert!(!host.is_localhost(), "{input} should not be local");
+    }
+  }
+
   #[test]
   fn test_shell_quote_simple() {
     assert_eq!(shell_quote("simple"), "simple");
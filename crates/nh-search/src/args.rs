@@ -1,7 +1,10 @@
-use std::path::PathBuf;
+use std::{io::Read, path::PathBuf};
 
 use clap::{Args, Subcommand, ValueEnum};
-use color_eyre::{Result, eyre::bail};
+use color_eyre::{
+  Result,
+  eyre::{Context, bail},
+};
 
 const DEFAULT_LIMIT: u64 = 30;
 const DEFAULT_CHANNEL: &str = "nixos-unstable";
@@ -19,6 +22,15 @@ pub struct SearchArgs {
   #[command(flatten)]
   pub platforms: PlatformsArg,
 
+  #[command(flatten)]
+  pub platform_filter: PlatformFilterArg,
+
+  #[command(flatten)]
+  pub first: FirstArg,
+
+  #[command(flatten)]
+  pub installed_only: InstalledOnlyArg,
+
   /// Output results as JSON
   #[arg(
     long,
@@ -29,6 +41,21 @@ pub struct SearchArgs {
   )]
   pub json: bool,
 
+  /// Print plain links instead of terminal hyperlinks, even if the terminal
+  /// appears to support them
+  #[arg(
+    long,
+    env = "NH_NO_HYPERLINKS",
+    value_parser = clap::builder::BoolishValueParser::new(),
+    global = true
+  )]
+  pub no_hyperlinks: bool,
+
+  /// Read the query from stdin instead of the arguments, e.g. for piping in
+  /// a selection from fzf
+  #[arg(long, global = true, conflicts_with = "query")]
+  pub stdin: bool,
+
   /// Default search mode used when no subcommand is given.
   /// Accepts `packages` or `options` (scope defaults to `all`).
   #[arg(
@@ -72,8 +99,17 @@ pub struct PackagesArgs {
   #[command(flatten)]
   pub platforms: PlatformsArg,
 
+  #[command(flatten)]
+  pub platform_filter: PlatformFilterArg,
+
+  #[command(flatten)]
+  pub first: FirstArg,
+
+  #[command(flatten)]
+  pub installed_only: InstalledOnlyArg,
+
   /// Name of the package to search
-  #[arg(required = true)]
+  #[arg(required_unless_present = "stdin")]
   pub query: Vec<String>,
 }
 
@@ -96,7 +132,7 @@ pub struct OptionsArgs {
   pub scope: Option<OptionScope>,
 
   /// Name of the option to search
-  #[arg(required = true)]
+  #[arg(required_unless_present = "stdin")]
   pub query: Vec<String>,
 }
 
@@ -118,7 +154,7 @@ pub struct OfflineArgs {
   pub databases: Vec<PathBuf>,
 
   /// Name of the package or option to search
-  #[arg(required = true)]
+  #[arg(required_unless_present = "stdin")]
   pub query: Vec<String>,
 }
 
@@ -128,7 +164,7 @@ pub struct PrsArgs {
   pub days: DaysArg,
 
   /// Pull request search query
-  #[arg(required = true)]
+  #[arg(required_unless_present = "stdin")]
   pub query: Vec<String>,
 }
 
@@ -138,7 +174,7 @@ pub struct IssuesArgs {
   pub days: DaysArg,
 
   /// Issue search query
-  #[arg(required = true)]
+  #[arg(required_unless_present = "stdin")]
   pub query: Vec<String>,
 }
 
@@ -180,6 +216,65 @@ pub struct PlatformsArg {
   pub value: bool,
 }
 
+#[derive(Args, Debug, Clone, Copy)]
+pub struct FirstArg {
+  /// Print only the attribute name of the most relevant package, for use in
+  /// scripts. Fails if nothing matches
+  #[arg(id = "first", long = "first", conflicts_with = "json")]
+  pub value: bool,
+}
+
+#[derive(Args, Debug, Clone, Copy)]
+pub struct InstalledOnlyArg {
+  /// Only show packages whose name appears in the closure of the current
+  /// system or the user's profile. Matching is by package name, so this is
+  /// approximate
+  #[arg(id = "installed_only", long = "installed-only")]
+  pub value: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PlatformFilterArg {
+  /// Only show packages that support this system, e.g. aarch64-darwin.
+  /// Defaults to the system nh was built for
+  #[arg(id = "platform", long = "platform", value_name = "SYSTEM")]
+  pub platform: Option<String>,
+
+  /// Show packages for all platforms instead of only the local system
+  #[arg(
+    id = "all_platforms",
+    long = "all-platforms",
+    conflicts_with = "platform"
+  )]
+  pub all_platforms: bool,
+}
+
+impl PlatformFilterArg {
+  /// The system results are filtered by, or `None` with `--all-platforms`.
+  #[must_use]
+  pub fn system(&self) -> Option<String> {
+    if self.all_platforms {
+      None
+    } else {
+      Some(self.platform.clone().unwrap_or_else(local_system))
+    }
+  }
+
+  const fn is_set(&self) -> bool {
+    self.platform.is_some() || self.all_platforms
+  }
+}
+
+/// The Nix system double of the machine nh was built for, e.g.
+/// `x86_64-linux` or `aarch64-darwin`.
+fn local_system() -> String {
+  let os = match std::env::consts::OS {
+    "macos" => "darwin",
+    os => os,
+  };
+  format!("{}-{os}", std::env::consts::ARCH)
+}
+
 #[derive(Args, Debug, Clone, Copy)]
 pub struct DaysArg {
   /// Search GitHub results updated in the last n days (default: 15).
@@ -217,6 +312,9 @@ pub enum ResolvedSearchMode<'a> {
     channel:   &'a str,
     limit:     u64,
     platforms: bool,
+    system:    Option<String>,
+    first:     bool,
+    installed: bool,
     query:     &'a [String],
   },
   Options {
@@ -235,6 +333,33 @@ pub enum ResolvedSearchMode<'a> {
 }
 
 impl SearchArgs {
+  /// With `--stdin`, read the query from stdin into the selected mode.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if stdin cannot be read or contains no query.
+  pub fn read_stdin_query(&mut self) -> Result<()> {
+    if !self.stdin {
+      return Ok(());
+    }
+
+    let mut input = String::new();
+    std::io::stdin()
+      .read_to_string(&mut input)
+      .wrap_err("Failed to read the query from stdin")?;
+    let query = stdin_query(&input)?;
+
+    match &mut self.mode {
+      Some(SearchMode::Packages(args)) => args.query = query,
+      Some(SearchMode::Options(args)) => args.query = query,
+      Some(SearchMode::Offline(args)) => args.query = query,
+      Some(SearchMode::Prs(args)) => args.query = query,
+      Some(SearchMode::Issues(args)) => args.query = query,
+      None => self.query = query,
+    }
+    Ok(())
+  }
+
   /// Resolve explicit subcommands and shorthand query arguments into one mode.
   ///
   /// # Errors
@@ -248,6 +373,9 @@ impl SearchArgs {
           channel:   &args.channel.value,
           limit:     args.limit.value,
           platforms: args.platforms.value,
+          system:    args.platform_filter.system(),
+          first:     args.first.value,
+          installed: args.installed_only.value,
           query:     &args.query,
         })
       },
@@ -286,6 +414,9 @@ impl SearchArgs {
           channel:   &self.channel.value,
           limit:     self.limit.value,
           platforms: self.platforms.value,
+          system:    self.platform_filter.system(),
+          first:     self.first.value,
+          installed: self.installed_only.value,
           query:     &self.query,
         })
       },
@@ -293,6 +424,15 @@ impl SearchArgs {
         if self.platforms.value {
           bail!("--platforms only applies to package search");
         }
+        if self.platform_filter.is_set() {
+          bail!("--platform and --all-platforms only apply to package search");
+        }
+        if self.first.value {
+          bail!("--first only applies to package search");
+        }
+        if self.installed_only.value {
+          bail!("--installed-only only applies to package search");
+        }
 
         Ok(ResolvedSearchMode::Options {
           channel: &self.channel.value,
@@ -305,11 +445,27 @@ impl SearchArgs {
   }
 }
 
+/// Split a query read from stdin into words, like a query given as arguments.
+fn stdin_query(input: &str) -> Result<Vec<String>> {
+  let query: Vec<String> =
+    input.split_whitespace().map(ToOwned::to_owned).collect();
+  if query.is_empty() {
+    bail!("no query provided on stdin");
+  }
+  Ok(query)
+}
+
 #[cfg(test)]
 mod tests {
   use clap::{Parser, Subcommand, error::ErrorKind};
 
-  use super::{SearchArgs, SearchDefault, SearchMode};
+  use super::{
+    SearchArgs,
+    SearchDefault,
+    SearchMode,
+    local_system,
+    stdin_query,
+  };
 
   #[derive(Debug, Parser)]
   struct TestCli {
@@ -418,6 +574,14 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn no_hyperlinks_parses_after_subcommand() -> clap::error::Result<()> {
+    let args = parse_search(&["search", "prs", "hello", "--no-hyperlinks"])?;
+
+    assert!(args.no_hyperlinks);
+    Ok(())
+  }
+
   #[test]
   fn shorthand_flags_parse_after_query() -> clap::error::Result<()> {
     let args = parse_search(&[
@@ -452,6 +616,100 @@ mod tests {
     Ok(())
   }
 
+  #[test]
+  fn platform_filter_defaults_to_local_system() -> clap::error::Result<()> {
+    let args = parse_search(&["search", "hello"])?;
+
+    assert_eq!(args.platform_filter.system(), Some(local_system()));
+    Ok(())
+  }
+
+  #[test]
+  fn platform_filter_uses_requested_system() -> clap::error::Result<()> {
+    let args = parse_search(&[
+      "search",
+      "packages",
+      "hello",
+      "--platform",
+      "aarch64-darwin",
+    ])?;
+
+    match args.mode {
+      Some(SearchMode::Packages(packages)) => {
+        assert_eq!(
+          packages.platform_filter.system().as_deref(),
+          Some("aarch64-darwin")
+        );
+      },
+      other => {
+        return Err(clap::Error::raw(
+          ErrorKind::InvalidValue,
+          format!("expected packages mode, got {other:?}"),
+        ));
+      },
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn all_platforms_disables_platform_filter() -> clap::error::Result<()> {
+    let args = parse_search(&["search", "hello", "--all-platforms"])?;
+    assert_eq!(args.platform_filter.system(), None);
+
+    let err = parse_search_error(&[
+      "search",
+      "hello",
+      "--all-platforms",
+      "--platform",
+      "x86_64-linux",
+    ])?;
+    assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    Ok(())
+  }
+
+  #[test]
+  fn first_conflicts_with_json() -> clap::error::Result<()> {
+    let err = parse_search_error(&[
+      "search", "packages", "hello", "--first", "--json",
+    ])?;
+
+    assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    Ok(())
+  }
+
+  #[test]
+  fn stdin_replaces_required_query() -> clap::error::Result<()> {
+    let args = parse_search(&["search", "packages", "--stdin", "--json"])?;
+    assert!(args.stdin);
+    assert!(args.json);
+
+    let args = parse_search(&["search", "--stdin"])?;
+    assert!(args.stdin);
+    assert!(args.query.is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn stdin_conflicts_with_query() -> clap::error::Result<()> {
+    let err = parse_search_error(&["search", "packages", "hello", "--stdin"])?;
+
+    assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    Ok(())
+  }
+
+  #[test]
+  fn stdin_query_splits_words_and_rejects_empty_input() {
+    assert_eq!(
+      stdin_query("ripgrep\n").ok(),
+      Some(vec!["ripgrep".to_owned()])
+    );
+    assert_eq!(
+      stdin_query("  rust analyzer ").ok(),
+      Some(vec!["rust".to_owned(), "analyzer".to_owned()])
+    );
+    assert!(stdin_query(" \n").is_err());
+  }
+
   #[test]
   fn options_reject_platforms() -> clap::error::Result<()> {
     let err =
@@ -11,6 +11,9 @@ const DEPRECATED_VERSIONS: &[&str] = &[
   "nixos-25.11",
 ];
 
+/// A release branch to show in the error for an unsupported channel.
+const EXAMPLE_RELEASE: &str = "nixos-26.05";
+
 /// Validates the channel, applying fallback for deprecated versions.
 ///
 /// # Returns
@@ -32,11 +35,37 @@ pub fn validate(channel: &str) -> Result<String> {
     channel = "nixos-unstable".to_string();
   }
   if !supported_branch(&channel) {
-    bail!("Channel {channel} is not supported!");
+    let hint = suggest_channel(&channel)
+      .map(|suggestion| format!(" Did you mean '{suggestion}'?"))
+      .unwrap_or_default();
+    bail!(
+      "Channel {channel} is not supported!{hint} Supported channels are \
+       'nixos-unstable' and releases like '{EXAMPLE_RELEASE}'."
+    );
   }
   Ok(channel)
 }
 
+/// Guess the supported channel a user meant, e.g. `nixos-24.11` for `24.11`
+/// or `nixos-unstable` for `nixpkgs-unstable` and `nixos-unstable-small`.
+fn suggest_channel(channel: &str) -> Option<String> {
+  let core = channel
+    .trim_end_matches("-darwin")
+    .trim_end_matches("-small")
+    .trim_start_matches("nixpkgs-")
+    .trim_start_matches("nixos-");
+
+  match core {
+    "unstable" | "nixpkgs" | "master" => Some("nixos-unstable".to_owned()),
+    version => {
+      let suggestion = format!("nixos-{version}");
+      let known = DEPRECATED_VERSIONS.contains(&suggestion.as_str())
+        || supported_branch(&suggestion);
+      (known && suggestion != channel).then_some(suggestion)
+    },
+  }
+}
+
 fn supported_branch<S: AsRef<str>>(branch: S) -> bool {
   let branch = branch.as_ref();
 
@@ -77,3 +106,27 @@ fn test_supported_branch() {
   assert!(!supported_branch("nixpkgs-darwin"));
   assert!(!supported_branch("nixpks-21.11-darwin"));
 }
+
+#[test]
+fn test_suggest_channel() {
+  assert_eq!(suggest_channel("24.11").as_deref(), Some("nixos-24.11"));
+  assert_eq!(suggest_channel("26.05").as_deref(), Some("nixos-26.05"));
+  assert_eq!(
+    suggest_channel("unstable").as_deref(),
+    Some("nixos-unstable")
+  );
+  assert_eq!(
+    suggest_channel("nixos-unstable-small").as_deref(),
+    Some("nixos-unstable")
+  );
+  assert_eq!(
+    suggest_channel("nixpkgs-unstable").as_deref(),
+    Some("nixos-unstable")
+  );
+  assert_eq!(
+    suggest_channel("nixpkgs-26.05-darwin").as_deref(),
+    Some("nixos-26.05")
+  );
+  assert_eq!(suggest_channel("nixos-26"), None);
+  assert_eq!(suggest_channel("foo"), None);
+}
@@ -0,0 +1,101 @@
+use std::{collections::HashSet, env, path::Path};
+
+use color_eyre::{Result, eyre::bail};
+use nh_core::util::get_closure_paths;
+use tracing::debug;
+
+/// Length of the hash part of a store path name, plus the separating dash.
+const STORE_HASH_PREFIX_LEN: usize = 33;
+
+/// Collect the package names of everything reachable from the current system
+/// and the user's profiles.
+///
+/// Names are derived from store path names the way Nix splits derivation
+/// names, so this is approximate: a package counts as installed if any store
+/// path with the same pname is in one of the closures.
+///
+/// # Errors
+///
+/// Returns an error if no profile exists or the closures cannot be queried.
+pub fn installed_pnames() -> Result<HashSet<String>> {
+  let mut roots = vec![String::from("/run/current-system")];
+  if let Ok(user) = env::var("USER") {
+    roots.push(format!("/etc/profiles/per-user/{user}"));
+  }
+  if let Some(home) = env::var_os("HOME").filter(|home| !home.is_empty()) {
+    roots.push(Path::new(&home).join(".nix-profile").display().to_string());
+  }
+
+  let roots: Vec<&Path> = roots
+    .iter()
+    .map(Path::new)
+    .filter(|root| root.exists())
+    .collect();
+  debug!(?roots, "Collecting installed packages");
+  if roots.is_empty() {
+    bail!("No system or user profile found to compare search results against");
+  }
+
+  Ok(
+    get_closure_paths(&roots)?
+      .iter()
+      .filter_map(|path| store_path_pname(path))
+      .map(ToOwned::to_owned)
+      .collect(),
+  )
+}
+
+/// The pname of a store path, e.g. `ripgrep` for
+/// `/nix/store/<hash>-ripgrep-14.1.1`.
+///
+/// Like Nix's `DrvName`, the version starts at the first dash that is not
+/// followed by a letter.
+fn store_path_pname(path: &Path) -> Option<&str> {
+  let name = path.file_name()?.to_str()?.get(STORE_HASH_PREFIX_LEN..)?;
+  let pname = name
+    .match_indices('-')
+    .find(|(i, _)| {
+      !name[i + 1..]
+        .chars()
+        .next()
+        .is_some_and(char::is_alphabetic)
+    })
+    .map_or(name, |(i, _)| &name[..i]);
+  (!pname.is_empty()).then_some(pname)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HASH: &str = "0123456789abcdfghijklmnpqrsvwxyz";
+
+  fn pname(name: &str) -> Option<String> {
+    let path = format!("/nix/store/{HASH}-{name}");
+    store_path_pname(Path::new(&path)).map(ToOwned::to_owned)
+  }
+
+  #[test]
+  fn store_path_pname_strips_version() {
+    assert_eq!(pname("ripgrep-14.1.1").as_deref(), Some("ripgrep"));
+    assert_eq!(
+      pname("python3.12-requests-2.32.3").as_deref(),
+      Some("python3.12-requests")
+    );
+    assert_eq!(
+      pname("gnome-shell-46.2-man").as_deref(),
+      Some("gnome-shell")
+    );
+  }
+
+  #[test]
+  fn store_path_pname_keeps_unversioned_names() {
+    assert_eq!(pname("etc").as_deref(), Some("etc"));
+    assert_eq!(pname("system-path").as_deref(), Some("system-path"));
+  }
+
+  #[test]
+  fn store_path_pname_rejects_non_store_names() {
+    assert_eq!(store_path_pname(Path::new("/run/current-system")), None);
+  }
+}
@@ -1,4 +1,6 @@
-use color_eyre::Result;
+use std::collections::HashSet;
+
+use color_eyre::{Result, eyre::bail};
 use elasticsearch_dsl::Search;
 use serde::de::DeserializeOwned;
 use tracing::debug;
@@ -17,14 +19,18 @@ use crate::{
   },
 };
 
+/// How many packages are fetched when they are filtered locally, so that
+/// `--limit` results are left once the filtered-out ones are dropped.
+const FILTERED_FETCH_SIZE: u64 = 500;
+
 pub fn run_packages(
+  mode: &Packages,
   channel: &str,
   limit: u64,
-  platforms: bool,
   json: bool,
   query: &[String],
 ) -> Result<()> {
-  run_online(&Packages { platforms }, channel, limit, json, query)
+  run_online(mode, channel, limit, json, query)
 }
 
 pub fn run_options(
@@ -51,17 +57,27 @@ where
   let query_s = query.join(" ");
   mode.log_query(&query_s);
 
-  let search = mode.search_query(&query_s, limit);
+  let search = mode.search_query(&query_s, mode.fetch_size(limit));
 
-  if !json {
+  if !json && !mode.first_only() {
     mode.print_querying(&channel);
   }
 
-  let (documents, elapsed) = backend::search_documents::<M::Document>(
+  let (mut documents, elapsed) = backend::search_documents::<M::Document>(
     &search,
     &channel,
     mode.contexts(),
   )?;
+  mode.retain(&mut documents);
+  documents.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+
+  if mode.first_only() {
+    let Some(first) = documents.first() else {
+      bail!("No results found for {query_s:?}");
+    };
+    println!("{}", mode.name(first));
+    return Ok(());
+  }
 
   if json {
     return mode.print_json(query_s, channel, elapsed.as_millis(), documents);
@@ -81,6 +97,19 @@ trait OnlineMode {
   fn log_query(&self, query: &str);
   fn search_query(&self, query: &str, limit: u64) -> Search;
   fn contexts(&self) -> SearchContexts;
+  /// How many documents to fetch for `limit` results. Modes that drop
+  /// documents in [`Self::retain`] fetch more, so the limit still applies to
+  /// what is shown.
+  fn fetch_size(&self, limit: u64) -> u64 {
+    limit
+  }
+  /// Drop fetched documents that should not be shown.
+  fn retain(&self, _documents: &mut Vec<Self::Document>) {}
+  /// Whether only the name of the most relevant document is printed.
+  fn first_only(&self) -> bool {
+    false
+  }
+  fn name<'a>(&self, document: &'a Self::Document) -> &'a str;
   fn print_querying(&self, channel: &str);
   fn print_json(
     &self,
@@ -92,8 +121,15 @@ trait OnlineMode {
   fn print_results(&self, channel: &str, documents: &[Self::Document]);
 }
 
-struct Packages {
-  platforms: bool,
+pub struct Packages {
+  /// Show the supported platforms of each package
+  pub platforms: bool,
+  /// Only keep packages that support this system
+  pub system:    Option<String>,
+  /// Print only the attribute name of the most relevant package
+  pub first:     bool,
+  /// Only keep packages whose pname is in this set
+  pub installed: Option<HashSet<String>>,
 }
 
 impl OnlineMode for Packages {
@@ -115,6 +151,31 @@ impl OnlineMode for Packages {
     }
   }
 
+  fn fetch_size(&self, limit: u64) -> u64 {
+    if self.system.is_some() || self.installed.is_some() {
+      limit.max(FILTERED_FETCH_SIZE)
+    } else {
+      limit
+    }
+  }
+
+  fn retain(&self, documents: &mut Vec<Self::Document>) {
+    if let Some(system) = &self.system {
+      documents.retain(|document| supports_system(document, system));
+    }
+    if let Some(installed) = &self.installed {
+      documents.retain(|document| installed.contains(&document.package_pname));
+    }
+  }
+
+  fn first_only(&self) -> bool {
+    self.first
+  }
+
+  fn name<'a>(&self, document: &'a Self::Document) -> &'a str {
+    &document.package_attr_name
+  }
+
   fn print_querying(&self, channel: &str) {
     println!("Querying search.nixos.org, with channel {channel}...");
   }
@@ -166,6 +227,10 @@ impl OnlineMode for Options {
     }
   }
 
+  fn name<'a>(&self, document: &'a Self::Document) -> &'a str {
+    &document.option_name
+  }
+
   fn print_querying(&self, channel: &str) {
     println!("Querying options on search.nixos.org, with channel {channel}...");
   }
@@ -193,3 +258,71 @@ impl OnlineMode for Options {
     render::options::print(channel, documents);
   }
 }
+
+/// Whether a package supports `system`. Packages without platform information
+/// are assumed to support every system, like `meta.platforms` in nixpkgs.
+fn supports_system(document: &PackageSearchResult, system: &str) -> bool {
+  document.package_platforms.is_empty()
+    || document.package_platforms.iter().any(|p| p == system)
+}
+
+#[cfg(test)]
+#[expect(clippy::expect_used, reason = "Fine in tests")]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn package(platforms: &[&str]) -> PackageSearchResult {
+    serde_json::from_value(json!({
+      "package_attr_name": "hello",
+      "package_attr_set": "No package set",
+      "package_pname": "hello",
+      "package_pversion": "2.12.1",
+      "package_platforms": platforms,
+      "package_outputs": ["out"],
+      "package_default_output": "out",
+      "package_programs": ["hello"],
+      "package_license_set": [],
+      "package_description": null,
+      "package_longDescription": null,
+      "package_hydra": null,
+      "package_system": "x86_64-linux",
+      "package_homepage": [],
+      "package_position": null,
+    }))
+    .expect("valid package search result")
+  }
+
+  #[test]
+  fn supports_system_matches_listed_platforms() {
+    let package = package(&["x86_64-linux", "aarch64-darwin"]);
+
+    assert!(supports_system(&package, "aarch64-darwin"));
+    assert!(!supports_system(&package, "aarch64-linux"));
+  }
+
+  #[test]
+  fn supports_system_keeps_packages_without_platforms() {
+    assert!(supports_system(&package(&[]), "aarch64-darwin"));
+  }
+
+  #[test]
+  fn filtered_searches_fetch_more_than_the_limit() {
+    let mut packages = Packages {
+      platforms: false,
+      system:    None,
+      first:     true,
+      installed: None,
+    };
+    assert_eq!(packages.fetch_size(30), 30);
+
+    packages.system = Some("aarch64-darwin".to_owned());
+    assert_eq!(packages.fetch_size(30), FILTERED_FETCH_SIZE);
+
+    packages.system = None;
+    packages.installed = Some(HashSet::from(["hello".to_owned()]));
+    assert_eq!(packages.fetch_size(30), FILTERED_FETCH_SIZE);
+    assert_eq!(packages.fetch_size(5000), 5000);
+  }
+}
@@ -1,12 +1,32 @@
-use std::{path::PathBuf, sync::OnceLock};
+use std::{
+  env,
+  fs,
+  io::Write,
+  os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+  path::{Path, PathBuf},
+  sync::OnceLock,
+  time::{Duration, SystemTime},
+};
 
-use nh_core::command::{CommandKind, NixCommand};
+use nh_core::{
+  command::{CommandKind, NixCommand},
+  util,
+};
 use regex::Regex;
-use tracing::warn;
+use tracing::{debug, warn};
 
 static HYPERLINKS_SUPPORTED: OnceLock<bool> = OnceLock::new();
 const DIM: &str = "\x1b[2m";
 const RESET: &str = "\x1b[0m";
+const NIXPKGS_PATH_CACHE_FILE: &str = "nixpkgs-path";
+const NIXPKGS_PATH_CACHE_TTL: Duration = Duration::from_hours(1);
+
+/// Never emit OSC 8 hyperlinks, regardless of what the terminal supports.
+///
+/// Must be called before anything is rendered.
+pub fn disable_hyperlinks() {
+  let _ = HYPERLINKS_SUPPORTED.set(false);
+}
 
 pub(super) fn hyperlink(text: &str, link: &str) -> String {
   let hyperlinks =
@@ -68,7 +88,87 @@ pub(super) fn strip_html(html: &str) -> String {
 ///
 /// This path only backs the local `file://` link. The channel-specific source
 /// link is rendered separately, so failure here should not block search output.
+///
+/// Evaluating `<nixpkgs>` is slow, so the result is cached under the nh cache
+/// directory for [`NIXPKGS_PATH_CACHE_TTL`], keyed on `NIX_PATH`.
 pub(super) fn resolve_nixpkgs_path() -> Option<PathBuf> {
+  let cache_file = cache_dir().map(|dir| dir.join(NIXPKGS_PATH_CACHE_FILE));
+  let nix_path = env::var("NIX_PATH").unwrap_or_default();
+
+  if let Some(cache_file) = &cache_file
+    && let Some(path) =
+      read_cached_nixpkgs_path(cache_file, &nix_path, SystemTime::now())
+  {
+    debug!("Using cached nixpkgs path from {}", cache_file.display());
+    return Some(path);
+  }
+
+  let path = eval_nixpkgs_path()?;
+  if let Some(cache_file) = &cache_file
+    && let Err(err) = write_cached_nixpkgs_path(cache_file, &nix_path, &path)
+  {
+    debug!("Failed to cache nixpkgs path: {err}");
+  }
+  Some(path)
+}
+
+/// `$XDG_CACHE_HOME/nh`, falling back to `~/.cache/nh`.
+///
+/// Caching is disabled when neither variable is set, rather than falling back
+/// to a predictable path in a shared directory such as `/tmp`.
+fn cache_dir() -> Option<PathBuf> {
+  let dir = util::cache_dir();
+  if dir.is_none() {
+    warn!("Neither XDG_CACHE_HOME nor HOME is set, not caching search data");
+  }
+  dir
+}
+
+/// Read a cached nixpkgs path, ignoring it if it is older than the TTL, was
+/// resolved for a different `NIX_PATH`, or no longer exists.
+fn read_cached_nixpkgs_path(
+  cache_file: &Path,
+  nix_path: &str,
+  now: SystemTime,
+) -> Option<PathBuf> {
+  let modified = fs::metadata(cache_file).ok()?.modified().ok()?;
+  if now.duration_since(modified).ok()? > NIXPKGS_PATH_CACHE_TTL {
+    return None;
+  }
+
+  let contents = fs::read_to_string(cache_file).ok()?;
+  let (cached_nix_path, path) = contents.split_once('\n')?;
+  let path = PathBuf::from(path.trim_end());
+  (cached_nix_path == nix_path && path.exists()).then_some(path)
+}
+
+/// Write the cache file. The cache reveals what was searched for, so the
+/// directory is made private to the user and the file readable only by them,
+/// including when they already existed with looser permissions.
+fn write_cached_nixpkgs_path(
+  cache_file: &Path,
+  nix_path: &str,
+  path: &Path,
+) -> std::io::Result<()> {
+  if let Some(dir) = cache_file.parent() {
+    fs::DirBuilder::new()
+      .recursive(true)
+      .mode(0o700)
+      .create(dir)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+  }
+
+  let mut file = fs::OpenOptions::new()
+    .create(true)
+    .write(true)
+    .truncate(true)
+    .mode(0o600)
+    .open(cache_file)?;
+  file.set_permissions(fs::Permissions::from_mode(0o600))?;
+  write!(file, "{nix_path}\n{}\n", path.display())
+}
+
+fn eval_nixpkgs_path() -> Option<PathBuf> {
   let output = nixpkgs_path_command().output().ok()?;
   if !output.status.success() {
     return None;
@@ -95,6 +195,66 @@ fn nixpkgs_path_command() -> NixCommand {
 mod tests {
   use super::*;
 
+  #[test]
+  fn cached_nixpkgs_path_round_trips() -> std::io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let cache_file = dir.path().join("nh").join(NIXPKGS_PATH_CACHE_FILE);
+
+    write_cached_nixpkgs_path(&cache_file, "nixpkgs=/a", dir.path())?;
+
+    assert_eq!(
+      read_cached_nixpkgs_path(&cache_file, "nixpkgs=/a", SystemTime::now()),
+      Some(dir.path().to_path_buf())
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn cache_file_is_private() -> std::io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let cache_dir = dir.path().join("nh");
+    fs::create_dir(&cache_dir)?;
+    fs::set_permissions(&cache_dir, fs::Permissions::from_mode(0o755))?;
+    let cache_file = cache_dir.join(NIXPKGS_PATH_CACHE_FILE);
+
+    write_cached_nixpkgs_path(&cache_file, "nixpkgs=/a", dir.path())?;
+
+    let file_mode = fs::metadata(&cache_file)?.permissions().mode();
+    assert_eq!(0, file_mode & 0o077);
+    let dir_mode = fs::metadata(&cache_dir)?.permissions().mode();
+    assert_eq!(0, dir_mode & 0o077);
+    Ok(())
+  }
+
+  #[test]
+  fn cached_nixpkgs_path_is_invalidated() -> std::io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let cache_file = dir.path().join(NIXPKGS_PATH_CACHE_FILE);
+    write_cached_nixpkgs_path(&cache_file, "nixpkgs=/a", dir.path())?;
+
+    // A different NIX_PATH
+    assert_eq!(
+      read_cached_nixpkgs_path(&cache_file, "nixpkgs=/b", SystemTime::now()),
+      None
+    );
+
+    // An expired entry
+    let later = SystemTime::now() + NIXPKGS_PATH_CACHE_TTL * 2;
+    assert_eq!(
+      read_cached_nixpkgs_path(&cache_file, "nixpkgs=/a", later),
+      None
+    );
+
+    // A path that no longer exists
+    let missing = dir.path().join("gone");
+    write_cached_nixpkgs_path(&cache_file, "nixpkgs=/a", &missing)?;
+    assert_eq!(
+      read_cached_nixpkgs_path(&cache_file, "nixpkgs=/a", SystemTime::now()),
+      None
+    );
+    Ok(())
+  }
+
   #[test]
   fn nixpkgs_path_lookup_is_local_and_offline() {
     let argv = nixpkgs_path_command().argv();
@@ -4,3 +4,5 @@ pub mod issues;
 pub mod options;
 pub mod packages;
 pub mod prs;
+
+pub use common::disable_hyperlinks;
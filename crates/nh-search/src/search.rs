@@ -1,24 +1,47 @@
 use color_eyre::Result;
 use tracing::trace;
 
-use crate::{args, issues, offline, online, prs};
+use crate::{args, installed, issues, offline, online, prs, render};
 
 impl args::SearchArgs {
   /// Execute the search subcommand.
   ///
   /// # Errors
   ///
-  /// Returns an error if no query is provided when using the shorthand form,
-  /// if the channel is unsupported, or if the underlying search request fails.
-  pub fn run(&self) -> Result<()> {
+  /// Returns an error if no query is provided when using the shorthand form
+  /// or on stdin, if the channel is unsupported, or if the underlying search
+  /// request fails.
+  pub fn run(mut self) -> Result<()> {
     trace!("args: {self:?}");
+    self.read_stdin_query()?;
+    if self.no_hyperlinks {
+      render::disable_hyperlinks();
+    }
     match self.resolved_mode()? {
       args::ResolvedSearchMode::Packages {
         channel,
         limit,
         platforms,
+        system,
+        first,
+        installed,
         query,
-      } => online::run_packages(channel, limit, platforms, self.json, query),
+      } => {
+        let installed =
+          installed.then(installed::installed_pnames).transpose()?;
+        online::run_packages(
+          &online::Packages {
+            platforms,
+            system,
+            first,
+            installed,
+          },
+          channel,
+          limit,
+          self.json,
+          query,
+        )
+      },
       args::ResolvedSearchMode::Options {
         channel,
         limit,
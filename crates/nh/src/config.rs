@@ -0,0 +1,197 @@
+use std::env;
+
+use clap::Args;
+use nh_core::{command::ElevationStrategy, util};
+use nh_installable::{CommandContext, InstallableArgs};
+use yansi::{Color, Paint};
+
+use crate::{NH_REV, NH_VERSION, Result};
+
+/// Nix variables that influence how nh invokes Nix, shown alongside the
+/// `NH_*` variables.
+const NIX_ENV_VARS: &[&str] = &[
+  "FLAKE",
+  "NIX_CONFIG",
+  "NIX_PATH",
+  "NIX_REMOTE",
+  "NIX_SSHOPTS",
+  "NIX_SUDOOPTS",
+  "NIX_USER_CONF_FILES",
+];
+
+/// Nix settings whose values are credentials, or point at them. Their values
+/// are hidden when printing `NIX_CONFIG`, as the output of `nh config` often
+/// ends up in bug reports.
+const SECRET_NIX_SETTINGS: &[&str] =
+  &["access-tokens", "netrc-file", "secret-key-files"];
+
+/// Parts of variable names that suggest the value is a credential.
+const SECRET_VAR_MARKERS: &[&str] = &["TOKEN", "PASSWORD", "SECRET"];
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Args)]
+/// Show nh's effective configuration and environment
+pub struct ConfigArgs {}
+
+impl ConfigArgs {
+  /// Print the detected Nix installation, the installable each subcommand
+  /// would use, the elevation strategy and the relevant environment.
+  ///
+  /// Resolution failures are printed instead of returned, so that this can be
+  /// used to debug a broken setup.
+  ///
+  /// # Errors
+  ///
+  /// This currently never fails.
+  #[allow(clippy::unnecessary_wraps, reason = "Matches the other commands")]
+  pub fn run(&self, elevation: &ElevationStrategy) -> Result<()> {
+    section("nh");
+    entry(
+      "version",
+      NH_REV.map_or_else(
+        || NH_VERSION.to_string(),
+        |rev| format!("{NH_VERSION} ({rev})"),
+      ),
+    );
+
+    section("Nix");
+    entry("variant", util::get_nix_variant().display_name());
+    entry(
+      "version",
+      util::get_nix_version().unwrap_or_else(|err| failure(&err)),
+    );
+    entry(
+      "experimental features",
+      util::get_nix_experimental_features().map_or_else(
+        |err| failure(&err),
+        |features| {
+          let mut features: Vec<_> = features.into_iter().collect();
+          features.sort_unstable();
+          features.join(" ")
+        },
+      ),
+    );
+
+    section("Elevation");
+    entry("strategy", elevation_name(elevation));
+    if !matches!(elevation, ElevationStrategy::None) {
+      entry(
+        "program",
+        elevation
+          .resolve()
+          .map_or_else(|err| failure(&err), |path| path.display().to_string()),
+      );
+    }
+
+    section("Installables");
+    for (name, context) in [
+      ("os", CommandContext::Os),
+      ("home", CommandContext::Home),
+      ("darwin", CommandContext::Darwin),
+    ] {
+      let installable = InstallableArgs::Unspecified { attr: None }
+        .resolve_or_default(context)
+        .map_or_else(|err| failure(&err), |i| i.to_args().join(" "));
+      entry(name, installable);
+    }
+
+    section("Environment");
+    let mut vars: Vec<_> = env::vars()
+      .filter(|(key, _)| {
+        key.starts_with("NH_") || NIX_ENV_VARS.contains(&key.as_str())
+      })
+      .collect();
+    vars.sort_unstable();
+
+    if vars.is_empty() {
+      println!("  {}", Paint::new("(none set)").dim());
+    }
+    for (key, value) in vars {
+      let value = redact(&key, &value);
+      entry(&key, value);
+    }
+
+    Ok(())
+  }
+}
+
+fn section(title: &str) {
+  println!("{}", Paint::new(title).fg(Color::Blue).bold());
+}
+
+fn entry(key: &str, value: impl AsRef<str>) {
+  println!("  {}: {}", Paint::new(key).bold(), value.as_ref());
+}
+
+/// Hide credentials in the value of the environment variable `key`.
+fn redact(key: &str, value: &str) -> String {
+  if SECRET_VAR_MARKERS.iter().any(|marker| key.contains(marker)) {
+    return REDACTED.to_string();
+  }
+  if key != "NIX_CONFIG" {
+    return value.to_string();
+  }
+
+  value
+    .lines()
+    .map(|line| {
+      let Some((setting, _)) = line.split_once('=') else {
+        return line.to_string();
+      };
+      let name = setting.trim();
+      let name = name.strip_prefix("extra-").unwrap_or(name);
+      if SECRET_NIX_SETTINGS.contains(&name) {
+        format!("{setting}= {REDACTED}")
+      } else {
+        line.to_string()
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Render an error as a single line, keeping the outermost message only.
+fn failure(err: &color_eyre::Report) -> String {
+  let message = err.to_string();
+  let first_line = message.lines().next().unwrap_or_default();
+  Paint::new(format!("unavailable ({first_line})"))
+    .fg(Color::Red)
+    .to_string()
+}
+
+fn elevation_name(elevation: &ElevationStrategy) -> String {
+  match elevation {
+    ElevationStrategy::Auto => "auto".to_string(),
+    ElevationStrategy::Passwordless => "passwordless".to_string(),
+    ElevationStrategy::None => "none".to_string(),
+    ElevationStrategy::Prefer(path) => format!("prefer {}", path.display()),
+    ElevationStrategy::Force(program) => format!("force {program}"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn redacts_credentials_in_nix_config() {
+    let config = "experimental-features = nix-command flakes\naccess-tokens = \
+                  github.com=ghp_secret\nextra-netrc-file=/run/netrc";
+    assert_eq!(
+      redact("NIX_CONFIG", config),
+      [
+        "experimental-features = nix-command flakes",
+        "access-tokens = <redacted>",
+        "extra-netrc-file= <redacted>",
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn redacts_credential_variables() {
+    assert_eq!(redact("NH_GITHUB_TOKEN", "ghp_secret"), "<redacted>");
+    assert_eq!(redact("NH_FLAKE", "/etc/nixos"), "/etc/nixos");
+  }
+}
@@ -2,7 +2,7 @@ use anstyle::Style;
 use clap::{Parser, Subcommand, builder::Styles};
 use clap_verbosity_flag::InfoLevel;
 use nh_core::{
-  checks::{FeatureRequirements, NoFeatures},
+  checks::{FeatureRequirements, FlakeFeatures, NoFeatures},
   command::ElevationStrategy,
 };
 use nh_nixos;
@@ -56,6 +56,47 @@ pub struct Main {
   /// elevation programs in order: doas, sudo, run0, pkexec)
   pub elevation_strategy: Option<nh_core::command::ElevationStrategyArg>,
 
+  #[arg(
+    long,
+    global = true,
+    env = "NH_PRINT_COMMANDS",
+    value_parser = clap::builder::BoolishValueParser::new()
+  )]
+  /// Print every external command (nix, ssh, activation, elevation) to
+  /// stderr before running it, regardless of the log level
+  pub print_commands: bool,
+
+  #[arg(
+    long,
+    global = true,
+    env = "NH_DEFAULT_YES",
+    value_parser = clap::builder::BoolishValueParser::new()
+  )]
+  /// Make pressing Enter at an `--ask` prompt proceed instead of declining.
+  /// Typing 'n' still declines
+  pub default_yes: bool,
+
+  #[arg(long, global = true, value_name = "OPTS", allow_hyphen_values = true)]
+  /// Extra options passed to every ssh invocation, including Nix copying over
+  /// SSH. Takes precedence over `NH_SSHOPTS` and `NIX_SSHOPTS`
+  pub ssh_opts: Option<String>,
+
+  #[arg(long, global = true, env = "NH_DIFF_TOOL", value_name = "COMMAND")]
+  /// Pipe package diffs into this command, e.g. `less -R`, instead of
+  /// printing them. Falls back to printing if the command cannot be run
+  pub diff_tool: Option<String>,
+
+  #[arg(
+    long,
+    global = true,
+    env = "NH_LOG_FORMAT",
+    value_enum,
+    default_value_t
+  )]
+  /// Format of nh's own log output. 'json' prints one JSON object per line,
+  /// for log pipelines
+  pub nh_log_format: crate::logging::LogFormat,
+
   #[command(subcommand)]
   pub command: NHCommand,
 }
@@ -68,6 +109,9 @@ pub enum NHCommand {
   Darwin(nh_darwin::args::DarwinArgs),
   Search(nh_search::args::SearchArgs),
   Clean(nh_clean::args::CleanProxy),
+  Update(nh_core::update::UpdateCommandArgs),
+  Config(crate::config::ConfigArgs),
+  Doctor(nh_core::doctor::DoctorArgs),
 }
 
 impl NHCommand {
@@ -77,7 +121,11 @@ impl NHCommand {
       Self::Os(args) => args.get_feature_requirements(),
       Self::Home(args) => args.get_feature_requirements(),
       Self::Darwin(args) => args.get_feature_requirements(),
-      Self::Search(..) | Self::Clean(..) => Box::new(NoFeatures),
+      Self::Update(..) => Box::new(FlakeFeatures),
+      Self::Search(..)
+      | Self::Clean(..)
+      | Self::Config(..)
+      | Self::Doctor(..) => Box::new(NoFeatures),
     }
   }
 
@@ -96,8 +144,25 @@ impl NHCommand {
       Self::Os(args) => args.run(elevation),
       Self::Search(args) => args.run(),
       Self::Clean(proxy) => proxy.command.run(elevation),
+      Self::Update(args) => args.run(),
       Self::Home(args) => args.run(),
       Self::Darwin(args) => args.run(elevation),
+      Self::Config(args) => args.run(&elevation),
+      Self::Doctor(args) => args.run(&elevation),
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use clap::CommandFactory;
+
+  use super::*;
+
+  #[test]
+  fn cli_is_valid() {
+    // Clap only reports clashing ids of global and flattened arguments by
+    // panicking when a command is parsed
+    Main::command().debug_assert();
+  }
+}
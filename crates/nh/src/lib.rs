@@ -2,7 +2,11 @@ use std::str::FromStr;
 
 use color_eyre::Result;
 use nh_core::command::{ElevationStrategy, ElevationStrategyArg};
+pub use nh_core::outcome::{DiffSummary, Outcome};
 
+use crate::interface::NHCommand;
+
+pub mod config;
 pub mod interface;
 pub mod logging;
 
@@ -45,17 +49,17 @@ pub fn main() -> Result<()> {
   }
 
   // Set up logging
-  crate::logging::setup_logging(args.verbosity)?;
+  crate::logging::setup_logging(args.verbosity, args.nh_log_format)?;
   tracing::debug!("{args:#?}");
   tracing::debug!(%NH_VERSION, ?NH_REV);
-
-  // Check Nix version upfront
-  nh_core::checks::verify_nix_environment()?;
-
-  // Once we assert required Nix features, validate NH environment checks
-  // For now, this is just NH_* variables being set. More checks may be
-  // added to setup_environment in the future.
-  nh_core::checks::verify_variables()?;
+  nh_core::command::set_print_commands(args.print_commands);
+  nh_core::confirm::set_default_yes(args.default_yes);
+  if let Some(opts) = args.ssh_opts.clone() {
+    nh_remote::set_ssh_opts(opts);
+  }
+  if let Some(tool) = args.diff_tool.clone() {
+    nh_diff::set_diff_tool(tool);
+  }
 
   let elevation =
     args
@@ -72,5 +76,41 @@ pub fn main() -> Result<()> {
         }
       });
 
-  args.command.run(elevation)
+  run_command(args.command, elevation).map(drop)
+}
+
+/// Run `command` and report what it did, for embedding nh in other tools.
+///
+/// This runs the same checks as the `nh` binary before the command, but does
+/// not set up logging or apply global flags such as `--default-yes`; use the
+/// `nh_core` setters for those. Output is still printed as usual.
+///
+/// The outcome is recorded per thread, so calls on different threads each get
+/// their own.
+///
+/// # Errors
+///
+/// Returns an error if Nix environment validation, environment checks, or the
+/// command fails.
+pub fn run_command(
+  command: NHCommand,
+  elevation: ElevationStrategy,
+) -> Result<Outcome> {
+  // Check Nix version upfront. Commands without feature requirements, like
+  // `nh search`, `nh clean`, `nh config` and `nh doctor`, have to keep
+  // working on installations that fail this check.
+  nh_core::exit::enter(nh_core::exit::Phase::Precheck);
+  if command.get_feature_requirements().requires_nix_version() {
+    nh_core::checks::verify_nix_environment()?;
+  }
+
+  // Once we assert required Nix features, validate NH environment checks
+  // For now, this is just NH_* variables being set. More checks may be
+  // added to setup_environment in the future.
+  nh_core::checks::verify_variables()?;
+  nh_core::exit::leave();
+
+  let (result, outcome) = nh_core::outcome::capture(|| command.run(elevation));
+  result?;
+  Ok(outcome)
 }
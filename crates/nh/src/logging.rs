@@ -1,5 +1,15 @@
+use std::fmt::Debug;
+
+use chrono::{SecondsFormat, Utc};
+use clap::ValueEnum;
 use clap_verbosity_flag::InfoLevel;
-use tracing::{Event, Level, Subscriber};
+use serde_json::{Map, Value, json};
+use tracing::{
+  Event,
+  Level,
+  Subscriber,
+  field::{Field, Visit},
+};
 use tracing_subscriber::{
   EnvFilter,
   filter::LevelFilter,
@@ -11,6 +21,16 @@ use yansi::{Color, Paint};
 
 use crate::Result;
 
+#[derive(ValueEnum, Clone, Copy, Default, Debug)]
+pub enum LogFormat {
+  /// Human-readable output
+  #[default]
+  Pretty,
+  /// One JSON object per line, with timestamp, level, target, message and
+  /// any other fields of the event
+  Json,
+}
+
 struct InfoFormatter;
 
 impl<S, N> FormatEvent<S, N> for InfoFormatter
@@ -56,6 +76,91 @@ where
   }
 }
 
+struct JsonFormatter;
+
+impl<S, N> FormatEvent<S, N> for JsonFormatter
+where
+  S: Subscriber + for<'a> LookupSpan<'a>,
+  N: for<'a> FormatFields<'a> + 'static,
+{
+  fn format_event(
+    &self,
+    _ctx: &fmt::FmtContext<'_, S, N>,
+    mut writer: fmt::format::Writer,
+    event: &Event,
+  ) -> std::fmt::Result {
+    let mut fields = JsonFields::default();
+    event.record(&mut fields);
+
+    let metadata = event.metadata();
+    let line = json_line(
+      &Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+      *metadata.level(),
+      metadata.target(),
+      fields,
+    );
+
+    writeln!(writer, "{line}")
+  }
+}
+
+/// The fields of an event, with the message kept apart from the rest.
+#[derive(Default)]
+struct JsonFields {
+  message: Option<String>,
+  fields:  Map<String, Value>,
+}
+
+impl JsonFields {
+  fn insert(&mut self, field: &Field, value: Value) {
+    if field.name() == "message" {
+      self.message = Some(match value {
+        Value::String(message) => message,
+        other => other.to_string(),
+      });
+    } else {
+      self.fields.insert(field.name().to_owned(), value);
+    }
+  }
+}
+
+impl Visit for JsonFields {
+  fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+    self.insert(field, Value::String(format!("{value:?}")));
+  }
+
+  fn record_str(&mut self, field: &Field, value: &str) {
+    self.insert(field, Value::String(value.to_owned()));
+  }
+
+  fn record_i64(&mut self, field: &Field, value: i64) {
+    self.insert(field, value.into());
+  }
+
+  fn record_u64(&mut self, field: &Field, value: u64) {
+    self.insert(field, value.into());
+  }
+
+  fn record_bool(&mut self, field: &Field, value: bool) {
+    self.insert(field, value.into());
+  }
+}
+
+fn json_line(
+  timestamp: &str,
+  level: Level,
+  target: &str,
+  fields: JsonFields,
+) -> Value {
+  json!({
+    "timestamp": timestamp,
+    "level": level.as_str(),
+    "target": target,
+    "message": fields.message.unwrap_or_default(),
+    "fields": fields.fields,
+  })
+}
+
 /// Configure error reporting and tracing output.
 ///
 /// # Errors
@@ -64,6 +169,7 @@ where
 /// directives cannot be parsed.
 pub fn setup_logging(
   verbosity: clap_verbosity_flag::Verbosity<InfoLevel>,
+  format: LogFormat,
 ) -> Result<()> {
   color_eyre::config::HookBuilder::default()
     .display_location_section(true)
@@ -84,17 +190,28 @@ pub fn setup_logging(
       }
     });
 
+  let filter = EnvFilter::from_env("NH_LOG")
+    .add_directive(fallback_level.into())
+    .add_directive("dix=WARN".parse()?);
   let layer = fmt::layer()
     .with_writer(std::io::stderr)
     .without_time()
     .compact()
-    .with_line_number(true)
-    .event_format(InfoFormatter)
-    .with_filter(
-      EnvFilter::from_env("NH_LOG")
-        .add_directive(fallback_level.into())
-        .add_directive("dix=WARN".parse()?),
-    );
+    .with_line_number(true);
+  let layer = match format {
+    LogFormat::Pretty => {
+      layer
+        .event_format(InfoFormatter)
+        .with_filter(filter)
+        .boxed()
+    },
+    LogFormat::Json => {
+      layer
+        .event_format(JsonFormatter)
+        .with_filter(filter)
+        .boxed()
+    },
+  };
 
   tracing_subscriber::registry().with(layer).init();
 
@@ -102,3 +219,27 @@ pub fn setup_logging(
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn json_line_separates_message_from_fields() {
+    let fields = JsonFields {
+      message: Some("Building configuration".to_owned()),
+      fields:  Map::from_iter([("cmd".to_owned(), json!("Exec { .. }"))]),
+    };
+
+    assert_eq!(
+      json_line("2025-01-01T00:00:00.000Z", Level::INFO, "nh_core", fields),
+      json!({
+        "timestamp": "2025-01-01T00:00:00.000Z",
+        "level": "INFO",
+        "target": "nh_core",
+        "message": "Building configuration",
+        "fields": { "cmd": "Exec { .. }" },
+      })
+    );
+  }
+}
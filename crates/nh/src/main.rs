@@ -1,5 +1,16 @@
 use color_eyre::Result;
 
 fn main() -> Result<()> {
-  nh::main()
+  let result = nh::main();
+
+  // Exit with a code that tells which phase failed, see "Exit codes" in the
+  // README, instead of collapsing every failure into exit code 1.
+  if let Err(report) = &result
+    && let Some(code) = nh_core::exit::exit_code(report)
+  {
+    eprintln!("Error: {report:?}");
+    std::process::exit(code);
+  }
+
+  result
 }
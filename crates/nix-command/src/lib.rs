@@ -2,7 +2,10 @@ use std::{
   ffi::{OsStr, OsString},
   io::{self, Read, Write},
   process::{Command, ExitStatus, Output, Stdio},
-  sync::mpsc,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+  },
   thread,
   time::{Duration, Instant},
 };
@@ -160,6 +163,30 @@ pub struct UnknownCommand {
   command: String,
 }
 
+static PRINT_COMMANDS: AtomicBool = AtomicBool::new(false);
+
+/// Print every command to stderr before it runs, in a copy-pasteable form.
+pub fn set_print_commands(enabled: bool) {
+  PRINT_COMMANDS.store(enabled, Ordering::Relaxed);
+}
+
+/// Print `command_line` to stderr if [`set_print_commands`] is enabled.
+pub fn print_command_line(command_line: &str) {
+  if PRINT_COMMANDS.load(Ordering::Relaxed) {
+    eprintln!("$ {command_line}");
+  }
+}
+
+/// Print a [`Command`] to stderr if [`set_print_commands`] is enabled.
+pub fn print_command(cmd: &Command) {
+  if PRINT_COMMANDS.load(Ordering::Relaxed) {
+    let cmdline = Exec::cmd(cmd.get_program())
+      .args(cmd.get_args())
+      .to_cmdline_lossy();
+    eprintln!("$ {cmdline}");
+  }
+}
+
 #[derive(Debug)]
 enum PipeEvent {
   Stdout(Vec<u8>),
@@ -432,6 +459,8 @@ impl NixCommand {
     argv
   }
 
+  /// The command as a [`Command`], ready to run. It is printed here with
+  /// [`set_print_commands`], since every caller runs it right away.
   #[must_use]
   pub fn to_std_command(&self) -> Command {
     let argv = self.argv();
@@ -440,6 +469,7 @@ impl NixCommand {
     for (k, v) in &self.env {
       cmd.env(k, v);
     }
+    print_command(&cmd);
     cmd
   }
 
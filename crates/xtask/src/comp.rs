@@ -1,7 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use clap::{CommandFactory, ValueEnum};
-use clap_complete::generate_to;
+use clap_complete::Generator;
 
 const BINARY_NAME: &str = "nh";
 
@@ -37,6 +37,16 @@ const ALL_SHELLS: [CompletionShell; 6] = [
   CompletionShell::Nushell,
 ];
 
+/// Print the shells completions can be generated for, one per line, as they
+/// are spelled on the command line.
+pub fn list() {
+  for shell in ALL_SHELLS {
+    if let Some(value) = shell.to_possible_value() {
+      println!("{}", value.get_name());
+    }
+  }
+}
+
 pub fn generate(
   out_dir: &str,
   shell: Option<CompletionShell>,
@@ -63,41 +73,60 @@ pub fn generate(
   Ok(())
 }
 
+/// Write the completion for `shell` to the file `out`, creating its parent
+/// directories, for build phases where redirecting stdout is awkward.
+pub fn generate_file(shell: CompletionShell, out: &Path) -> Result<(), String> {
+  if let Some(parent) = out.parent().filter(|p| !p.as_os_str().is_empty()) {
+    std::fs::create_dir_all(parent).map_err(|e| {
+      format!("Failed to create directory '{}': {e}", parent.display())
+    })?;
+  }
+
+  let mut cmd = nh::interface::Main::command();
+  write_completion(generator(shell).as_ref(), &mut cmd, out)?;
+
+  println!("Generated {shell} completion to {}", out.display());
+  Ok(())
+}
+
+fn generator(shell: CompletionShell) -> Box<dyn Generator> {
+  match shell {
+    CompletionShell::Bash => Box::new(clap_complete::Shell::Bash),
+    CompletionShell::Elvish => Box::new(clap_complete::Shell::Elvish),
+    CompletionShell::Fish => Box::new(clap_complete::Shell::Fish),
+    CompletionShell::PowerShell => Box::new(clap_complete::Shell::PowerShell),
+    CompletionShell::Zsh => Box::new(clap_complete::Shell::Zsh),
+    CompletionShell::Nushell => Box::new(clap_complete_nushell::Nushell),
+  }
+}
+
+/// Generate the completion into a buffer first, as generating straight into
+/// a file panics if writing fails.
+fn write_completion(
+  generator: &dyn Generator,
+  cmd: &mut clap::Command,
+  out: &Path,
+) -> Result<(), String> {
+  cmd.set_bin_name(BINARY_NAME);
+  cmd.build();
+  let mut buf = Vec::new();
+  generator.generate(cmd, &mut buf);
+  std::fs::write(out, buf)
+    .map_err(|e| format!("Failed to write '{}': {e}", out.display()))
+}
+
 fn generate_single(
   shell: CompletionShell,
   cmd: &mut clap::Command,
   out_dir: &Path,
 ) -> Result<PathBuf, String> {
-  match shell {
-    CompletionShell::Bash => {
-      generate_to(clap_complete::Shell::Bash, cmd, BINARY_NAME, out_dir)
-        .map_err(|e| format!("Failed to generate Bash completion: {e}"))
-    },
-    CompletionShell::Elvish => {
-      generate_to(clap_complete::Shell::Elvish, cmd, BINARY_NAME, out_dir)
-        .map_err(|e| format!("Failed to generate Elvish completion: {e}"))
-    },
-    CompletionShell::Fish => {
-      generate_to(clap_complete::Shell::Fish, cmd, BINARY_NAME, out_dir)
-        .map_err(|e| format!("Failed to generate Fish completion: {e}"))
-    },
-    CompletionShell::PowerShell => {
-      generate_to(clap_complete::Shell::PowerShell, cmd, BINARY_NAME, out_dir)
-        .map_err(|e| format!("Failed to generate PowerShell completion: {e}"))
-    },
-    CompletionShell::Zsh => {
-      generate_to(clap_complete::Shell::Zsh, cmd, BINARY_NAME, out_dir)
-        .map_err(|e| format!("Failed to generate Zsh completion: {e}"))
-        .and_then(|path| {
-          let new_path = path.with_file_name(format!("{BINARY_NAME}.zsh"));
-          std::fs::rename(&path, &new_path)
-            .map_err(|e| format!("Failed to rename Zsh completion: {e}"))?;
-          Ok(new_path)
-        })
-    },
-    CompletionShell::Nushell => {
-      generate_to(clap_complete_nushell::Nushell, cmd, BINARY_NAME, out_dir)
-        .map_err(|e| format!("Failed to generate Nushell completion: {e}"))
-    },
-  }
+  let generator = generator(shell);
+  let file_name = match shell {
+    CompletionShell::Zsh => format!("{BINARY_NAME}.zsh"),
+    _ => generator.file_name(BINARY_NAME),
+  };
+  let path = out_dir.join(file_name);
+  write_completion(generator.as_ref(), cmd, &path)
+    .map_err(|e| format!("Failed to generate {shell} completion: {e}"))?;
+  Ok(path)
 }
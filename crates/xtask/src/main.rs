@@ -29,9 +29,21 @@ enum Command {
     /// Output directory for completions
     #[arg(long, default_value = "comp")]
     out_dir: String,
+    /// Write the completion to this file instead, creating its parent
+    /// directories. Requires a shell
+    #[arg(
+      long,
+      value_name = "FILE",
+      requires = "shell",
+      conflicts_with = "out_dir"
+    )]
+    out:     Option<PathBuf>,
     /// Shell to generate completions for (generates all if not specified)
     #[arg(value_enum)]
     shell:   Option<CompletionShell>,
+    /// List the supported shells instead of generating completions
+    #[arg(long, conflicts_with_all = ["shell", "out_dir", "out"])]
+    list:    bool,
   },
   /// Generate both manpages and completions
   Dist,
@@ -40,13 +52,27 @@ enum Command {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
   let Cli { command } = Cli::parse();
 
+  // --out is relative to where xtask was run, not the project root
+  let cwd = env::current_dir()?;
   env::set_current_dir(project_root())?;
 
   match command {
     Command::Man { out_dir } => {
       man::generate(&out_dir).map_err(std::convert::Into::into)
     },
-    Command::Completions { out_dir, shell } => {
+    Command::Completions { list: true, .. } => {
+      comp::list();
+      Ok(())
+    },
+    Command::Completions {
+      out: Some(out),
+      shell: Some(shell),
+      ..
+    } => {
+      comp::generate_file(shell, &cwd.join(out))
+        .map_err(std::convert::Into::into)
+    },
+    Command::Completions { out_dir, shell, .. } => {
       comp::generate(&out_dir, shell).map_err(std::convert::Into::into)
     },
     Command::Dist => {
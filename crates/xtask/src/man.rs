@@ -75,6 +75,11 @@ pub fn generate(out_dir: &str) -> Result<(), String> {
       "Command-specific flake reference for nh darwin commands. Takes \
        precedence over NH_FLAKE.",
     ),
+    (
+      "NH_HOME_SPECIALISATION_DIR",
+      "Directory containing the Home Manager specialisation file. Defaults to \
+       $XDG_DATA_HOME/home-manager.",
+    ),
     (
       "NH_FILE",
       "Preferred path to a directory/file containing a Nix expression. Chosen \
@@ -93,7 +98,23 @@ pub fn generate(out_dir: &str) -> Result<(), String> {
       "NH_PRESERVE_ENV",
       "Controls whether environment variables marked for preservation are \
        passed to elevated commands. Set to \"0\" to disable, \"1\" to force. \
-       If unset, defaults to enabled.",
+       If unset, defaults to enabled. A comma-separated list of variable \
+       names enables preservation and forwards those variables as well.",
+    ),
+    (
+      "NH_IMPURE",
+      "Equivalent of --impure. Setting to \"1\" evaluates the configuration \
+       impurely, for flake and non-flake configurations alike.",
+    ),
+    (
+      "NH_ACCEPT_FLAKE_CONFIG",
+      "When set to a truthy value, rebuilds pass --accept-flake-config to Nix \
+       unless --ask-flake-config is given. Not used by nh search.",
+    ),
+    (
+      "NH_DEFAULT_YES",
+      "Equivalent of --default-yes. Setting to \"1\" makes Enter proceed at \
+       --ask prompts instead of declining.",
     ),
     (
       "NH_SHOW_ACTIVATION_LOGS",
@@ -106,9 +127,19 @@ pub fn generate(out_dir: &str) -> Result<(), String> {
        (e.g., nh=trace).",
     ),
     (
-      "NH_NOM",
-      "Control whether nix-output-monitor (nom) is enabled for build \
-       processes. Equivalent of --no-nom.",
+      "NH_LOG_FORMAT",
+      "Equivalent of --nh-log-format. Setting to \"json\" prints logs as one \
+       JSON object per line.",
+    ),
+    (
+      "NH_PRINT_COMMANDS",
+      "Equivalent of --print-commands. Setting to \"1\" prints every external \
+       command NH runs to stderr before running it.",
+    ),
+    (
+      "NH_NO_NOM",
+      "When set to a truthy value, disables nix-output-monitor (nom) for \
+       build processes. Equivalent of --no-nom.",
     ),
     (
       "NH_REMOTE_CLEANUP",
@@ -118,7 +149,12 @@ pub fn generate(out_dir: &str) -> Result<(), String> {
     (
       "NH_SSHOPTS",
       "SSH options for remote operations. Takes precedence over NIX_SSHOPTS. \
-       Accepts the same format as NIX_SSHOPTS.",
+       Accepts the same format as NIX_SSHOPTS. Overridden by --ssh-opts.",
+    ),
+    (
+      "NH_DIFF_TOOL",
+      "Command that package diffs are piped into instead of being printed, \
+       e.g. `less -R`. Same as --diff-tool.",
     ),
     (
       "NH_SUDOOPTS",